@@ -1,11 +1,20 @@
 use anyhow::{Context, Result};
-use chequer_common::{Message, LatencyResults, TestResults, TestConfig};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use chequer_common::{Message, LatencyResults, HostResults, LinkCapacityResults, PortCheckResults, PreciseTiming, ReportConfig, TestResults, TestConfig};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tracing::{info, debug};
-use chrono::Utc;
-use std::time::Instant;
-use std::os::unix::io::AsRawFd;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, debug, warn};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::os::unix::io::{AsRawFd, RawFd};
+use socket2::SockRef;
+use crate::probe::{self, UdpProber};
+use crate::traffic::{self, OwnCounters, TrafficMonitor};
+use crate::{clocks, loss, mtu, netif, ports, power, precise, pressure, qos, schedule, sweep, sysenv, tcpinfo, timestamping, video, wakeup};
 use crossterm::{
     style::{Color, ResetColor, SetForegroundColor},
     ExecutableCommand,
@@ -16,6 +25,8 @@ use std::io::stdout;
 pub struct Client {
     host_addr: String,
     config: TestConfig,
+    report_config: ReportConfig,
+    counters: Arc<OwnCounters>,
 }
 
 impl Client {
@@ -24,6 +35,8 @@ impl Client {
         Self {
             host_addr,
             config: TestConfig::default(),
+            report_config: ReportConfig::default(),
+            counters: Arc::default(),
         }
     }
 
@@ -33,38 +46,166 @@ impl Client {
         self
     }
 
+    /// Report settings sent to the host with the results, so both print the same report
+    pub fn with_report_config(mut self, report_config: ReportConfig) -> Self {
+        self.report_config = report_config;
+        self
+    }
+
+    /// Count the run's own traffic in `counters`, e.g. to include the discovery that found the host
+    pub fn with_counters(mut self, counters: Arc<OwnCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
     /// Connect to host and run all diagnostics
     pub async fn run(&self) -> Result<TestResults> {
+        // Interface counters before, between and after the tests, to spot other traffic on the network
+        let mut traffic = self.config.traffic_check.then(|| TrafficMonitor::start(Arc::clone(&self.counters)));
+        // Battery, clocks and temperatures from the start, since the Deck changes behaviour with them
+        let power_sampler = power::PowerSampler::start();
+
+        // Local scheduling delay, measured before test traffic competes for the CPU
+        let system = if self.config.scheduling_test {
+            let duration = Duration::from_secs(self.config.scheduling_test_secs);
+            Some(tokio::task::spawn_blocking(move || wakeup::run_wakeup_test(duration, wakeup::WAKEUP_INTERVAL)).await?)
+        } else {
+            None
+        };
+        // The scheduling test sends nothing, so it doubles as the quiet window before the tests
+        if let Some(monitor) = traffic.as_mut() {
+            if system.is_none() {
+                tokio::time::sleep(traffic::QUIET_WINDOW).await;
+            }
+            monitor.checkpoint("before");
+        }
+        
         info!("Connecting to host at {}...", self.host_addr);
         
         let mut socket = TcpStream::connect(&self.host_addr).await?;
         socket.set_nodelay(true)?;
+        if let Some(monitor) = traffic.as_mut() {
+            monitor.set_connection(socket.as_raw_fd(), socket.peer_addr()?);
+        }
         
         // Disable TCP delayed ACK on Linux (TCP_QUICKACK)
-        #[cfg(target_os = "linux")]
-        unsafe {
-            let fd = socket.as_raw_fd();
-            let tcp_quickack: libc::c_int = 1;
-            libc::setsockopt(
-                fd,
-                libc::IPPROTO_TCP,
-                libc::TCP_QUICKACK,
-                &tcp_quickack as *const _ as *const libc::c_void,
-                std::mem::size_of_val(&tcp_quickack) as libc::socklen_t,
-            );
-        }
+        enable_quickack(socket.as_raw_fd());
         
         info!("Connected successfully");
+        
+        // Sample CPU load and stalls throughout, to tell local pressure apart from the network
+        let sampler = pressure::PressureSampler::start();
+        // Watch for suspends and clock steps, which make samples across them meaningless
+        let clock_watcher = clocks::ClockWatcher::start();
 
         // Run latency test
-        let latency = self.run_latency_test(&mut socket).await?;
+        let mut latency = self.run_latency_test(&mut socket).await?;
+        end_phase(&mut traffic, "latency", true);
+        
+        // The UDP tests below all need the host's echo; check it once rather than letting each time out
+        let udp_tests = self.config.kernel_timestamps || self.config.packet_size_sweep || self.config.mtu_discovery
+            || self.config.loss_test || self.config.qos_test;
+        let udp_echo = udp_tests && probe::echo_reachable(socket.peer_addr()?, &self.counters).await;
+        if udp_tests && !udp_echo {
+            warn!("No UDP echo from {}; skipping the kernel RTT, packet size sweep, MTU, loss and QoS tests", socket.peer_addr()?);
+        }
+
+        // Time UDP probes in the kernel too, separating the path from local scheduling
+        let kernel_rtt = if self.config.kernel_timestamps && udp_echo {
+            let interval = Duration::from_millis(self.config.latency_interval_ms.max(1));
+            timestamping::run_kernel_rtt_test(socket.peer_addr()?, self.config.latency_samples, interval, &self.counters).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "kernel RTT", self.config.kernel_timestamps && udp_echo);
+
+        // Run packet size sweep over UDP
+        let link_capacity = if self.config.packet_size_sweep && udp_echo {
+            self.run_packet_size_sweep(socket.peer_addr()?).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "packet size sweep", self.config.packet_size_sweep && udp_echo);
+        
+        // Find the largest UDP payload that passes unfragmented
+        let mtu = if self.config.mtu_discovery && udp_echo {
+            mtu::run_mtu_discovery(socket.peer_addr()?, &self.counters).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "MTU discovery", self.config.mtu_discovery && udp_echo);
+        
+        // Record which probes of a fixed-rate UDP stream are lost
+        let loss = if self.config.loss_test && udp_echo {
+            loss::run_loss_test(socket.peer_addr()?, self.config.loss_probes, self.config.loss_interval_ms, &self.counters).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "loss", self.config.loss_test && udp_echo);
+        
+        // Check whether DSCP markings survive the path
+        let qos = if self.config.qos_test && udp_echo {
+            qos::run_qos_test(socket.peer_addr()?, self.config.qos_probes_per_class, &self.counters).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "QoS", self.config.qos_test && udp_echo);
+        
+        // Check that the Remote Play ports get through
+        let ports = if self.config.port_check && !self.config.ports.is_empty() {
+            self.run_port_check(&mut socket).await?
+        } else {
+            None
+        };
+        end_phase(&mut traffic, "port check", ports.is_some());
+        let client_traffic = match traffic {
+            Some(mut monitor) => {
+                tokio::time::sleep(traffic::QUIET_WINDOW).await;
+                monitor.checkpoint("after");
+                Some(monitor.finish())
+            }
+            None => None,
+        };
+        
+        // Describe the interfaces carrying the traffic, the machine and its decoders, then collect the host's side
+        let local_ip = socket.local_addr()?.ip();
+        let (client_network, client_environment, video) = tokio::task::spawn_blocking(move || {
+            (netif::probe(Some(local_ip)), sysenv::probe(), video::probe())
+        }).await?;
+        let host = self.request_host_results(&mut socket).await?;
+        let client_pressure = sampler.finish();
+        let client_clock_events = clock_watcher.finish();
+        let client_power = power_sampler.finish();
+        // Host events are on the host's clock; without an offset to move them onto ours they cannot be placed
+        let host_clock_events: Vec<_> = match latency.host_clock_offset_ms {
+            Some(offset) => host.clock_events.iter().map(|event| event.shifted(offset)).collect(),
+            None => Vec::new(),
+        };
+        let clock_events: Vec<_> = client_clock_events.iter().chain(&host_clock_events).cloned().collect();
+        if !clock_events.is_empty() {
+            warn!("{} suspend or clock step event(s) during the tests; excluding the samples across them", clock_events.len());
+            latency = latency.with_clock_events(&clock_events);
+        }
         
         // Send results to host
         let results = TestResults {
             latency: Some(latency),
-            bandwidth: None,
-            video: None,
-            audio: None,
+            video,
+            link_capacity,
+            mtu,
+            loss,
+            qos,
+            ports,
+            client_network: Some(client_network),
+            client_environment: Some(client_environment),
+            host: Some(host),
+            client_pressure: Some(client_pressure),
+            client_clock_events,
+            client_traffic,
+            client_power: Some(client_power),
+            kernel_rtt,
+            system,
+            ..TestResults::default()
         };
 
         self.send_results(&mut socket, &results).await?;
@@ -73,86 +214,104 @@ impl Client {
     }
 
     async fn run_latency_test(&self, socket: &mut TcpStream) -> Result<LatencyResults> {
-        info!("Running latency test ({} samples)...", self.config.latency_samples);
+        let schedule = self.config.probe_schedule;
+        info!("Running latency test ({} samples, {} warm-up, {} schedule)...",
+              self.config.latency_samples, self.config.latency_warmup_samples, schedule);
         
-        let mut samples = Vec::with_capacity(self.config.latency_samples);
-        let mut stdout = stdout();
+        let total = self.config.latency_warmup_samples + self.config.latency_samples;
+        let mut progress = ProgressDisplay::start(total, self.config.latency_warmup_samples)?;
         
-        // Show progress header
-        stdout.execute(SetForegroundColor(Color::Cyan))?;
-        println!("\n┌─ Running Latency Test ────────────────────────────────┐");
-        stdout.execute(ResetColor)?;
+        let tcp_before = tcpinfo::read_tcp_info(socket.as_raw_fd());
+        let offsets = schedule::send_offsets(schedule, self.config.latency_interval_ms, total)?;
+        let started_at_ms = pressure::now_utc_ms();
+        let (probes, precise_timing) = if self.config.precise {
+            let (probes, timing) = self.probe_precise(socket, offsets, total).await?;
+            // The probe thread does no terminal output, so progress is shown afterwards
+            for probe in &probes {
+                progress.update(probe.seq as usize, probe.rtt_ms)?;
+            }
+            (probes, Some(timing))
+        } else {
+            let probes = match offsets {
+                Some(offsets) => self.probe_pipelined(socket, &offsets, &mut progress).await?,
+                None => self.probe_sequential(socket, total, &mut progress).await?,
+            };
+            (probes, None)
+        };
+        progress.finish()?;
+        let tcp_after = tcpinfo::read_tcp_info(socket.as_raw_fd());
+        
+        let warmup_recorded = probes.iter()
+            .filter(|p| (p.seq as usize) < self.config.latency_warmup_samples)
+            .count();
+        let host_clock_offset = estimate_clock_offset(&probes);
+        let samples = probes.iter().map(|p| p.rtt_ms).collect();
+        let send_offsets = probes.iter().map(|p| p.send_offset_ms).collect();
+        
+        let mut latency = LatencyResults::from_samples(samples, 0.0) // No packet loss in TCP
+            .with_send_offsets(send_offsets)
+            .with_start_time(started_at_ms)
+            .with_warmup(warmup_recorded)
+            .with_schedule(schedule);
+        if let Some(threshold) = self.config.latency_outlier_threshold {
+            latency = latency.with_outlier_filter(threshold);
+        }
+        if let (Some(before), Some(after)) = (tcp_before, tcp_after) {
+            latency = latency.with_tcp_info(before, after);
+        }
+        if let Some(timing) = precise_timing {
+            latency = latency.with_precise_timing(timing);
+        }
+        if let Some(offset) = host_clock_offset {
+            debug!("Host clock is {:+.2}ms off the client's", offset);
+            latency = latency.with_host_clock_offset(offset);
+        }
+        if let Some(tcp) = &latency.tcp_info {
+            info!("Kernel RTT: {:.2}ms ± {:.2}ms, {} retransmits during the test",
+                  tcp.after.rtt_ms, tcp.after.rttvar_ms, tcp.retransmits());
+        }
+        
+        info!("Latency test complete - Min: {:.2}ms, Max: {:.2}ms, Avg: {:.2}ms, P99: {:.2}ms, Jitter: {:.2}ms ({} warm-up, {} outliers excluded)",
+              latency.min_ms, latency.max_ms, latency.avg_ms, latency.p99_ms, latency.jitter_ms,
+              latency.warmup_samples, latency.outlier_indices.len());
+        
+        Ok(latency)
+    }
+
+    /// One probe in flight: wait for each reply, then sleep the configured interval
+    async fn probe_sequential(
+        &self,
+        socket: &mut TcpStream,
+        total: usize,
+        progress: &mut ProgressDisplay,
+    ) -> Result<Vec<ProbeSample>> {
+        let mut probes = Vec::with_capacity(total);
+        let test_start = Instant::now();
         
         // Pure measurement loop
-        for i in 0..self.config.latency_samples {
+        for i in 0..total {
             let timestamp = Utc::now();
-            let ping = Message::Ping { timestamp };
+            let ping = Message::Ping { timestamp, seq: i as u64 };
             
             send_message(socket, &ping).await?;
-            
-            // Re-enable TCP_QUICKACK before each receive (it gets cleared after every recv)
-            #[cfg(target_os = "linux")]
-            unsafe {
-                use std::os::unix::io::AsRawFd;
-                let fd = socket.as_raw_fd();
-                let tcp_quickack: libc::c_int = 1;
-                libc::setsockopt(
-                    fd,
-                    libc::IPPROTO_TCP,
-                    libc::TCP_QUICKACK,
-                    &tcp_quickack as *const _ as *const libc::c_void,
-                    std::mem::size_of_val(&tcp_quickack) as libc::socklen_t,
-                );
-            }
+            enable_quickack(socket.as_raw_fd());
             
             let start = Instant::now();
             let response = receive_message(socket).await?;
             let elapsed = start.elapsed().as_secs_f64() * 1000.0;
             
             match response {
-                Message::Pong { timestamp: recv_timestamp } => {
+                Message::Pong { timestamp: recv_timestamp, host_time, .. } => {
                     if recv_timestamp == timestamp {
-                        samples.push(elapsed);
+                        probes.push(ProbeSample {
+                            seq: i as u64,
+                            send_offset_ms: start.duration_since(test_start).as_secs_f64() * 1000.0,
+                            rtt_ms: elapsed,
+                            host_clock_offset_ms: clock_offset_ms(timestamp, host_time, elapsed),
+                        });
                         
                         // Update progress display AFTER measurement
-                        let progress = (i + 1) as f64 / self.config.latency_samples as f64;
-                        let bar_width = 40;
-                        let filled = (progress * bar_width as f64) as usize;
-                        let current_avg = samples.iter().sum::<f64>() / samples.len() as f64;
-                        
-                        // Color code the latency
-                        let latency_color = if elapsed < 20.0 {
-                            Color::Green
-                        } else if elapsed < 50.0 {
-                            Color::Yellow
-                        } else {
-                            Color::Red
-                        };
-                        
-                        use crossterm::cursor;
-                        stdout.execute(cursor::MoveToColumn(0))?;
-                        print!("│ Progress: [");
-                        stdout.execute(SetForegroundColor(Color::Green))?;
-                        print!("{}", "█".repeat(filled));
-                        stdout.execute(ResetColor)?;
-                        print!("{}", "░".repeat(bar_width - filled));
-                        print!("] {:3}%", (progress * 100.0) as u8);
-                        
-                        stdout.execute(cursor::MoveToColumn(0))?;
-                        stdout.execute(cursor::MoveDown(1))?;
-                        print!("│ Sample {}/{}: ", i + 1, self.config.latency_samples);
-                        stdout.execute(SetForegroundColor(latency_color))?;
-                        print!("{:.2}ms", elapsed);
-                        stdout.execute(ResetColor)?;
-                        print!(" │ Avg: {:.2}ms", current_avg);
-                        
-                        if i < self.config.latency_samples - 1 {
-                            stdout.execute(cursor::MoveUp(1))?;
-                        }
-                        use std::io::Write;
-                        stdout.flush()?;
-                        
-                        debug!("Sample {}/{}: {:.2}ms", i + 1, self.config.latency_samples, elapsed);
+                        progress.update(i, elapsed)?;
                     }
                 }
                 _ => {
@@ -160,54 +319,429 @@ impl Client {
                 }
             }
             
-            if i < self.config.latency_samples - 1 {
+            if i < total - 1 {
                 tokio::time::sleep(tokio::time::Duration::from_millis(self.config.latency_interval_ms)).await;
             }
         }
         
-        // Clear progress and show completion
-        use crossterm::{cursor, terminal};
-        stdout.execute(cursor::MoveToColumn(0))?;
-        stdout.execute(cursor::MoveDown(1))?;
-        stdout.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
-        stdout.execute(SetForegroundColor(Color::Cyan))?;
-        println!("└───────────────────────────────────────────────────────┘");
-        stdout.execute(ResetColor)?;
+        Ok(probes)
+    }
+
+    /// Send probes at precomputed offsets while replies are collected concurrently
+    async fn probe_pipelined(
+        &self,
+        socket: &mut TcpStream,
+        offsets: &[Duration],
+        progress: &mut ProgressDisplay,
+    ) -> Result<Vec<ProbeSample>> {
+        let fd = socket.as_raw_fd();
+        let (mut reader, mut writer) = socket.split();
+        let (sent_tx, mut sent_rx) = mpsc::unbounded_channel::<(u64, Instant)>();
+        let test_start = Instant::now();
+        
+        let sender = async move {
+            for (seq, offset) in offsets.iter().enumerate() {
+                tokio::time::sleep_until((test_start + *offset).into()).await;
+                let ping = Message::Ping { timestamp: Utc::now(), seq: seq as u64 };
+                let sent_at = Instant::now();
+                send_message(&mut writer, &ping).await?;
+                // The receiver only hangs up after failing, and its error is the one reported
+                let _ = sent_tx.send((seq as u64, sent_at));
+            }
+            Ok::<_, anyhow::Error>(())
+        };
+        
+        let receiver = async {
+            let mut in_flight: HashMap<u64, Instant> = HashMap::new();
+            let mut probes = Vec::with_capacity(offsets.len());
+            
+            while probes.len() < offsets.len() {
+                enable_quickack(fd);
+                let response = receive_message(&mut reader).await?;
+                let received_at = Instant::now();
+                
+                let Message::Pong { seq, timestamp, host_time } = response else {
+                    return Err(anyhow::anyhow!("Expected Pong, got unexpected message"));
+                };
+                // The reply can be read before the sender has recorded its send time
+                while !in_flight.contains_key(&seq) {
+                    let (sent_seq, sent_at) = sent_rx.recv().await
+                        .ok_or_else(|| anyhow::anyhow!("Pong for unknown probe {}", seq))?;
+                    in_flight.insert(sent_seq, sent_at);
+                }
+                let sent_at = in_flight.remove(&seq).unwrap();
+                let rtt_ms = received_at.duration_since(sent_at).as_secs_f64() * 1000.0;
+                
+                progress.update(seq as usize, rtt_ms)?;
+                probes.push(ProbeSample {
+                    seq,
+                    send_offset_ms: sent_at.duration_since(test_start).as_secs_f64() * 1000.0,
+                    rtt_ms,
+                    host_clock_offset_ms: clock_offset_ms(timestamp, host_time, rtt_ms),
+                });
+            }
+            
+            probes.sort_by_key(|p| p.seq);
+            Ok(probes)
+        };
+        
+        let ((), probes) = tokio::try_join!(sender, receiver)?;
+        Ok(probes)
+    }
+
+    /// Run the probes on a dedicated pinned thread that busy-polls the socket
+    async fn probe_precise(
+        &self,
+        socket: &TcpStream,
+        offsets: Option<Vec<Duration>>,
+        total: usize,
+    ) -> Result<(Vec<ProbeSample>, PreciseTiming)> {
+        // The duplicate shares the runtime's nonblocking file description, which is what busy-polling needs
+        let stream: std::net::TcpStream = SockRef::from(socket).try_clone()?.into();
+        let interval = Duration::from_millis(self.config.latency_interval_ms);
+        let realtime = self.config.realtime;
+        
+        let (result_tx, result_rx) = oneshot::channel();
+        std::thread::Builder::new()
+            .name("chequer-probe".to_string())
+            .spawn(move || {
+                let timing = precise::setup_current_thread(realtime);
+                let probes = probe_busy_poll(stream, offsets.as_deref(), interval, total);
+                let _ = result_tx.send(probes.map(|probes| (probes, timing)));
+            })?;
+        
+        let (probes, timing) = result_rx.await
+            .map_err(|_| anyhow::anyhow!("Precise probe thread exited without a result"))??;
+        info!("Precise probes ran on CPU {:?} with SCHED_FIFO priority {:?}{}",
+              timing.pinned_cpu, timing.fifo_priority,
+              if timing.denied.is_empty() { String::new() } else { format!(" (denied: {})", timing.denied.join("; ")) });
+        Ok((probes, timing))
+    }
+
+    async fn run_packet_size_sweep(&self, host: SocketAddr) -> Result<Option<LinkCapacityResults>> {
+        let mut prober = UdpProber::connect(host, &self.counters).await?;
+        let results = sweep::run_packet_size_sweep(&mut prober, self.config.sweep_probes_per_size).await?;
+        
+        // A host without UDP echo (or a firewall dropping it) gives nothing to analyze
+        if results.points.iter().all(|p| p.min_rtt_ms.is_none()) {
+            warn!("No UDP echoes received from {}; skipping link capacity results", host);
+            return Ok(None);
+        }
+        Ok(Some(results))
+    }
+
+    async fn run_port_check(&self, socket: &mut TcpStream) -> Result<Option<PortCheckResults>> {
+        let request = Message::PortCheckRequest { ports: self.config.ports.clone() };
+        send_message(socket, &request).await?;
         
-        // Calculate statistics
-        let min_ms = samples.iter().cloned().fold(f64::INFINITY, f64::min);
-        let max_ms = samples.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-        let avg_ms = samples.iter().sum::<f64>() / samples.len() as f64;
+        let states = match receive_message(socket).await? {
+            Message::PortCheckReady { states } => states,
+            Message::Error { message } => {
+                warn!("Host {}; skipping the port check", message);
+                return Ok(None);
+            }
+            _ => return Err(anyhow::anyhow!("Expected PortCheckReady, got unexpected message")),
+        };
         
-        // Calculate jitter (standard deviation)
-        let variance = samples.iter()
-            .map(|&x| (x - avg_ms).powi(2))
-            .sum::<f64>() / samples.len() as f64;
-        let jitter_ms = variance.sqrt();
+        let results = ports::check_ports(socket.peer_addr()?.ip(), &self.config.ports, states, &self.counters).await;
         
-        info!("Latency test complete - Min: {:.2}ms, Max: {:.2}ms, Avg: {:.2}ms, Jitter: {:.2}ms",
-              min_ms, max_ms, avg_ms, jitter_ms);
+        // Close the host's listeners rather than wait for them to time out
+        send_message(socket, &Message::PortCheckDone).await?;
         
-        Ok(LatencyResults {
-            min_ms,
-            max_ms,
-            avg_ms,
-            jitter_ms,
-            packet_loss_percent: 0.0, // No packet loss in TCP
-            samples,
-        })
+        Ok(Some(PortCheckResults { ports: results }))
+    }
+
+    async fn request_host_results(&self, socket: &mut TcpStream) -> Result<HostResults> {
+        send_message(socket, &Message::HostResultsRequest).await?;
+        
+        match receive_message(socket).await? {
+            Message::HostResults { results } => Ok(*results),
+            _ => Err(anyhow::anyhow!("Expected HostResults, got unexpected message")),
+        }
     }
 
     async fn send_results(&self, socket: &mut TcpStream, results: &TestResults) -> Result<()> {
         info!("Sending results to host");
         let message = Message::TestResults { 
-            results: results.clone() 
+            results: Box::new(results.clone()),
+            report_config: self.report_config.clone(),
         };
         send_message(socket, &message).await
     }
 }
 
-async fn send_message(socket: &mut TcpStream, message: &Message) -> Result<()> {
+/// A single round-trip measurement
+struct ProbeSample {
+    seq: u64,
+    send_offset_ms: f64,
+    rtt_ms: f64,
+    /// Host clock minus client clock as seen by this probe, if the host stamped its reply
+    host_clock_offset_ms: Option<f64>,
+}
+
+/// Host clock minus client clock, taking the host's stamp to fall at the midpoint of the round trip
+fn clock_offset_ms(sent: DateTime<Utc>, host_time: Option<DateTime<Utc>>, rtt_ms: f64) -> Option<f64> {
+    let host_ms = (host_time? - sent).num_microseconds()? as f64 / 1000.0;
+    Some(host_ms - rtt_ms / 2.0)
+}
+
+/// Clock offset seen by the fastest probe, the one least skewed by queueing on either leg
+fn estimate_clock_offset(probes: &[ProbeSample]) -> Option<f64> {
+    probes.iter()
+        .filter(|p| p.host_clock_offset_ms.is_some())
+        .min_by(|a, b| a.rtt_ms.total_cmp(&b.rtt_ms))?
+        .host_clock_offset_ms
+}
+
+/// Give up on a precise run when neither a probe nor a reply happened for this long
+const PRECISE_STALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long before each send the precise loop stops sleeping and spins, covering the timer's wake-up latency
+const SPIN_WINDOW: Duration = Duration::from_micros(100);
+
+/// Busy-polling probe loop for `--precise`
+///
+/// Sends each probe at its scheduled offset, or `interval` after the previous
+/// reply without offsets. It spins on nonblocking reads while probes are in
+/// flight, and sleeps until just before the next send while none are.
+fn probe_busy_poll(
+    mut stream: std::net::TcpStream,
+    offsets: Option<&[Duration]>,
+    interval: Duration,
+    total: usize,
+) -> Result<Vec<ProbeSample>> {
+    let fd = stream.as_raw_fd();
+    let mut sent_at: Vec<Instant> = Vec::with_capacity(total);
+    let mut probes = Vec::with_capacity(total);
+    let mut pending: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 4096];
+    
+    let test_start = Instant::now();
+    let mut next_send = Some(test_start);
+    let mut last_activity = test_start;
+    
+    while probes.len() < total {
+        let sent = sent_at.len();
+        let due = match offsets {
+            Some(offsets) => offsets.get(sent).map(|&offset| test_start + offset),
+            None => next_send.filter(|_| sent < total),
+        };
+        if due.is_some_and(|due| Instant::now() >= due) {
+            let ping = Message::Ping { timestamp: Utc::now(), seq: sent as u64 };
+            let now = Instant::now();
+            write_message_blocking(&mut stream, &ping)?;
+            sent_at.push(now);
+            next_send = None;
+            last_activity = now;
+        }
+        
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err(anyhow::anyhow!("Host closed the connection")),
+            Ok(len) => {
+                let received_at = Instant::now();
+                enable_quickack(fd);
+                pending.extend_from_slice(&chunk[..len]);
+                
+                while let Some(message) = take_message(&mut pending)? {
+                    let Message::Pong { seq, timestamp, host_time } = message else {
+                        return Err(anyhow::anyhow!("Expected Pong, got unexpected message"));
+                    };
+                    let sent = *sent_at.get(seq as usize)
+                        .ok_or_else(|| anyhow::anyhow!("Pong for unknown probe {}", seq))?;
+                    let rtt_ms = received_at.duration_since(sent).as_secs_f64() * 1000.0;
+                    probes.push(ProbeSample {
+                        seq,
+                        send_offset_ms: sent.duration_since(test_start).as_secs_f64() * 1000.0,
+                        rtt_ms,
+                        host_clock_offset_ms: clock_offset_ms(timestamp, host_time, rtt_ms),
+                    });
+                    next_send = Some(received_at + interval);
+                }
+                last_activity = received_at;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if last_activity.elapsed() > PRECISE_STALL_TIMEOUT {
+                    return Err(anyhow::anyhow!("Host stopped answering probes"));
+                }
+                // With nothing in flight there is no reply to catch until the next send
+                if let Some(due) = due.filter(|_| sent_at.len() == probes.len()) {
+                    sleep_until_spin_window(due);
+                }
+                std::hint::spin_loop();
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    
+    probes.sort_by_key(|p| p.seq);
+    Ok(probes)
+}
+
+/// Live progress box shown while the latency test runs
+struct ProgressDisplay {
+    stdout: std::io::Stdout,
+    total: usize,
+    warmup: usize,
+    received: usize,
+    sum_ms: f64,
+}
+
+impl ProgressDisplay {
+    fn start(total: usize, warmup: usize) -> Result<Self> {
+        let mut stdout = stdout();
+        
+        // Show progress header
+        stdout.execute(SetForegroundColor(Color::Cyan))?;
+        println!("\n┌─ Running Latency Test ────────────────────────────────┐");
+        stdout.execute(ResetColor)?;
+        
+        Ok(Self { stdout, total, warmup, received: 0, sum_ms: 0.0 })
+    }
+
+    fn update(&mut self, index: usize, elapsed: f64) -> Result<()> {
+        use crossterm::cursor;
+        use std::io::Write;
+        
+        self.received += 1;
+        self.sum_ms += elapsed;
+        let progress = self.received as f64 / self.total as f64;
+        let bar_width = 40;
+        let filled = (progress * bar_width as f64) as usize;
+        let current_avg = self.sum_ms / self.received as f64;
+        
+        // Color code the latency
+        let latency_color = if elapsed < 20.0 {
+            Color::Green
+        } else if elapsed < 50.0 {
+            Color::Yellow
+        } else {
+            Color::Red
+        };
+        
+        let stdout = &mut self.stdout;
+        stdout.execute(cursor::MoveToColumn(0))?;
+        print!("│ Progress: [");
+        stdout.execute(SetForegroundColor(Color::Green))?;
+        print!("{}", "█".repeat(filled));
+        stdout.execute(ResetColor)?;
+        print!("{}", "░".repeat(bar_width - filled));
+        print!("] {:3}%", (progress * 100.0) as u8);
+        
+        stdout.execute(cursor::MoveToColumn(0))?;
+        stdout.execute(cursor::MoveDown(1))?;
+        let phase = if index < self.warmup { " (warm-up)" } else { "" };
+        print!("│ Sample {}/{}{}: ", index + 1, self.total, phase);
+        stdout.execute(SetForegroundColor(latency_color))?;
+        print!("{:.2}ms", elapsed);
+        stdout.execute(ResetColor)?;
+        print!(" │ Avg: {:.2}ms", current_avg);
+        
+        if self.received < self.total {
+            stdout.execute(cursor::MoveUp(1))?;
+        }
+        stdout.flush()?;
+        
+        debug!("Sample {}/{}: {:.2}ms", index + 1, self.total, elapsed);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        use crossterm::{cursor, terminal};
+        
+        // Clear progress and show completion
+        self.stdout.execute(cursor::MoveToColumn(0))?;
+        self.stdout.execute(cursor::MoveDown(1))?;
+        self.stdout.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
+        self.stdout.execute(SetForegroundColor(Color::Cyan))?;
+        println!("└───────────────────────────────────────────────────────┘");
+        self.stdout.execute(ResetColor)?;
+        Ok(())
+    }
+}
+
+/// End the traffic phase of a test that ran; a skipped test's moment goes to the next phase
+fn end_phase(traffic: &mut Option<TrafficMonitor>, name: &str, ran: bool) {
+    if let Some(monitor) = traffic.as_mut().filter(|_| ran) {
+        monitor.checkpoint(name);
+    }
+}
+
+/// Sleep until `SPIN_WINDOW` before `deadline`, leaving the rest to the spin loop
+///
+/// On Linux the wake-up is an absolute CLOCK_MONOTONIC deadline, so an
+/// interrupted sleep resumes without drifting.
+fn sleep_until_spin_window(deadline: Instant) {
+    let Some(remaining) = deadline.checked_duration_since(Instant::now()).and_then(|r| r.checked_sub(SPIN_WINDOW)) else {
+        return;
+    };
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let mut now: libc::timespec = std::mem::zeroed();
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now);
+        let nanos = now.tv_nsec as u64 + remaining.subsec_nanos() as u64;
+        let wake = libc::timespec {
+            tv_sec: now.tv_sec + remaining.as_secs() as libc::time_t + (nanos / 1_000_000_000) as libc::time_t,
+            tv_nsec: (nanos % 1_000_000_000) as _,
+        };
+        while libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &wake, std::ptr::null_mut()) == libc::EINTR {}
+    }
+    #[cfg(not(target_os = "linux"))]
+    std::thread::sleep(remaining);
+}
+
+/// Disable TCP delayed ACK on Linux (TCP_QUICKACK); the kernel clears it after every recv
+fn enable_quickack(fd: RawFd) {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let tcp_quickack: libc::c_int = 1;
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_QUICKACK,
+            &tcp_quickack as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&tcp_quickack) as libc::socklen_t,
+        );
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = fd;
+}
+
+/// Write a length-prefixed message to a nonblocking socket, spinning while its buffer is full
+fn write_message_blocking(stream: &mut std::net::TcpStream, message: &Message) -> Result<()> {
+    use std::io::Write;
+    
+    let serialized = serde_json::to_vec(message)?;
+    let mut frame = (serialized.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(&serialized);
+    
+    let mut written = 0;
+    while written < frame.len() {
+        match stream.write(&frame[written..]) {
+            Ok(len) => written += len,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => std::hint::spin_loop(),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Remove and decode the first complete length-prefixed message in `pending`, if any
+fn take_message(pending: &mut Vec<u8>) -> Result<Option<Message>> {
+    if pending.len() < 4 {
+        return Ok(None);
+    }
+    let len = u32::from_be_bytes([pending[0], pending[1], pending[2], pending[3]]) as usize;
+    if pending.len() < 4 + len {
+        return Ok(None);
+    }
+    
+    let message = serde_json::from_slice(&pending[4..4 + len])
+        .context("Failed to deserialize message")?;
+    pending.drain(..4 + len);
+    Ok(Some(message))
+}
+
+async fn send_message<W: AsyncWrite + Unpin>(socket: &mut W, message: &Message) -> Result<()> {
     let serialized = serde_json::to_vec(message)?;
     let len = (serialized.len() as u32).to_be_bytes();
     
@@ -218,7 +752,7 @@ async fn send_message(socket: &mut TcpStream, message: &Message) -> Result<()> {
     Ok(())
 }
 
-async fn receive_message(socket: &mut TcpStream) -> Result<Message> {
+async fn receive_message<R: AsyncRead + Unpin>(socket: &mut R) -> Result<Message> {
     let mut len_buf = [0u8; 4];
     socket.read_exact(&mut len_buf).await?;
     let msg_len = u32::from_be_bytes(len_buf) as usize;
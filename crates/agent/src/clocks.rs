@@ -0,0 +1,153 @@
+/// Suspend and wall-clock step detection from `CLOCK_MONOTONIC`, `CLOCK_BOOTTIME` and `CLOCK_REALTIME`
+use chequer_common::{ClockEvent, ClockEventKind};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Time between clock reads; a discontinuity is placed within one interval
+pub const CLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Disagreement between two clocks over one interval that counts as a discontinuity
+///
+/// NTP slewing changes the wall clock by at most 0.5ms per second, far below this.
+pub const CLOCK_JUMP_THRESHOLD_MS: f64 = 50.0;
+
+/// The three clocks read back to back, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockReading {
+    /// Stops while suspended
+    pub monotonic_ms: f64,
+    /// Keeps counting while suspended
+    pub boottime_ms: f64,
+    /// Wall clock, stepped by NTP or the user
+    pub realtime_ms: f64,
+}
+
+/// Clock that keeps counting through suspend; elsewhere the monotonic clock stands in and suspends go undetected
+#[cfg(target_os = "linux")]
+const BOOTTIME: libc::clockid_t = libc::CLOCK_BOOTTIME;
+#[cfg(not(target_os = "linux"))]
+const BOOTTIME: libc::clockid_t = libc::CLOCK_MONOTONIC;
+
+impl ClockReading {
+    /// Read the clocks now
+    pub fn now() -> Self {
+        let read = |clock: libc::clockid_t| {
+            let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+            unsafe { libc::clock_gettime(clock, &mut ts) };
+            ts.tv_sec as f64 * 1000.0 + ts.tv_nsec as f64 / 1e6
+        };
+        Self {
+            monotonic_ms: read(libc::CLOCK_MONOTONIC),
+            boottime_ms: read(BOOTTIME),
+            realtime_ms: read(libc::CLOCK_REALTIME),
+        }
+    }
+}
+
+/// Compare two consecutive readings
+///
+/// `timeline_ms` places the earlier reading on the test's UTC timeline, which
+/// advances with the monotonic clock so that it does not jump itself.
+pub fn detect(before: &ClockReading, after: &ClockReading, timeline_ms: f64) -> Vec<ClockEvent> {
+    let monotonic = after.monotonic_ms - before.monotonic_ms;
+    let slept = (after.boottime_ms - before.boottime_ms) - monotonic;
+    // Time spent asleep moves the wall clock too, so compare it against boot time
+    let stepped = (after.realtime_ms - before.realtime_ms) - (after.boottime_ms - before.boottime_ms);
+
+    let window = |kind, magnitude_ms| ClockEvent { kind, from_ms: timeline_ms, to_ms: timeline_ms + monotonic, magnitude_ms };
+    let mut events = Vec::new();
+    if slept > CLOCK_JUMP_THRESHOLD_MS {
+        events.push(window(ClockEventKind::Suspend, slept));
+    }
+    if stepped.abs() > CLOCK_JUMP_THRESHOLD_MS {
+        events.push(window(ClockEventKind::ClockStep, stepped));
+    }
+    events
+}
+
+/// Background watcher started when a test begins and finished when it ends
+pub struct ClockWatcher {
+    events: Arc<Mutex<Vec<ClockEvent>>>,
+    task: JoinHandle<()>,
+}
+
+impl ClockWatcher {
+    /// Start checking the clocks every `CLOCK_CHECK_INTERVAL`
+    pub fn start() -> Self {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::clone(&events);
+
+        let task = tokio::spawn(async move {
+            let start = ClockReading::now();
+            let mut previous = start;
+            let mut ticker = tokio::time::interval(CLOCK_CHECK_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                let reading = ClockReading::now();
+                let timeline_ms = start.realtime_ms + (previous.monotonic_ms - start.monotonic_ms);
+                shared.lock().unwrap().extend(detect(&previous, &reading, timeline_ms));
+                previous = reading;
+            }
+        });
+
+        Self { events, task }
+    }
+
+    /// Stop watching and return the discontinuities seen
+    pub fn finish(self) -> Vec<ClockEvent> {
+        self.task.abort();
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+}
+
+impl Drop for ClockWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(monotonic_ms: f64, boottime_ms: f64, realtime_ms: f64) -> ClockReading {
+        ClockReading { monotonic_ms, boottime_ms, realtime_ms }
+    }
+
+    #[test]
+    fn test_steady_clocks() {
+        let before = reading(1000.0, 1500.0, 1_700_000_000_000.0);
+        let after = reading(1100.0, 1600.0, 1_700_000_000_100.2);
+        assert!(detect(&before, &after, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_suspend_moves_boottime_and_wall_clock() {
+        let before = reading(1000.0, 1500.0, 1_700_000_000_000.0);
+        let after = reading(1100.0, 31_600.0, 1_700_000_030_100.0);
+        let events = detect(&before, &after, 5000.0);
+
+        assert_eq!(events, vec![ClockEvent { kind: ClockEventKind::Suspend, from_ms: 5000.0, to_ms: 5100.0, magnitude_ms: 30_000.0 }]);
+    }
+
+    #[test]
+    fn test_ntp_step_moves_only_wall_clock() {
+        let before = reading(1000.0, 1500.0, 1_700_000_000_000.0);
+        let after = reading(1100.0, 1600.0, 1_699_999_998_900.0);
+        let events = detect(&before, &after, 5000.0);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, ClockEventKind::ClockStep);
+        assert_eq!(events[0].magnitude_ms, -1200.0);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_sees_no_jumps_in_a_quiet_run() {
+        let watcher = ClockWatcher::start();
+        tokio::time::sleep(CLOCK_CHECK_INTERVAL * 3).await;
+        assert!(watcher.finish().is_empty());
+    }
+}
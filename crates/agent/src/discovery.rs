@@ -0,0 +1,124 @@
+/// LAN discovery of Chequer hosts over UDP broadcast and multicast
+use anyhow::Result;
+use chequer_common::{HostInfo, Message};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+use tracing::debug;
+use crate::traffic::{OwnCounters, PeerCounters};
+
+/// UDP port hosts listen on for discovery probes
+pub const DISCOVERY_PORT: u16 = 7778;
+
+/// Multicast group hosts join, for networks that filter broadcast
+pub const DISCOVERY_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 77, 77);
+
+/// A host that answered a discovery probe
+#[derive(Debug, Clone)]
+pub struct DiscoveredHost {
+    /// Control address to connect to
+    pub address: SocketAddr,
+    pub info: HostInfo,
+}
+
+/// Host side: bind the discovery port, shared with other hosts on the same machine
+pub fn bind_responder(port: u16) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port).into())?;
+    if let Err(e) = socket.join_multicast_v4(&DISCOVERY_GROUP, &Ipv4Addr::UNSPECIFIED) {
+        debug!("Cannot join discovery group {}: {}", DISCOVERY_GROUP, e);
+    }
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+/// Host side: answer every discovery probe with this host's identity
+///
+/// Probes from a connected client are counted in its run.
+pub async fn run_responder(socket: UdpSocket, info: HostInfo, peers: PeerCounters) -> Result<()> {
+    let response = serde_json::to_vec(&Message::DiscoveryResponse { host: info })?;
+    let mut buffer = vec![0u8; 2048];
+
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                debug!("Discovery receive error: {}", e);
+                continue;
+            }
+        };
+
+        if let Ok(Message::DiscoveryRequest { version }) = serde_json::from_slice(&buffer[..len]) {
+            debug!("Discovery probe from {} (version {})", peer, version);
+            let counters = peers.get(peer.ip());
+            if let Some(counters) = &counters {
+                counters.count_udp_received(len, peer);
+            }
+            match socket.send_to(&response, peer).await {
+                Ok(sent) => {
+                    if let Some(counters) = &counters {
+                        counters.count_udp_sent(sent, peer);
+                    }
+                }
+                Err(e) => debug!("Failed to answer discovery probe from {}: {}", peer, e),
+            }
+        }
+    }
+}
+
+/// Client side: broadcast and multicast a probe on `port` and collect answers for `timeout`
+pub async fn discover(port: u16, timeout: Duration, counters: &OwnCounters) -> Result<Vec<DiscoveredHost>> {
+    let targets = [
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), port),
+        SocketAddr::new(IpAddr::V4(DISCOVERY_GROUP), port),
+    ];
+    discover_at(&targets, timeout, counters).await
+}
+
+/// Client side: send a probe to each target and collect answers for `timeout`, counting them in `counters`
+pub async fn discover_at(targets: &[SocketAddr], timeout: Duration, counters: &OwnCounters) -> Result<Vec<DiscoveredHost>> {
+    let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)).await?;
+    SockRef::from(&socket).set_broadcast(true)?;
+
+    let request = serde_json::to_vec(&Message::DiscoveryRequest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })?;
+    for target in targets {
+        // Hosts without a default route cannot broadcast; the other targets may still work
+        match socket.send_to(&request, target).await {
+            Ok(sent) => counters.count_udp_sent(sent, *target),
+            Err(e) => debug!("Cannot send discovery probe to {}: {}", target, e),
+        }
+    }
+
+    let deadline = Instant::now() + timeout;
+    let mut hosts: Vec<DiscoveredHost> = Vec::new();
+    let mut buffer = vec![0u8; 2048];
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+        let (len, peer) = received?;
+        counters.count_udp_received(len, peer);
+        if let Ok(Message::DiscoveryResponse { host }) = serde_json::from_slice(&buffer[..len]) {
+            let address = SocketAddr::new(host.ip.unwrap_or(peer.ip()), host.port);
+            // Broadcast and multicast both reach the same host
+            if !hosts.iter().any(|h| h.address == address) {
+                hosts.push(DiscoveredHost { address, info: host });
+            }
+        }
+    }
+
+    Ok(hosts)
+}
+
+/// This machine's host name
+pub fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if result != 0 {
+        return "unknown".to_string();
+    }
+    let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
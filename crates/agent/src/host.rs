@@ -1,14 +1,31 @@
 use anyhow::{Context, Result};
-use chequer_common::{Message, TestResults};
+use chequer_common::{HostInfo, HostResults, Message, TcpInfoResults, TestResults};
+use chequer_report::DiagnosticReport;
+use chrono::Utc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, UdpSocket};
 use tracing::{info, warn, error};
 use std::sync::Arc;
+use std::os::unix::io::AsRawFd;
 use tokio::sync::Mutex;
+use crate::clocks::ClockWatcher;
+use crate::discovery::{self, DISCOVERY_PORT};
+use crate::netif;
+use crate::ports::PortListeners;
+use crate::pressure::PressureSampler;
+use crate::sysenv;
+use crate::probe;
+use crate::tcpinfo;
+use crate::traffic::{OwnCounters, PeerCounters, TrafficMonitor};
+use crate::video;
 
 /// Host agent that accepts connections from clients and runs diagnostics
 pub struct Host {
     listen_addr: String,
+    discovery_port: Option<u16>,
+    print_reports: bool,
+    any_port: bool,
+    peers: PeerCounters,
     results: Arc<Mutex<Vec<TestResults>>>,
 }
 
@@ -17,10 +34,32 @@ impl Host {
     pub fn new(listen_addr: String) -> Self {
         Self {
             listen_addr,
+            discovery_port: Some(DISCOVERY_PORT),
+            print_reports: false,
+            any_port: false,
+            peers: PeerCounters::default(),
             results: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Set the UDP port for LAN discovery, or `None` to not answer discovery probes
+    pub fn with_discovery_port(mut self, port: Option<u16>) -> Self {
+        self.discovery_port = port;
+        self
+    }
+
+    /// Print the combined report each time a client sends its results
+    pub fn with_report(mut self, print: bool) -> Self {
+        self.print_reports = print;
+        self
+    }
+
+    /// Let clients check any port instead of only Steam Remote Play's
+    pub fn with_any_ports(mut self, allow: bool) -> Self {
+        self.any_port = allow;
+        self
+    }
+
     /// Start the host server and listen for client connections
     pub async fn run(&self) -> Result<()> {
         let listener = TcpListener::bind(&self.listen_addr)
@@ -28,6 +67,50 @@ impl Host {
             .context("Failed to bind to address")?;
         
         info!("Host listening on {}", self.listen_addr);
+        
+        let mut capabilities = vec!["latency".to_string(), "port-check".to_string()];
+        
+        // UDP echo service for probe-based tests, on the same port as the control connection
+        match UdpSocket::bind(&self.listen_addr).await {
+            Ok(socket) => {
+                info!("UDP echo listening on {}", self.listen_addr);
+                capabilities.push("udp-echo".to_string());
+                let peers = self.peers.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = probe::run_echo(socket, peers).await {
+                        error!("UDP echo stopped: {}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("UDP echo unavailable, probe tests will fail: {}", e),
+        }
+        
+        let local = listener.local_addr()?;
+        let host_info = HostInfo {
+            name: discovery::host_name(),
+            ip: (!local.ip().is_unspecified()).then_some(local.ip()),
+            port: local.port(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities,
+        };
+        
+        // Answer LAN discovery probes so clients can find this host without an address
+        if let Some(port) = self.discovery_port {
+            match discovery::bind_responder(port) {
+                Ok(socket) => {
+                    info!("Answering discovery probes on UDP port {}", port);
+                    let info = host_info.clone();
+                    let peers = self.peers.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = discovery::run_responder(socket, info, peers).await {
+                            error!("Discovery responder stopped: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("LAN discovery unavailable: {}", e),
+            }
+        }
+        
         info!("Waiting for client connections...");
 
         loop {
@@ -36,9 +119,13 @@ impl Host {
                     socket.set_nodelay(true).ok();
                     info!("Client connected from {}", addr);
                     let results = Arc::clone(&self.results);
+                    let info = host_info.clone();
+                    let print_reports = self.print_reports;
+                    let any_port = self.any_port;
+                    let peers = self.peers.clone();
                     
                     tokio::spawn(async move {
-                        if let Err(e) = handle_client(socket, results).await {
+                        if let Err(e) = handle_client(socket, info, print_reports, any_port, peers, results).await {
                             error!("Error handling client {}: {}", addr, e);
                         }
                     });
@@ -58,16 +145,34 @@ impl Host {
 
 async fn handle_client(
     mut socket: tokio::net::TcpStream,
+    info: HostInfo,
+    print_reports: bool,
+    any_port: bool,
+    peers: PeerCounters,
     results: Arc<Mutex<Vec<TestResults>>>,
 ) -> Result<()> {
     let mut buffer = vec![0u8; 8192];
+    let mut port_listeners: Option<PortListeners> = None;
+    let tcp_info_at_accept = tcpinfo::read_tcp_info(socket.as_raw_fd());
+    let mut sampler = Some(PressureSampler::start());
+    let mut clock_watcher = Some(ClockWatcher::start());
+    // This client's share of the shared sockets' traffic, counted while it stays connected
+    let peer = socket.peer_addr()?;
+    let counters = Arc::new(OwnCounters::default());
+    let _registration = peers.register(peer.ip(), Arc::clone(&counters));
+    let mut monitor = TrafficMonitor::start(counters);
+    monitor.set_connection(socket.as_raw_fd(), peer);
+    let mut traffic = Some(monitor);
 
     loop {
-        // Read message length (4 bytes)
-        let n = socket.read(&mut buffer[..4]).await?;
-        if n == 0 {
-            info!("Client disconnected");
-            break;
+        // Read message length (4 bytes); pipelined probes can split it across reads
+        match socket.read_exact(&mut buffer[..4]).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                info!("Client disconnected");
+                break;
+            }
+            Err(e) => return Err(e.into()),
         }
 
         let msg_len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
@@ -83,18 +188,69 @@ async fn handle_client(
             .context("Failed to deserialize message")?;
 
         match message {
-            Message::Ping { timestamp } => {
+            Message::Ping { timestamp, seq } => {
                 // Echo back as Pong
-                let response = Message::Pong { timestamp };
+                let response = Message::Pong { timestamp, seq, host_time: Some(Utc::now()) };
+                send_message(&mut socket, &response).await?;
+            }
+            Message::PortCheckRequest { ports } => {
+                info!("Opening {} temporary listeners for the port check", ports.len());
+                let local_ip = socket.local_addr()?.ip();
+                let response = match PortListeners::open(&ports, any_port, local_ip, &peers).await {
+                    Ok(listeners) => {
+                        let response = Message::PortCheckReady { states: listeners.states.clone() };
+                        port_listeners = Some(listeners);
+                        response
+                    }
+                    Err(e) => {
+                        warn!("Refusing port check: {}", e);
+                        Message::Error { message: format!("port check refused: {}", e) }
+                    }
+                };
                 send_message(&mut socket, &response).await?;
             }
-            Message::TestResults { results: test_results } => {
+            Message::PortCheckDone => {
+                // Dropping the listeners closes the ports again
+                if let Some(listeners) = port_listeners.take() {
+                    info!("Closing {} temporary listeners", listeners.states.len());
+                }
+            }
+            Message::HostResultsRequest => {
+                let local_ip = socket.local_addr().ok().map(|addr| addr.ip());
+                let (environment, network, video) = tokio::task::spawn_blocking(move || {
+                    (sysenv::probe(), netif::probe(local_ip), video::probe())
+                }).await?;
+                let tcp_info = tcp_info_at_accept
+                    .zip(tcpinfo::read_tcp_info(socket.as_raw_fd()))
+                    .map(|(before, after)| TcpInfoResults { before, after });
+                let pressure = sampler.take().map(PressureSampler::finish);
+                let clock_events = clock_watcher.take().map(ClockWatcher::finish).unwrap_or_default();
+                let traffic = traffic.take().map(|mut monitor| {
+                    monitor.checkpoint("during");
+                    monitor.finish()
+                });
+                let host_results = HostResults { info: info.clone(), environment, network, tcp_info, pressure, clock_events, traffic, video };
+                send_message(&mut socket, &Message::HostResults { results: Box::new(host_results) }).await?;
+            }
+            Message::TestResults { results: test_results, report_config } => {
                 info!("Received test results from client");
-                results.lock().await.push(test_results);
+                if print_reports {
+                    DiagnosticReport::from_results_with_config((*test_results).clone(), &report_config).print_terminal();
+                }
+                results.lock().await.push(*test_results);
             }
             Message::Pong { .. } => {
                 warn!("Host received unexpected Pong message");
             }
+            Message::PortCheckReady { .. } => {
+                warn!("Host received unexpected PortCheckReady message");
+            }
+            Message::HostResults { .. } => {
+                warn!("Host received unexpected HostResults message");
+            }
+            Message::DiscoveryRequest { .. } | Message::DiscoveryResponse { .. } => {
+                warn!("Host received discovery message on the control connection");
+            }
             Message::Error { message } => {
                 error!("Client reported error: {}", message);
             }
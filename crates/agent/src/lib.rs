@@ -1,6 +1,25 @@
 pub mod client;
+pub mod clocks;
+pub mod discovery;
 pub mod host;
+pub mod loss;
+pub mod mtu;
+pub mod netif;
 pub mod network;
+pub mod ports;
+pub mod power;
+pub mod precise;
+pub mod pressure;
+pub mod probe;
+pub mod qos;
+pub mod schedule;
+pub mod sweep;
+pub mod sysenv;
+pub mod tcpinfo;
+pub mod timestamping;
+pub mod traffic;
+pub mod video;
+pub mod wakeup;
 
 pub use client::Client;
 pub use host::Host;
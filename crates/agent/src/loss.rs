@@ -0,0 +1,75 @@
+/// Fixed-rate UDP stream recording which probes are lost
+use anyhow::Result;
+use chequer_common::LossResults;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+use crate::probe::{probe_packet, probe_seq, UdpProber, MAX_PROBE_SIZE};
+use crate::traffic::OwnCounters;
+
+/// Payload size of a typical video packet
+pub const LOSS_PROBE_SIZE: usize = 1200;
+
+/// Time to wait for late echoes after the last probe is sent
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Send `count` probes `interval_ms` apart and record which echoes never arrive
+///
+/// Returns `None` if no echo arrived at all, e.g. because the host has no
+/// UDP echo or a firewall drops it.
+pub async fn run_loss_test(host: SocketAddr, count: usize, interval_ms: u64, counters: &Arc<OwnCounters>) -> Result<Option<LossResults>> {
+    info!("Running loss test ({} probes every {}ms)...", count, interval_ms);
+
+    let prober = UdpProber::connect(host, counters).await?;
+    let socket = prober.socket();
+    let interval = Duration::from_millis(interval_ms.max(1));
+    let deadline = Instant::now() + interval * count as u32 + DRAIN_TIMEOUT;
+
+    let sender = async {
+        let mut ticker = tokio::time::interval(interval);
+        for seq in 0..count as u32 {
+            ticker.tick().await;
+            counters.count_udp_sent(socket.send(&probe_packet(seq, LOSS_PROBE_SIZE)).await?, host);
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let receiver = async {
+        let mut received = HashSet::with_capacity(count);
+        let mut buffer = vec![0u8; MAX_PROBE_SIZE];
+        while received.len() < count {
+            let len = match tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+                Err(_) => break,
+                // ICMP errors from probes the host did not answer; keep listening
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => continue,
+                Ok(result) => result?,
+            };
+            counters.count_udp_received(len, host);
+            if let Some(seq) = probe_seq(&buffer[..len]) {
+                received.insert(seq);
+            }
+        }
+        Ok::<_, anyhow::Error>(received)
+    };
+
+    let ((), received) = tokio::try_join!(sender, receiver)?;
+    if received.is_empty() {
+        warn!("No UDP echoes received from {}; skipping loss results", host);
+        return Ok(None);
+    }
+
+    let results = LossResults {
+        sent: count,
+        interval_ms: interval.as_secs_f64() * 1000.0,
+        payload_bytes: LOSS_PROBE_SIZE,
+        lost_seqs: (0..count as u32).filter(|seq| !received.contains(seq)).collect(),
+    };
+
+    info!("Loss test complete - {} of {} lost ({:.2}%)",
+          results.lost_seqs.len(), results.sent, results.loss_percent());
+
+    Ok(Some(results))
+}
@@ -1,8 +1,15 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
 use tracing::info;
 use chequer_agent::{Host, Client};
-use chequer_report::DiagnosticReport;
+use chequer_agent::discovery::{self, DISCOVERY_PORT};
+use chequer_agent::{ports, wakeup};
+use chequer_agent::traffic::OwnCounters;
+use chequer_common::{PortSpec, ProbeSchedule, TestConfig, TestResults};
+use chequer_report::{DiagnosticReport, ReportConfig};
 
 #[derive(Parser)]
 #[command(name = "chequer")]
@@ -19,13 +26,127 @@ enum Commands {
         /// Address to listen on
         #[arg(short, long, default_value = "0.0.0.0:7777")]
         listen: String,
+
+        /// UDP port to answer LAN discovery probes on
+        #[arg(long, default_value_t = DISCOVERY_PORT)]
+        discovery_port: u16,
+
+        /// Do not answer LAN discovery probes
+        #[arg(long)]
+        no_discovery: bool,
+
+        /// Let clients check ports other than Steam Remote Play's, including privileged ones
+        #[arg(long)]
+        allow_any_ports: bool,
     },
     /// Run as client (Steam Deck)
     Client {
         /// Host address to connect to
-        #[arg(short, long)]
-        connect: String,
+        #[arg(short, long, required_unless_present = "discover", conflicts_with = "discover")]
+        connect: Option<String>,
+
+        /// Find hosts on the local network and connect to one
+        #[arg(long)]
+        discover: bool,
+
+        /// UDP port hosts answer discovery probes on
+        #[arg(long, default_value_t = DISCOVERY_PORT)]
+        discovery_port: u16,
+
+        /// Window length for per-window latency statistics in the report
+        #[arg(long, default_value_t = ReportConfig::default().window_ms)]
+        window_ms: f64,
+
+        /// Warm-up probes recorded before the measured samples and excluded from statistics
+        #[arg(long, default_value_t = TestConfig::default().latency_warmup_samples)]
+        warmup: usize,
+
+        /// Exclude samples whose modified z-score exceeds this threshold (e.g. 3.5)
+        #[arg(long)]
+        outlier_threshold: Option<f64>,
+
+        /// How latency probes are scheduled
+        #[arg(long, value_enum, default_value_t = ScheduleArg::Sequential)]
+        schedule: ScheduleArg,
+
+        /// Probe interval in milliseconds (mean gap for Poisson, burst period for burst)
+        #[arg(long, default_value_t = TestConfig::default().latency_interval_ms)]
+        interval_ms: u64,
+
+        /// Probe rate within a burst
+        #[arg(long, default_value_t = 1000)]
+        burst_rate_hz: u32,
+
+        /// Probes per burst; a burst must finish within the probe interval
+        #[arg(long, default_value_t = 5)]
+        burst_size: usize,
+
+        /// Skip the UDP packet size sweep and link capacity estimate
+        #[arg(long)]
+        no_sweep: bool,
+
+        /// Skip path MTU discovery
+        #[arg(long)]
+        no_mtu: bool,
+
+        /// Skip the UDP loss stream
+        #[arg(long)]
+        no_loss: bool,
+
+        /// Skip the DSCP marking test
+        #[arg(long)]
+        no_qos: bool,
+
+        /// Skip the port reachability check
+        #[arg(long)]
+        no_port_check: bool,
+
+        /// Ports to check instead of Steam Remote Play's, e.g. udp:27031-27036,tcp:27036-27037
+        #[arg(long)]
+        ports: Option<String>,
+
+        /// Also time UDP probes with kernel software timestamps (Linux SO_TIMESTAMPING)
+        #[arg(long)]
+        kernel_timestamps: bool,
+
+        /// Run latency probes on a pinned thread that busy-polls instead of sleeping
+        #[arg(long)]
+        precise: bool,
+
+        /// With --precise, also request SCHED_FIFO priority (needs CAP_SYS_NICE or an rtprio limit)
+        #[arg(long, requires = "precise")]
+        realtime: bool,
+
+        /// Also measure this device's timer wake-up latency before the network tests
+        #[arg(long)]
+        scheduling_test: bool,
+
+        /// Duration of the wake-up latency test in seconds
+        #[arg(long, requires = "scheduling_test", default_value_t = TestConfig::default().scheduling_test_secs)]
+        scheduling_secs: u64,
+
+        /// Skip the background traffic check and its quiet windows around the tests
+        #[arg(long)]
+        no_traffic_check: bool,
+
+        /// Video frame rate used to estimate corrupted frames from packet loss
+        #[arg(long, default_value_t = ReportConfig::default().frame_rate)]
+        frame_rate: f64,
     },
+    /// Measure this machine's scheduling latency only; no host needed
+    System {
+        /// Duration of the wake-up latency test in seconds
+        #[arg(long, default_value_t = TestConfig::default().scheduling_test_secs)]
+        duration_secs: u64,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ScheduleArg {
+    Sequential,
+    FixedRate,
+    Poisson,
+    Burst,
 }
 
 #[tokio::main]
@@ -36,30 +157,142 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Host { listen } => {
+        Commands::Host { listen, discovery_port, no_discovery, allow_any_ports } => {
             info!("Starting chequer in HOST mode, listening on {}", listen);
-            run_host(listen).await?;
+            run_host(listen, (!no_discovery).then_some(discovery_port), allow_any_ports).await?;
         }
-        Commands::Client { connect } => {
+        Commands::Client {
+            connect,
+            // clap requires exactly one of `connect` and `discover`
+            discover: _,
+            discovery_port,
+            window_ms,
+            warmup,
+            outlier_threshold,
+            schedule,
+            interval_ms,
+            burst_rate_hz,
+            burst_size,
+            no_sweep,
+            no_mtu,
+            no_loss,
+            no_qos,
+            no_port_check,
+            ports,
+            kernel_timestamps,
+            precise,
+            realtime,
+            scheduling_test,
+            scheduling_secs,
+            no_traffic_check,
+            frame_rate,
+        } => {
+            // Discovery is part of the run's own traffic
+            let counters = Arc::new(OwnCounters::default());
+            let connect = match connect {
+                Some(connect) => connect,
+                None => select_discovered_host(discovery_port, &counters).await?,
+            };
             info!("Starting chequer in CLIENT mode, connecting to {}", connect);
-            run_client(connect).await?;
+            let probe_schedule = match schedule {
+                ScheduleArg::Sequential => ProbeSchedule::Sequential,
+                ScheduleArg::FixedRate => ProbeSchedule::FixedRate,
+                ScheduleArg::Poisson => ProbeSchedule::Poisson,
+                ScheduleArg::Burst => ProbeSchedule::Burst { rate_hz: burst_rate_hz, burst_size },
+            };
+            let config = TestConfig {
+                latency_interval_ms: interval_ms,
+                latency_warmup_samples: warmup,
+                latency_outlier_threshold: outlier_threshold,
+                probe_schedule,
+                packet_size_sweep: !no_sweep,
+                mtu_discovery: !no_mtu,
+                loss_test: !no_loss,
+                qos_test: !no_qos,
+                port_check: !no_port_check,
+                ports: match ports {
+                    Some(list) => parse_ports(&list)?,
+                    None => PortSpec::steam_remote_play(),
+                },
+                kernel_timestamps,
+                precise,
+                realtime,
+                scheduling_test,
+                scheduling_test_secs: scheduling_secs,
+                traffic_check: !no_traffic_check,
+                ..TestConfig::default()
+            };
+            run_client(connect, config, ReportConfig { window_ms, frame_rate }, counters).await?;
+        }
+        Commands::System { duration_secs } => {
+            info!("Starting chequer in SYSTEM mode");
+            run_system(duration_secs).await?;
         }
     }
 
     Ok(())
 }
 
-async fn run_host(listen: String) -> Result<()> {
-    let host = Host::new(listen);
+/// Parse `--ports`, which the host caps at `ports::MAX_PORTS`
+fn parse_ports(list: &str) -> Result<Vec<PortSpec>> {
+    let ports = PortSpec::parse_list(list).map_err(anyhow::Error::msg)?;
+    if ports.len() > ports::MAX_PORTS {
+        anyhow::bail!("--ports lists {} ports; the host checks at most {}", ports.len(), ports::MAX_PORTS);
+    }
+    Ok(ports)
+}
+
+async fn run_host(listen: String, discovery_port: Option<u16>, allow_any_ports: bool) -> Result<()> {
+    let host = Host::new(listen)
+        .with_discovery_port(discovery_port)
+        .with_any_ports(allow_any_ports)
+        .with_report(true);
     host.run().await
 }
 
-async fn run_client(connect: String) -> Result<()> {
-    let client = Client::new(connect);
+/// List the hosts that answer a discovery probe and let the user pick one
+async fn select_discovered_host(discovery_port: u16, counters: &OwnCounters) -> Result<String> {
+    info!("Searching for hosts on the local network...");
+    let hosts = discovery::discover(discovery_port, Duration::from_secs(2), counters).await?;
+    
+    match hosts.len() {
+        0 => anyhow::bail!("No hosts found; is `chequer host` running on the same network? Use --connect to give its address."),
+        1 => {
+            let host = &hosts[0];
+            println!("Found {} at {} (chequer {})", host.info.name, host.address, host.info.version);
+            Ok(host.address.to_string())
+        }
+        _ => {
+            println!("Found {} hosts:", hosts.len());
+            for (i, host) in hosts.iter().enumerate() {
+                println!("  {}) {} at {} (chequer {}, {})",
+                         i + 1, host.info.name, host.address, host.info.version, host.info.capabilities.join(", "));
+            }
+            print!("Connect to [1-{}, default 1]: ", hosts.len());
+            std::io::stdout().flush()?;
+            
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            let choice = match line.trim() {
+                "" => 1,
+                choice => choice.parse::<usize>().ok()
+                    .filter(|n| (1..=hosts.len()).contains(n))
+                    .ok_or_else(|| anyhow::anyhow!("Invalid choice '{}'", choice))?,
+            };
+            Ok(hosts[choice - 1].address.to_string())
+        }
+    }
+}
+
+async fn run_client(connect: String, config: TestConfig, report_config: ReportConfig, counters: Arc<OwnCounters>) -> Result<()> {
+    let client = Client::new(connect)
+        .with_config(config)
+        .with_report_config(report_config.clone())
+        .with_counters(counters);
     let results = client.run().await?;
     
     // Generate and display report
-    let report = DiagnosticReport::from_results(results);
+    let report = DiagnosticReport::from_results_with_config(results, &report_config);
     report.print_terminal();
     
     // Optionally save JSON
@@ -70,3 +303,13 @@ async fn run_client(connect: String) -> Result<()> {
     
     Ok(())
 }
+
+async fn run_system(duration_secs: u64) -> Result<()> {
+    let duration = Duration::from_secs(duration_secs);
+    let system = tokio::task::spawn_blocking(move || wakeup::run_wakeup_test(duration, wakeup::WAKEUP_INTERVAL)).await?;
+    
+    let results = TestResults { system: Some(system), ..TestResults::default() };
+    DiagnosticReport::from_results(results).print_terminal();
+    
+    Ok(())
+}
@@ -0,0 +1,204 @@
+/// Path MTU discovery with UDP probes that must not be fragmented
+use anyhow::Result;
+use chequer_common::MtuResults;
+use std::net::SocketAddr;
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::probe::UdpProber;
+use crate::traffic::OwnCounters;
+
+/// Standard Ethernet MTU
+pub const ETHERNET_MTU: usize = 1500;
+
+/// Largest MTU searched, a 9000 byte jumbo frame
+pub const JUMBO_MTU: usize = 9000;
+
+/// Tries per payload size before it counts as not getting through
+const ATTEMPTS: usize = 3;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Binary search for the largest payload that gets through
+#[derive(Debug, Clone)]
+pub struct MtuSearch {
+    min: usize,
+    max: usize,
+    largest_passed: Option<usize>,
+    smallest_failed: usize,
+}
+
+impl MtuSearch {
+    /// Search payload sizes from `min` to `max` inclusive
+    pub fn new(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max,
+            largest_passed: None,
+            smallest_failed: max + 1,
+        }
+    }
+
+    /// Next payload size to try, or `None` once the search is complete
+    ///
+    /// The minimum is tried first, then the maximum, then the midpoint of
+    /// the remaining range.
+    pub fn next_size(&self) -> Option<usize> {
+        match self.largest_passed {
+            None if self.smallest_failed <= self.min => None,
+            None => Some(self.min),
+            Some(passed) if self.smallest_failed > self.max && passed < self.max => Some(self.max),
+            Some(passed) if self.smallest_failed - passed > 1 => Some((passed + self.smallest_failed) / 2),
+            Some(_) => None,
+        }
+    }
+
+    /// Record whether a payload of `size` got through
+    pub fn record(&mut self, size: usize, passed: bool) {
+        if passed {
+            self.largest_passed = Some(self.largest_passed.map_or(size, |p| p.max(size)));
+        } else {
+            self.smallest_failed = self.smallest_failed.min(size);
+        }
+    }
+
+    /// Largest payload that got through
+    pub fn result(&self) -> Option<usize> {
+        self.largest_passed
+    }
+}
+
+/// Find the largest UDP payload that reaches the host's echo service unfragmented
+///
+/// Returns `None` if not even a minimum-MTU probe came back, e.g. because
+/// the host has no UDP echo or a firewall drops it.
+pub async fn run_mtu_discovery(host: SocketAddr, counters: &Arc<OwnCounters>) -> Result<Option<MtuResults>> {
+    // IP and UDP headers, and the minimum MTU every path must carry
+    let (header_bytes, min_mtu) = if host.is_ipv4() { (28, 576) } else { (48, 1280) };
+    let ceiling = JUMBO_MTU - header_bytes;
+    info!("Running path MTU discovery ({}-{} byte payloads)...", min_mtu - header_bytes, ceiling);
+
+    let mut prober = UdpProber::connect(host, counters).await?;
+    let dont_fragment = set_dont_fragment(&prober, host.is_ipv6(), true);
+    if !dont_fragment {
+        warn!("Could not set the Don't Fragment bit; the path MTU result is the fragmentation limit");
+    }
+
+    let mut search = MtuSearch::new(min_mtu - header_bytes, ceiling);
+    while let Some(size) = search.next_size() {
+        let passed = payload_passes(&mut prober, size).await?;
+        search.record(size, passed);
+    }
+
+    let Some(max_payload_bytes) = search.result() else {
+        warn!("No UDP echoes received from {}; skipping path MTU results", host);
+        return Ok(None);
+    };
+
+    // Below Ethernet size, check whether full-size datagrams survive fragmentation or are black-holed
+    let ethernet_payload = ETHERNET_MTU - header_bytes;
+    let fragmented_delivery = if dont_fragment && max_payload_bytes < ethernet_payload {
+        let mut prober = UdpProber::connect(host, counters).await?;
+        if set_dont_fragment(&prober, host.is_ipv6(), false) {
+            Some(payload_passes(&mut prober, ethernet_payload).await?)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    let results = MtuResults {
+        max_payload_bytes,
+        header_bytes,
+        search_ceiling_bytes: ceiling,
+        dont_fragment,
+        fragmented_delivery,
+    };
+
+    info!("Path MTU discovery complete - path MTU: {} bytes, fragmented delivery: {:?}",
+          results.path_mtu(), results.fragmented_delivery);
+
+    Ok(Some(results))
+}
+
+/// Whether any of a few probes of `size` bytes comes back
+async fn payload_passes(prober: &mut UdpProber, size: usize) -> Result<bool> {
+    for _ in 0..ATTEMPTS {
+        match prober.probe(size, PROBE_TIMEOUT).await {
+            Ok(Some(_)) => return Ok(true),
+            Ok(None) => {}
+            // The kernel already knows the path cannot carry this size
+            Err(e) if is_message_too_long(&e) => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(false)
+}
+
+fn is_message_too_long(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .and_then(|e| e.raw_os_error())
+        == Some(libc::EMSGSIZE)
+}
+
+/// Set or clear the Don't Fragment bit (IP_MTU_DISCOVER), returning whether it took effect
+fn set_dont_fragment(prober: &UdpProber, ipv6: bool, dont_fragment: bool) -> bool {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let (level, name, value) = match (ipv6, dont_fragment) {
+            (false, true) => (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DO),
+            (false, false) => (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_DONT),
+            (true, true) => (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DO),
+            (true, false) => (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_DONT),
+        };
+        libc::setsockopt(
+            prober.socket().as_raw_fd(),
+            level,
+            name,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&value) as libc::socklen_t,
+        ) == 0
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (prober, ipv6, dont_fragment);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a search against a path that carries payloads up to `limit`
+    fn search(min: usize, max: usize, limit: usize) -> (Option<usize>, usize) {
+        let mut search = MtuSearch::new(min, max);
+        let mut probes = 0;
+        while let Some(size) = search.next_size() {
+            search.record(size, size <= limit);
+            probes += 1;
+        }
+        (search.result(), probes)
+    }
+
+    #[test]
+    fn test_search_finds_pppoe_mtu() {
+        // PPPoE: 1492 byte MTU, 1464 byte UDP payload
+        let (result, probes) = search(548, 8972, 1464);
+        assert_eq!(result, Some(1464));
+        assert!(probes <= 16, "{} probes", probes);
+    }
+
+    #[test]
+    fn test_search_stops_at_ceiling() {
+        assert_eq!(search(548, 8972, 65507), (Some(8972), 2));
+    }
+
+    #[test]
+    fn test_search_black_hole() {
+        assert_eq!(search(548, 8972, 100), (None, 1));
+        assert_eq!(search(548, 8972, 548).0, Some(548));
+    }
+}
@@ -0,0 +1,261 @@
+/// Local network interface and link probe from sysfs, procfs and nl80211
+use chequer_common::{InterfaceInfo, InterfaceKind, NetworkEnvironment, WifiInfo};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::process::Command;
+use tracing::debug;
+
+/// ARPHRD_ETHER from if_arp.h; WiFi interfaces report it too
+const ARPHRD_ETHER: u32 = 1;
+
+/// ARPHRD_LOOPBACK from if_arp.h
+const ARPHRD_LOOPBACK: u32 = 772;
+
+/// Probe this machine's interfaces, marking the one that owns `local_ip`
+///
+/// Runs `iw` for WiFi details, so call it from a blocking context.
+pub fn probe(local_ip: Option<IpAddr>) -> NetworkEnvironment {
+    let mut interfaces = read_interfaces(Path::new("/"));
+
+    // nl80211 (through iw) has the frequency, bitrate and power-save state /proc/net/wireless lacks
+    for iface in interfaces.iter_mut().filter(|i| i.kind == InterfaceKind::Wifi) {
+        let wifi = iface.wifi.get_or_insert_with(WifiInfo::default);
+        if let Some(output) = run_iw(&["dev", &iface.name, "link"]) {
+            let link = parse_iw_link(&output);
+            wifi.ssid = link.ssid.or(wifi.ssid.take());
+            wifi.frequency_mhz = link.frequency_mhz.or(wifi.frequency_mhz);
+            wifi.signal_dbm = link.signal_dbm.or(wifi.signal_dbm);
+            wifi.bitrate_mbps = link.bitrate_mbps.or(wifi.bitrate_mbps);
+        }
+        if let Some(output) = run_iw(&["dev", &iface.name, "get", "power_save"]) {
+            wifi.power_save = parse_iw_power_save(&output);
+        }
+    }
+
+    NetworkEnvironment {
+        interfaces,
+        active_interface: local_ip.and_then(interface_for_ip),
+    }
+}
+
+/// Read interfaces from `<root>/sys/class/net` and WiFi levels from `<root>/proc/net/wireless`
+pub fn read_interfaces(root: &Path) -> Vec<InterfaceInfo> {
+    let wireless = fs::read_to_string(root.join("proc/net/wireless"))
+        .map(|contents| parse_proc_wireless(&contents))
+        .unwrap_or_default();
+    let Ok(entries) = fs::read_dir(root.join("sys/class/net")) else {
+        return Vec::new();
+    };
+
+    let mut interfaces: Vec<InterfaceInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let dir = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let read = |file: &str| fs::read_to_string(dir.join(file)).ok().map(|s| s.trim().to_string());
+
+            let arp_type = read("type").and_then(|t| t.parse::<u32>().ok());
+            let kind = if dir.join("wireless").exists() || dir.join("phy80211").exists() {
+                InterfaceKind::Wifi
+            } else if arp_type == Some(ARPHRD_LOOPBACK) {
+                InterfaceKind::Loopback
+            } else if arp_type == Some(ARPHRD_ETHER) && dir.join("device").exists() {
+                InterfaceKind::Ethernet
+            } else {
+                InterfaceKind::Virtual
+            };
+
+            // Drivers report -1 or fail the read when the link is down or the speed is unknown
+            let speed_mbps = if kind == InterfaceKind::Ethernet {
+                read("speed").and_then(|s| s.parse::<i64>().ok()).filter(|&s| s > 0).map(|s| s as u32)
+            } else {
+                None
+            };
+
+            InterfaceInfo {
+                operstate: read("operstate").unwrap_or_else(|| "unknown".to_string()),
+                mtu: read("mtu").and_then(|m| m.parse().ok()),
+                speed_mbps,
+                wifi: (kind == InterfaceKind::Wifi).then(|| wireless.get(&name).cloned().unwrap_or_default()),
+                kind,
+                name,
+            }
+        })
+        .collect();
+
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Parse signal and noise levels per interface from /proc/net/wireless
+pub fn parse_proc_wireless(contents: &str) -> HashMap<String, WifiInfo> {
+    // Old drivers report levels as unsigned bytes
+    let dbm = |field: &str| {
+        field.trim_end_matches('.').parse::<f64>().ok()
+            .map(|level| if level > 0.0 { level - 256.0 } else { level })
+            .filter(|&level| level > -256.0)
+    };
+
+    contents
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, fields) = line.split_once(':')?;
+            let fields: Vec<&str> = fields.split_whitespace().collect();
+            let info = WifiInfo {
+                signal_dbm: dbm(fields.get(2)?),
+                noise_dbm: dbm(fields.get(3)?),
+                ..WifiInfo::default()
+            };
+            Some((name.trim().to_string(), info))
+        })
+        .collect()
+}
+
+/// Parse the output of `iw dev <interface> link`
+pub fn parse_iw_link(output: &str) -> WifiInfo {
+    let mut info = WifiInfo::default();
+    for line in output.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let number = || value.split_whitespace().next().and_then(|n| n.parse::<f64>().ok());
+        match key {
+            "SSID" => info.ssid = Some(value.to_string()),
+            "freq" => info.frequency_mhz = number().map(|f| f as u32),
+            "signal" => info.signal_dbm = number(),
+            "tx bitrate" => info.bitrate_mbps = number(),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Parse the output of `iw dev <interface> get power_save`
+pub fn parse_iw_power_save(output: &str) -> Option<bool> {
+    match output.trim().strip_prefix("Power save:")?.trim() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn run_iw(args: &[&str]) -> Option<String> {
+    let output = Command::new("iw").args(args).output()
+        .map_err(|e| debug!("Cannot run iw: {}", e))
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Name of the interface that has `ip` assigned
+fn interface_for_ip(ip: IpAddr) -> Option<String> {
+    let ip = ip.to_canonical();
+    let mut found = None;
+
+    unsafe {
+        let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+        if libc::getifaddrs(&mut addrs) != 0 {
+            return None;
+        }
+
+        let mut current = addrs;
+        while !current.is_null() {
+            let ifa = &*current;
+            current = ifa.ifa_next;
+            if ifa.ifa_addr.is_null() {
+                continue;
+            }
+
+            let addr = match (*ifa.ifa_addr).sa_family as libc::c_int {
+                libc::AF_INET => {
+                    let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                    IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)))
+                }
+                libc::AF_INET6 => {
+                    let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                    IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr))
+                }
+                _ => continue,
+            };
+            if addr == ip {
+                found = Some(CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned());
+                break;
+            }
+        }
+
+        libc::freeifaddrs(addrs);
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/netif").join(path)
+    }
+
+    #[test]
+    fn test_read_interfaces_from_sysfs() {
+        let interfaces = read_interfaces(&fixture("steamdeck"));
+        let names: Vec<&str> = interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["enp4s0", "lo", "wg0", "wlan0"]);
+
+        let ethernet = &interfaces[0];
+        assert_eq!(ethernet.kind, InterfaceKind::Ethernet);
+        assert_eq!(ethernet.speed_mbps, Some(1000));
+        assert_eq!(ethernet.mtu, Some(1500));
+
+        assert_eq!(interfaces[1].kind, InterfaceKind::Loopback);
+        assert_eq!(interfaces[2].kind, InterfaceKind::Virtual);
+        assert_eq!(interfaces[2].mtu, Some(1420));
+
+        let wifi = &interfaces[3];
+        assert_eq!(wifi.kind, InterfaceKind::Wifi);
+        assert_eq!(wifi.operstate, "up");
+        assert_eq!(wifi.speed_mbps, None);
+        let levels = wifi.wifi.as_ref().unwrap();
+        assert_eq!(levels.signal_dbm, Some(-72.0));
+        assert_eq!(levels.noise_dbm, None);
+    }
+
+    #[test]
+    fn test_parse_proc_wireless_unsigned_levels() {
+        let contents = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+                        face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n \
+                        eth1: 0000   46.  195.  161.       0      0      0      0      0        0\n";
+        let wireless = parse_proc_wireless(contents);
+        assert_eq!(wireless["eth1"].signal_dbm, Some(-61.0));
+        assert_eq!(wireless["eth1"].noise_dbm, Some(-95.0));
+    }
+
+    #[test]
+    fn test_parse_iw_output() {
+        let link = parse_iw_link(&fs::read_to_string(fixture("iw-link.txt")).unwrap());
+        assert_eq!(link.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(link.frequency_mhz, Some(2437));
+        assert_eq!(link.band(), Some("2.4 GHz"));
+        assert_eq!(link.signal_dbm, Some(-72.0));
+        assert_eq!(link.bitrate_mbps, Some(72.2));
+
+        let disconnected = parse_iw_link("Not connected.\n");
+        assert!(disconnected.ssid.is_none() && disconnected.frequency_mhz.is_none());
+
+        assert_eq!(parse_iw_power_save("Power save: on\n"), Some(true));
+        assert_eq!(parse_iw_power_save("Power save: off\n"), Some(false));
+        assert_eq!(parse_iw_power_save("command failed: Operation not supported (-95)\n"), None);
+    }
+
+    #[test]
+    fn test_loopback_is_active_for_localhost() {
+        assert_eq!(interface_for_ip("127.0.0.1".parse().unwrap()).as_deref(), Some("lo"));
+        assert_eq!(interface_for_ip("::ffff:127.0.0.1".parse().unwrap()).as_deref(), Some("lo"));
+    }
+}
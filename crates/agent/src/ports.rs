@@ -0,0 +1,275 @@
+/// Reachability check of the host's Remote Play ports
+use anyhow::{bail, Result};
+use chequer_common::{PortCheckResult, PortFailure, PortSpec, PortState, Transport};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+use crate::probe::{self, UdpProber};
+use crate::traffic::{OwnCounters, PeerCounters, TCP_CHECK_SEGMENTS};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most ports one client may have the host open at a time
+pub const MAX_PORTS: usize = 16;
+
+/// Temporary listeners close after this even if the client never finishes the check
+const LISTENER_LIFETIME: Duration = Duration::from_secs(60);
+
+const UDP_ATTEMPTS: usize = 3;
+
+const UDP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Host side: temporary listeners, closed when dropped at the end of the check
+pub struct PortListeners {
+    pub states: Vec<PortState>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl PortListeners {
+    /// Open a listener on each port, on all interfaces like Steam does
+    ///
+    /// TCP listeners accept and drop connections; UDP listeners echo probes.
+    /// Only Steam Remote Play's ports are opened unless `any_port` is set,
+    /// and every listener closes after [`LISTENER_LIFETIME`] at the latest.
+    /// `local_ip` is the address the client reached the host on; traffic on
+    /// the listeners is counted in the run of the client it comes from.
+    pub async fn open(ports: &[PortSpec], any_port: bool, local_ip: IpAddr, peers: &PeerCounters) -> Result<Self> {
+        if ports.len() > MAX_PORTS {
+            bail!("asked to open {} ports, at most {} are allowed", ports.len(), MAX_PORTS);
+        }
+
+        let steam_ports = PortSpec::steam_remote_play();
+        let mut states = Vec::with_capacity(ports.len());
+        let mut tasks = Vec::new();
+
+        for spec in ports {
+            if !any_port && !steam_ports.contains(spec) {
+                debug!("{}: not a Steam Remote Play port, not opening it", spec);
+                states.push(PortState::Unavailable("not a Steam Remote Play port; the host was not started with --allow-any-ports".to_string()));
+                continue;
+            }
+
+            let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), spec.port);
+            let opened = match spec.transport {
+                Transport::Tcp => TcpListener::bind(addr).await.map(|listener| {
+                    let peers = peers.clone();
+                    tokio::spawn(async move {
+                        let accept = async {
+                            while let Ok((stream, peer)) = listener.accept().await {
+                                debug!("Port check connection from {}", peer);
+                                if let Some(counters) = peers.get(peer.ip()) {
+                                    let (client_sent, client_received) = TCP_CHECK_SEGMENTS;
+                                    counters.count_tcp_segments(client_received, client_sent, peer);
+                                }
+                                drop(stream);
+                            }
+                        };
+                        tokio::time::timeout(LISTENER_LIFETIME, accept).await.ok();
+                    })
+                }),
+                Transport::Udp => UdpSocket::bind(addr).await.map(|socket| {
+                    let peers = peers.clone();
+                    tokio::spawn(async move {
+                        tokio::time::timeout(LISTENER_LIFETIME, probe::run_echo(socket, peers)).await.ok();
+                    })
+                }),
+            };
+
+            let state = match opened {
+                Ok(task) => {
+                    tasks.push(task);
+                    PortState::Listening
+                }
+                Err(e) if e.kind() == ErrorKind::AddrInUse => in_use_state(spec, local_ip),
+                Err(e) => PortState::Unavailable(e.to_string()),
+            };
+            debug!("{}: {:?}", spec, state);
+            states.push(state);
+        }
+
+        Ok(Self { states, tasks })
+    }
+}
+
+impl Drop for PortListeners {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// State of a port another process holds
+///
+/// A UDP holder does not echo probes, so look it up instead: one bound only
+/// to addresses the client cannot reach, such as loopback, is as good as closed.
+fn in_use_state(spec: &PortSpec, local_ip: IpAddr) -> PortState {
+    if spec.transport == Transport::Tcp {
+        return PortState::InUse;
+    }
+    let bindings = udp_bindings(spec.port);
+    let reachable = |bound: &IpAddr| bound.is_unspecified() || bound.to_canonical() == local_ip.to_canonical();
+    if bindings.is_empty() || bindings.iter().any(reachable) {
+        return PortState::InUse;
+    }
+    let bound: Vec<String> = bindings.iter().map(ToString::to_string).collect();
+    PortState::Unavailable(format!("held on {} only, which the client cannot reach", bound.join(", ")))
+}
+
+/// Local addresses of the UDP sockets bound to `port`
+fn udp_bindings(port: u16) -> Vec<IpAddr> {
+    ["/proc/net/udp", "/proc/net/udp6"].iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| parse_udp_bindings(&table, port))
+        .collect()
+}
+
+/// Parse a `/proc/net/udp` table, which prints addresses as 32-bit words in host byte order
+fn parse_udp_bindings(table: &str, port: u16) -> Vec<IpAddr> {
+    table.lines().skip(1).filter_map(|line| {
+        let (addr, local_port) = line.split_whitespace().nth(1)?.split_once(':')?;
+        if u16::from_str_radix(local_port, 16).ok()? != port {
+            return None;
+        }
+        let bytes: Vec<u8> = (0..addr.len()).step_by(8)
+            .map(|i| u32::from_str_radix(addr.get(i..i + 8)?, 16).ok())
+            .collect::<Option<Vec<u32>>>()?
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect();
+        match bytes.len() {
+            4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+            16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+            _ => None,
+        }
+    }).collect()
+}
+
+/// Client side: try each port the host reported on, counting the traffic in `counters`
+pub async fn check_ports(host: IpAddr, ports: &[PortSpec], states: Vec<PortState>, counters: &Arc<OwnCounters>) -> Vec<PortCheckResult> {
+    info!("Checking {} ports on {}...", ports.len(), host);
+
+    let mut results = Vec::with_capacity(ports.len());
+    for (&port, host_state) in ports.iter().zip(states) {
+        let addr = SocketAddr::new(host, port.port);
+        let (reachable, failure) = match (port.transport, &host_state) {
+            // Whoever holds a TCP port accepts connections, so in-use ports are checked the same way
+            (Transport::Tcp, PortState::Listening | PortState::InUse) => check_tcp(addr, counters).await,
+            (Transport::Udp, PortState::Listening) => check_udp(addr, counters).await,
+            (Transport::Udp, PortState::InUse) => check_udp_in_use(addr, counters).await,
+            (_, PortState::Unavailable(_)) => (None, None),
+        };
+        results.push(PortCheckResult { port, host_state, reachable, failure });
+    }
+
+    let reachable = results.iter().filter(|r| r.reachable == Some(true)).count();
+    info!("Port check complete - {} of {} reachable", reachable, results.len());
+
+    results
+}
+
+async fn check_tcp(addr: SocketAddr, counters: &OwnCounters) -> (Option<bool>, Option<PortFailure>) {
+    let (checked, (sent, received)) = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await {
+        Ok(Ok(_)) => ((Some(true), None), TCP_CHECK_SEGMENTS),
+        // A SYN answered by a reset
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => ((Some(false), Some(PortFailure::Refused)), (1, 1)),
+        Ok(Err(e)) => ((Some(false), Some(PortFailure::Other(e.to_string()))), (1, 0)),
+        Err(_) => ((Some(false), Some(PortFailure::TimedOut)), (1, 0)),
+    };
+    counters.count_tcp_segments(sent, received, addr);
+    checked
+}
+
+async fn check_udp(addr: SocketAddr, counters: &Arc<OwnCounters>) -> (Option<bool>, Option<PortFailure>) {
+    let mut prober = match UdpProber::connect(addr, counters).await {
+        Ok(prober) => prober,
+        Err(e) => return (Some(false), Some(PortFailure::Other(e.to_string()))),
+    };
+    for _ in 0..UDP_ATTEMPTS {
+        match prober.probe(probe::PROBE_HEADER_LEN, UDP_TIMEOUT).await {
+            Ok(Some(_)) => return (Some(true), None),
+            Ok(None) if prober.refused() => return (Some(false), Some(PortFailure::Refused)),
+            Ok(None) => {}
+            // An earlier probe's ICMP port unreachable can surface on the next send
+            Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == ErrorKind::ConnectionRefused) => {
+                return (Some(false), Some(PortFailure::Refused));
+            }
+            Err(e) => return (Some(false), Some(PortFailure::Other(e.to_string()))),
+        }
+    }
+    (Some(false), Some(PortFailure::NoEcho))
+}
+
+/// Steam ignores probes, so silence proves nothing, but a firewall rejecting the port still answers
+async fn check_udp_in_use(addr: SocketAddr, counters: &Arc<OwnCounters>) -> (Option<bool>, Option<PortFailure>) {
+    match check_udp(addr, counters).await {
+        (Some(false), Some(PortFailure::NoEcho)) => (None, None),
+        checked => checked,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_host_only_opens_steam_ports_by_default() {
+        let local = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let ports = PortSpec::parse_list("udp:80,tcp:17790").unwrap();
+        let listeners = PortListeners::open(&ports, false, local, &PeerCounters::default()).await.unwrap();
+        assert!(listeners.states.iter().all(|s| matches!(s, PortState::Unavailable(_))));
+        assert!(listeners.tasks.is_empty());
+
+        let too_many = PortSpec::parse_list("udp:17790-17806").unwrap();
+        assert!(PortListeners::open(&too_many, true, local, &PeerCounters::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_port_check_traffic_is_counted_on_both_sides() {
+        let local = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let ports = PortSpec::parse_list("udp:17791,tcp:17791").unwrap();
+        let peers = PeerCounters::default();
+        let host_counters = Arc::new(OwnCounters::default());
+        let _registration = peers.register(local, Arc::clone(&host_counters));
+        let listeners = PortListeners::open(&ports, true, local, &peers).await.unwrap();
+
+        let client_counters = Arc::new(OwnCounters::default());
+        let results = check_ports(local, &ports, listeners.states.clone(), &client_counters).await;
+        assert!(results.iter().all(|r| r.reachable == Some(true)), "{:?}", results);
+
+        // Client and host each count the probe and its echo
+        for counters in [client_counters, host_counters] {
+            let totals = counters.totals();
+            assert!(totals.rx_bytes > 0 && totals.tx_bytes > 0, "{:?}", totals);
+        }
+    }
+
+    #[test]
+    #[cfg(target_endian = "little")]
+    fn test_parse_udp_bindings() {
+        let udp = "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  512: 00000000:6997 00000000:0000 07 00000000:00000000 00:00000000 00000000  1000        0 40113 2 0000000000000000 0
+  935: 0100007F:6998 00000000:0000 07 00000000:00000000 00:00000000 00000000  1000        0 40117 2 0000000000000000 0
+";
+        assert_eq!(parse_udp_bindings(udp, 27031), vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]);
+        assert_eq!(parse_udp_bindings(udp, 27032), vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        assert!(parse_udp_bindings(udp, 27033).is_empty());
+
+        let udp6 = "  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  512: 00000000000000000000000001000000:6997 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000  1000        0 40114 2 0000000000000000 0
+";
+        assert_eq!(parse_udp_bindings(udp6, 27031), vec!["::1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_in_use_udp_port_bound_to_loopback_only() {
+        let held = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let spec = PortSpec { transport: Transport::Udp, port: held.local_addr().unwrap().port() };
+        assert_eq!(in_use_state(&spec, IpAddr::V4(Ipv4Addr::LOCALHOST)), PortState::InUse);
+        assert!(matches!(in_use_state(&spec, "192.0.2.10".parse().unwrap()), PortState::Unavailable(_)));
+    }
+}
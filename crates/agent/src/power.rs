@@ -0,0 +1,250 @@
+/// Battery, power supply, CPU clock and thermal state from sysfs
+use chequer_common::{PowerResults, PowerSample, PowerState, Temperature};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use crate::pressure::now_utc_ms;
+
+/// Time between samples; temperatures and charge change over seconds, not milliseconds
+pub const POWER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Read the state from sysfs under `root`
+pub fn read_power(root: &Path) -> PowerState {
+    let mut state = PowerState {
+        platform_profile: read_trimmed(&root.join("sys/firmware/acpi/platform_profile")),
+        ..PowerState::default()
+    };
+    read_supplies(root, &mut state);
+    read_cpufreq(root, &mut state);
+    state.temperatures = read_thermal_zones(root);
+    state.temperatures.extend(read_hwmon(root));
+    state
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    read_trimmed(path)?.parse().ok()
+}
+
+/// Entries of `dir` whose names start with `prefix`, sorted by name
+fn entries(dir: &Path, prefix: &str) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| (entry.file_name().to_string_lossy().into_owned(), entry.path()))
+        .filter(|(name, _)| name.starts_with(prefix))
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// AC adapters and the system battery
+///
+/// USB ports are skipped: a desktop's empty USB-C port reports an offline
+/// supply just like a laptop's unplugged charger. So are batteries of mice and
+/// controllers.
+fn read_supplies(root: &Path, state: &mut PowerState) {
+    for (_, supply) in entries(&root.join("sys/class/power_supply"), "") {
+        match read_trimmed(&supply.join("type")).as_deref() {
+            Some("Mains") => {
+                if let Some(online) = read_number(&supply.join("online")) {
+                    state.ac_online = Some(state.ac_online == Some(true) || online > 0.0);
+                }
+            }
+            Some("Battery") if state.battery_status.is_none() => {
+                if read_trimmed(&supply.join("scope")).as_deref() == Some("Device") {
+                    continue;
+                }
+                state.battery_percent = read_number(&supply.join("capacity"));
+                state.battery_status = read_trimmed(&supply.join("status"));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Current frequency averaged over the CPUs, and the highest they support
+fn read_cpufreq(root: &Path, state: &mut PowerState) {
+    let cpus: Vec<PathBuf> = entries(&root.join("sys/devices/system/cpu"), "cpu").into_iter()
+        .filter(|(name, _)| name[3..].parse::<u32>().is_ok())
+        .map(|(_, path)| path.join("cpufreq"))
+        .collect();
+
+    let current: Vec<f64> = cpus.iter().filter_map(|cpufreq| read_number(&cpufreq.join("scaling_cur_freq"))).collect();
+    if !current.is_empty() {
+        state.cpu_freq_mhz = Some(current.iter().sum::<f64>() / current.len() as f64 / 1000.0);
+    }
+    state.cpu_max_freq_mhz = cpus.iter()
+        .filter_map(|cpufreq| read_number(&cpufreq.join("cpuinfo_max_freq")))
+        .reduce(f64::max)
+        .map(|khz| khz / 1000.0);
+}
+
+/// Thermal zones, throttling at their lowest passive trip point
+fn read_thermal_zones(root: &Path) -> Vec<Temperature> {
+    entries(&root.join("sys/class/thermal"), "thermal_zone").into_iter()
+        .filter_map(|(_, zone)| {
+            let millidegrees = read_number(&zone.join("temp"))?;
+            let passive_trips = entries(&zone, "trip_point_").into_iter()
+                .filter(|(name, _)| name.ends_with("_type"))
+                .filter(|(_, path)| read_trimmed(path).as_deref() == Some("passive"))
+                .filter_map(|(name, _)| read_number(&zone.join(name.replace("_type", "_temp"))));
+            Some(Temperature {
+                sensor: read_trimmed(&zone.join("type")).unwrap_or_else(|| "thermal zone".to_string()),
+                celsius: millidegrees / 1000.0,
+                throttle_celsius: passive_trips.reduce(f64::min).map(|limit| limit / 1000.0),
+            })
+        })
+        .collect()
+}
+
+/// hwmon temperature inputs, throttling at their critical limit
+///
+/// `temp*_max` is only a warning threshold; `temp*_crit` is where CPUs such
+/// as Intel's (TjMax) start throttling.
+fn read_hwmon(root: &Path) -> Vec<Temperature> {
+    entries(&root.join("sys/class/hwmon"), "hwmon").into_iter()
+        .flat_map(|(_, hwmon)| {
+            let chip = read_trimmed(&hwmon.join("name")).unwrap_or_else(|| "hwmon".to_string());
+            entries(&hwmon, "temp").into_iter()
+                .filter_map(|(name, input)| {
+                    let sensor = name.strip_suffix("_input")?;
+                    let attribute = |suffix: &str| hwmon.join(format!("{}_{}", sensor, suffix));
+                    let limit = read_number(&attribute("crit"));
+                    Some(Temperature {
+                        sensor: match read_trimmed(&attribute("label")) {
+                            Some(label) => format!("{} {}", chip, label),
+                            None => chip.clone(),
+                        },
+                        celsius: read_number(&input)? / 1000.0,
+                        throttle_celsius: limit.map(|limit| limit / 1000.0),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Background sampler started when a test begins and finished when it ends
+pub struct PowerSampler {
+    interval: Duration,
+    samples: Arc<Mutex<Vec<PowerSample>>>,
+    task: JoinHandle<()>,
+}
+
+impl PowerSampler {
+    /// Start sampling this machine every `POWER_INTERVAL`, beginning now
+    pub fn start() -> Self {
+        Self::start_at(PathBuf::from("/"), POWER_INTERVAL)
+    }
+
+    fn start_at(root: PathBuf, interval: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::clone(&samples);
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Timestamps advance with the monotonic clock, so a wall-clock step does not shift them
+            let (start_utc_ms, start) = (now_utc_ms(), tokio::time::Instant::now());
+
+            loop {
+                ticker.tick().await;
+                let state = read_power(&root);
+                let timestamp_ms = start_utc_ms + start.elapsed().as_secs_f64() * 1000.0;
+                shared.lock().unwrap().push(PowerSample { timestamp_ms, state });
+            }
+        });
+
+        Self { interval, samples, task }
+    }
+
+    /// Stop sampling and return what was collected
+    pub fn finish(self) -> PowerResults {
+        self.task.abort();
+        PowerResults {
+            interval_ms: self.interval.as_secs_f64() * 1000.0,
+            samples: std::mem::take(&mut *self.samples.lock().unwrap()),
+        }
+    }
+}
+
+impl Drop for PowerSampler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/power").join(path)
+    }
+
+    #[test]
+    fn test_steam_deck_on_battery() {
+        let state = read_power(&fixture("steamdeck-battery"));
+
+        assert_eq!(state.ac_online, Some(false));
+        // The mouse's battery is not the Deck's
+        assert_eq!(state.battery_percent, Some(64.0));
+        assert_eq!(state.battery_status.as_deref(), Some("Discharging"));
+        assert!(state.on_battery());
+        assert_eq!(state.cpu_freq_mhz, Some(2000.0));
+        assert_eq!(state.cpu_max_freq_mhz, Some(3500.0));
+        assert_eq!(state.platform_profile, None);
+
+        let sensors: Vec<&str> = state.temperatures.iter().map(|t| t.sensor.as_str()).collect();
+        assert_eq!(sensors, vec!["acpitz", "amdgpu edge", "nvme Composite"]);
+        // Only a critical trip, which shuts down rather than throttles
+        assert_eq!(state.temperatures[0].throttle_celsius, None);
+        // The NVMe warning threshold is not its limit
+        assert_eq!(state.temperatures[2].throttle_celsius, Some(84.85));
+        assert_eq!(state.hottest().map(|t| t.celsius), Some(61.0));
+        assert!(state.throttling().is_none());
+    }
+
+    #[test]
+    fn test_throttling_desktop() {
+        let state = read_power(&fixture("desktop-throttling"));
+
+        // No supplies at all: a desktop, not a laptop on battery
+        assert_eq!(state.ac_online, None);
+        assert!(!state.on_battery());
+        assert_eq!(state.platform_profile.as_deref(), Some("low-power"));
+        assert_eq!(state.cpu_freq_mhz, Some(800.0));
+
+        assert_eq!(state.temperatures[0].throttle_celsius, Some(95.0));
+        assert_eq!(state.throttling().map(|t| t.sensor.as_str()), Some("acpitz"));
+        assert_eq!(state.hottest().map(|t| t.sensor.as_str()), Some("coretemp Package id 0"));
+    }
+
+    #[test]
+    fn test_desktop_with_usb_c_port() {
+        let state = read_power(&fixture("desktop-usbc"));
+
+        // An empty USB-C port is not an unplugged charger
+        assert_eq!(state.ac_online, None);
+        assert_eq!(state.battery_status, None);
+        assert!(!state.on_battery());
+    }
+
+    #[tokio::test]
+    async fn test_sampler_samples_from_the_start() {
+        let sampler = PowerSampler::start_at(fixture("steamdeck-battery"), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let results = sampler.finish();
+
+        assert!(results.samples.len() >= 2, "{} samples", results.samples.len());
+        assert!(results.samples.iter().all(|s| s.state.on_battery()));
+    }
+}
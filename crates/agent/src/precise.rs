@@ -0,0 +1,87 @@
+/// Probe thread setup for the high-precision timing mode
+use chequer_common::PreciseTiming;
+
+/// SCHED_FIFO priority of the probe thread, below the default of threaded IRQ handlers (50)
+/// so that network interrupts still preempt it
+pub const FIFO_PRIORITY: i32 = 40;
+
+/// Prepare the calling thread: pin it to one CPU and, if `realtime`, switch it to SCHED_FIFO
+///
+/// Settings the system refuses are recorded in `denied` rather than failing the test.
+pub fn setup_current_thread(realtime: bool) -> PreciseTiming {
+    let mut timing = PreciseTiming::default();
+
+    match pin_to_last_cpu() {
+        Ok(cpu) => timing.pinned_cpu = Some(cpu),
+        Err(e) => timing.denied.push(format!("CPU pinning: {}", e)),
+    }
+
+    if realtime {
+        match set_fifo(FIFO_PRIORITY) {
+            Ok(()) => timing.fifo_priority = Some(FIFO_PRIORITY),
+            Err(e) => timing.denied.push(format!("SCHED_FIFO: {}", e)),
+        }
+    }
+
+    timing
+}
+
+/// Pin the calling thread to the highest CPU it may run on; CPU 0 takes most interrupts
+#[cfg(target_os = "linux")]
+fn pin_to_last_cpu() -> std::io::Result<usize> {
+    unsafe {
+        let mut allowed: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut allowed) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let cpu = (0..libc::CPU_SETSIZE as usize)
+            .rev()
+            .find(|&cpu| libc::CPU_ISSET(cpu, &allowed))
+            .ok_or_else(|| std::io::Error::other("no CPU in the affinity mask"))?;
+
+        let mut single: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut single);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &single) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(cpu)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_last_cpu() -> std::io::Result<usize> {
+    Err(std::io::Error::other("not supported on this platform"))
+}
+
+/// Switch the calling thread to SCHED_FIFO; needs CAP_SYS_NICE or an RLIMIT_RTPRIO allowance
+#[cfg(target_os = "linux")]
+fn set_fifo(priority: i32) -> std::io::Result<()> {
+    let param = libc::sched_param { sched_priority: priority };
+    if unsafe { libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_fifo(_priority: i32) -> std::io::Result<()> {
+    Err(std::io::Error::other("not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_records_what_was_granted() {
+        let timing = std::thread::spawn(|| setup_current_thread(true)).join().unwrap();
+
+        // Pinning is always allowed within the current affinity mask
+        assert!(timing.pinned_cpu.is_some(), "{:?}", timing.denied);
+        // SCHED_FIFO depends on privileges, but is either granted or explained
+        assert_eq!(timing.fifo_priority.is_some(), timing.denied.is_empty());
+
+        let plain = std::thread::spawn(|| setup_current_thread(false)).join().unwrap();
+        assert_eq!(plain.fifo_priority, None);
+    }
+}
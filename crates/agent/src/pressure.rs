@@ -0,0 +1,179 @@
+/// CPU load and pressure stall (PSI) sampling from procfs while the tests run
+use chequer_common::{PressureResults, PressureSample};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+
+/// Time between samples; short enough to tell which spikes a stall overlapped
+pub const PRESSURE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Current UTC time in milliseconds since the Unix epoch
+pub fn now_utc_ms() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64() * 1000.0
+}
+
+/// Cumulative counters, differenced between two reads to get one sample
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Counters {
+    /// Non-idle and total jiffies of the aggregate `cpu` line in /proc/stat
+    pub cpu_jiffies: Option<(u64, u64)>,
+    /// `some` stall totals in microseconds from /proc/pressure/{cpu,memory,io}
+    pub cpu_some_us: Option<u64>,
+    pub memory_some_us: Option<u64>,
+    pub io_some_us: Option<u64>,
+}
+
+/// Read the counters from procfs under `root`
+pub fn read_counters(root: &Path) -> Counters {
+    let read = |path: &str| fs::read_to_string(root.join(path)).ok();
+    Counters {
+        cpu_jiffies: read("proc/stat").and_then(|stat| parse_proc_stat(&stat)),
+        cpu_some_us: read("proc/pressure/cpu").and_then(|psi| parse_psi_some_total(&psi)),
+        memory_some_us: read("proc/pressure/memory").and_then(|psi| parse_psi_some_total(&psi)),
+        io_some_us: read("proc/pressure/io").and_then(|psi| parse_psi_some_total(&psi)),
+    }
+}
+
+/// Non-idle and total jiffies from the aggregate `cpu` line; idle includes iowait
+pub fn parse_proc_stat(stat: &str) -> Option<(u64, u64)> {
+    let fields: Vec<u64> = stat.lines()
+        .find(|line| line.starts_with("cpu "))?
+        .split_whitespace()
+        .skip(1)
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    // user nice system idle iowait irq softirq steal; guest time is already counted in user
+    let total: u64 = fields.iter().take(8).sum();
+    let idle = fields.get(3)? + fields.get(4).unwrap_or(&0);
+    Some((total - idle, total))
+}
+
+/// Total stall time of the `some` line of a PSI file
+pub fn parse_psi_some_total(psi: &str) -> Option<u64> {
+    psi.lines()
+        .find_map(|line| line.strip_prefix("some "))?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("total="))?
+        .parse()
+        .ok()
+}
+
+/// Turn two reads `elapsed_ms` apart into a sample ending at `timestamp_ms`
+pub fn sample_between(before: &Counters, after: &Counters, elapsed_ms: f64, timestamp_ms: f64) -> PressureSample {
+    let stall_percent = |before: Option<u64>, after: Option<u64>| {
+        let stalled_us = after?.checked_sub(before?)?;
+        Some((stalled_us as f64 / 1000.0 / elapsed_ms * 100.0).min(100.0))
+    };
+    let cpu_busy_percent = before.cpu_jiffies.zip(after.cpu_jiffies).and_then(|((busy0, total0), (busy1, total1))| {
+        let total = total1.checked_sub(total0).filter(|&total| total > 0)?;
+        Some(busy1.saturating_sub(busy0) as f64 / total as f64 * 100.0)
+    });
+
+    PressureSample {
+        timestamp_ms,
+        cpu_busy_percent,
+        cpu_some_percent: stall_percent(before.cpu_some_us, after.cpu_some_us),
+        memory_some_percent: stall_percent(before.memory_some_us, after.memory_some_us),
+        io_some_percent: stall_percent(before.io_some_us, after.io_some_us),
+    }
+}
+
+/// Background sampler started when a test begins and finished when it ends
+pub struct PressureSampler {
+    interval: Duration,
+    samples: Arc<Mutex<Vec<PressureSample>>>,
+    task: JoinHandle<()>,
+}
+
+impl PressureSampler {
+    /// Start sampling this machine every `PRESSURE_INTERVAL`
+    pub fn start() -> Self {
+        Self::start_at(PathBuf::from("/"), PRESSURE_INTERVAL)
+    }
+
+    fn start_at(root: PathBuf, interval: Duration) -> Self {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let shared = Arc::clone(&samples);
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            ticker.tick().await;
+            // Timestamps advance with the monotonic clock, so a wall-clock step does not shift them
+            let (start_utc_ms, start) = (now_utc_ms(), tokio::time::Instant::now());
+            let mut previous = (read_counters(&root), start);
+
+            loop {
+                ticker.tick().await;
+                let counters = read_counters(&root);
+                let now = tokio::time::Instant::now();
+                let elapsed_ms = now.duration_since(previous.1).as_secs_f64() * 1000.0;
+                let timestamp_ms = start_utc_ms + now.duration_since(start).as_secs_f64() * 1000.0;
+                let sample = sample_between(&previous.0, &counters, elapsed_ms, timestamp_ms);
+                shared.lock().unwrap().push(sample);
+                previous = (counters, now);
+            }
+        });
+
+        Self { interval, samples, task }
+    }
+
+    /// Stop sampling and return what was collected
+    pub fn finish(self) -> PressureResults {
+        self.task.abort();
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        PressureResults {
+            interval_ms: self.interval.as_secs_f64() * 1000.0,
+            samples,
+        }
+    }
+}
+
+impl Drop for PressureSampler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAT: &str = "cpu  100 10 50 800 40 0 0 0 0 0\ncpu0 50 5 25 400 20 0 0 0 0 0\nintr 12345\n";
+    const PSI_CPU: &str = "some avg10=1.50 avg60=0.80 avg300=0.20 total=2000000\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n";
+
+    #[test]
+    fn test_parse_procfs() {
+        assert_eq!(parse_proc_stat(STAT), Some((160, 1000)));
+        assert_eq!(parse_proc_stat("intr 1\n"), None);
+        assert_eq!(parse_psi_some_total(PSI_CPU), Some(2_000_000));
+        assert_eq!(parse_psi_some_total(""), None);
+    }
+
+    #[test]
+    fn test_sample_between_reads() {
+        let before = Counters { cpu_jiffies: Some((160, 1000)), cpu_some_us: Some(2_000_000), memory_some_us: Some(0), io_some_us: None };
+        let after = Counters { cpu_jiffies: Some((190, 1100)), cpu_some_us: Some(2_040_000), memory_some_us: Some(0), io_some_us: None };
+        let sample = sample_between(&before, &after, 100.0, 1_700_000_000_000.0);
+
+        assert_eq!(sample.cpu_busy_percent, Some(30.0));
+        assert_eq!(sample.cpu_some_percent, Some(40.0));
+        assert_eq!(sample.memory_some_percent, Some(0.0));
+        // Kernels without PSI leave the stall shares empty
+        assert_eq!(sample.io_some_percent, None);
+    }
+
+    #[tokio::test]
+    async fn test_sampler_collects_timestamped_samples() {
+        let started = now_utc_ms();
+        let sampler = PressureSampler::start_at(PathBuf::from("/"), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let results = sampler.finish();
+
+        assert!(results.samples.len() >= 3, "{} samples", results.samples.len());
+        assert!(results.samples.windows(2).all(|w| w[0].timestamp_ms < w[1].timestamp_ms));
+        assert!(results.samples[0].timestamp_ms > started);
+    }
+}
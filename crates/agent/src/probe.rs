@@ -0,0 +1,295 @@
+/// UDP echo probes between client and host
+use anyhow::Result;
+#[cfg(target_os = "linux")]
+use socket2::SockAddr;
+use socket2::SockRef;
+use std::net::SocketAddr;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+#[cfg(target_os = "linux")]
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+use crate::traffic::{OwnCounters, PeerCounters};
+
+/// Marks a datagram as a Chequer probe
+pub const PROBE_MAGIC: &[u8; 4] = b"CHQP";
+
+/// Magic (4) + sequence number (4) + received TOS flag (1) + received TOS (1) + reserved (2)
+pub const PROBE_HEADER_LEN: usize = 12;
+
+/// Set to 1 by the echo service when it filled in the received TOS byte
+const TOS_FLAG_OFFSET: usize = 8;
+
+/// TOS byte the probe arrived at the host with
+const TOS_OFFSET: usize = 9;
+
+/// Largest probe payload the echo service accepts
+pub const MAX_PROBE_SIZE: usize = 65507;
+
+/// Probes the reachability check sends before giving up
+const REACHABILITY_PROBES: usize = 3;
+
+/// How long the reachability check waits for each echo
+const REACHABILITY_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Host side: echo every valid probe back to its sender, counted in the sender's run
+pub async fn run_echo(socket: UdpSocket, peers: PeerCounters) -> Result<()> {
+    let mut buffer = vec![0u8; MAX_PROBE_SIZE];
+    let mut reply_tos = 0;
+
+    // Report the TOS byte each probe arrived with, for the DSCP marking test
+    if let Err(e) = SockRef::from(&socket).set_recv_tos(true) {
+        warn!("Cannot read the TOS byte of incoming probes: {}", e);
+    }
+
+    loop {
+        let (len, peer, tos) = match recv_with_tos(&socket, &mut buffer).await {
+            Ok(received) => received,
+            Err(e) => {
+                // ICMP errors from earlier replies surface here; keep serving
+                debug!("UDP echo receive error: {}", e);
+                continue;
+            }
+        };
+
+        if probe_seq(&buffer[..len]).is_none() {
+            continue;
+        }
+        let counters = peers.get(peer.ip());
+        if let Some(counters) = &counters {
+            counters.count_udp_received(len, peer);
+        }
+
+        if let Some(tos) = tos {
+            buffer[TOS_FLAG_OFFSET] = 1;
+            buffer[TOS_OFFSET] = tos;
+            // Mark the echo like the probe so both directions get the same treatment
+            if tos != reply_tos && SockRef::from(&socket).set_tos(tos as u32).is_ok() {
+                reply_tos = tos;
+            }
+        }
+
+        match socket.send_to(&buffer[..len], peer).await {
+            Ok(sent) => {
+                if let Some(counters) = &counters {
+                    counters.count_udp_sent(sent, peer);
+                }
+            }
+            Err(e) => warn!("Failed to echo probe to {}: {}", peer, e),
+        }
+    }
+}
+
+/// Receive a datagram along with the TOS byte it arrived with, if IP_RECVTOS is enabled
+#[cfg(target_os = "linux")]
+async fn recv_with_tos(socket: &UdpSocket, buffer: &mut [u8]) -> std::io::Result<(usize, SocketAddr, Option<u8>)> {
+    socket.async_io(Interest::READABLE, || unsafe {
+        let mut addr: libc::sockaddr_storage = std::mem::zeroed();
+        let mut control = [0u8; 64];
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_name = &mut addr as *mut _ as *mut libc::c_void;
+        msg.msg_namelen = std::mem::size_of_val(&addr) as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let len = libc::recvmsg(socket.as_raw_fd(), &mut msg, 0);
+        if len < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut tos = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_TOS {
+                tos = Some(*libc::CMSG_DATA(cmsg));
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+
+        let peer = SockAddr::new(addr, msg.msg_namelen)
+            .as_socket()
+            .ok_or_else(|| std::io::Error::other("unsupported peer address"))?;
+        Ok((len as usize, peer, tos))
+    }).await
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn recv_with_tos(socket: &UdpSocket, buffer: &mut [u8]) -> std::io::Result<(usize, SocketAddr, Option<u8>)> {
+    let (len, peer) = socket.recv_from(buffer).await?;
+    Ok((len, peer, None))
+}
+
+/// Whether the host's echo service answers a small probe at all
+///
+/// Run once before the UDP tests so a blocked port costs one short wait
+/// instead of every test timing out probe by probe.
+pub async fn echo_reachable(host: SocketAddr, counters: &Arc<OwnCounters>) -> bool {
+    let Ok(mut prober) = UdpProber::connect(host, counters).await else {
+        return false;
+    };
+    for _ in 0..REACHABILITY_PROBES {
+        match prober.probe(PROBE_HEADER_LEN, REACHABILITY_TIMEOUT).await {
+            Ok(Some(_)) => return true,
+            Ok(None) => {}
+            Err(e) => {
+                debug!("UDP echo reachability probe failed: {}", e);
+                return false;
+            }
+        }
+    }
+    false
+}
+
+/// Build a probe datagram of `size` bytes carrying `seq`
+pub fn probe_packet(seq: u32, size: usize) -> Vec<u8> {
+    let mut packet = vec![0u8; size.max(PROBE_HEADER_LEN)];
+    packet[..4].copy_from_slice(PROBE_MAGIC);
+    packet[4..8].copy_from_slice(&seq.to_be_bytes());
+    packet
+}
+
+/// Sequence number of a probe datagram, `None` if it is not a probe
+pub fn probe_seq(datagram: &[u8]) -> Option<u32> {
+    if datagram.len() < PROBE_HEADER_LEN || &datagram[..4] != PROBE_MAGIC {
+        return None;
+    }
+    Some(u32::from_be_bytes([datagram[4], datagram[5], datagram[6], datagram[7]]))
+}
+
+/// Client side: a UDP socket connected to the host's echo service
+pub struct UdpProber {
+    socket: UdpSocket,
+    host: SocketAddr,
+    counters: Arc<OwnCounters>,
+    buffer: Vec<u8>,
+    next_seq: u32,
+    echoed_tos: Option<u8>,
+    refused: bool,
+}
+
+impl UdpProber {
+    /// Bind a local socket and connect it to the host's echo service, counting its traffic in `counters`
+    pub async fn connect(host: SocketAddr, counters: &Arc<OwnCounters>) -> Result<Self> {
+        let local: SocketAddr = if host.is_ipv4() {
+            "0.0.0.0:0".parse()?
+        } else {
+            "[::]:0".parse()?
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(host).await?;
+
+        Ok(Self {
+            socket,
+            host,
+            counters: Arc::clone(counters),
+            buffer: vec![0u8; MAX_PROBE_SIZE],
+            next_seq: 0,
+            echoed_tos: None,
+            refused: false,
+        })
+    }
+
+    /// The underlying socket, for setting socket options
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// TOS byte the host saw on the last probe whose echo arrived, if the host could read it
+    pub fn echoed_tos(&self) -> Option<u8> {
+        self.echoed_tos
+    }
+
+    /// Whether the host answered a probe with ICMP port unreachable
+    pub fn refused(&self) -> bool {
+        self.refused
+    }
+
+    /// Build a probe datagram of `size` bytes with a fresh sequence number
+    fn packet(&mut self, size: usize) -> (u32, Vec<u8>) {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        (seq, probe_packet(seq, size))
+    }
+
+    /// Send one probe of `size` bytes and wait for its echo
+    ///
+    /// Returns the round-trip time, or `None` if no echo arrived within `timeout`.
+    pub async fn probe(&mut self, size: usize, timeout: Duration) -> Result<Option<Duration>> {
+        let (seq, packet) = self.packet(size);
+        let start = Instant::now();
+        self.counters.count_udp_sent(self.socket.send(&packet).await?, self.host);
+
+        Ok(self.wait_for(seq, start + timeout).await?.map(|at| at.duration_since(start)))
+    }
+
+    /// Send two probes of `size` bytes back to back and measure how far apart the echoes arrive
+    pub async fn probe_pair(&mut self, size: usize, timeout: Duration) -> Result<Option<Duration>> {
+        let (first_seq, first) = self.packet(size);
+        let (second_seq, second) = self.packet(size);
+        let deadline = Instant::now() + timeout;
+
+        self.counters.count_udp_sent(self.socket.send(&first).await?, self.host);
+        self.counters.count_udp_sent(self.socket.send(&second).await?, self.host);
+
+        let Some(first_at) = self.wait_for(first_seq, deadline).await? else {
+            return Ok(None);
+        };
+        let Some(second_at) = self.wait_for(second_seq, deadline).await? else {
+            return Ok(None);
+        };
+        Ok(Some(second_at.saturating_duration_since(first_at)))
+    }
+
+    /// Wait for the echo of `seq`, discarding stale echoes of earlier probes
+    async fn wait_for(&mut self, seq: u32, deadline: Instant) -> Result<Option<Instant>> {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let received = tokio::time::timeout(remaining, self.socket.recv(&mut self.buffer)).await;
+            let len = match received {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    self.refused = true;
+                    return Ok(None);
+                }
+                Ok(result) => result?,
+            };
+            let at = Instant::now();
+            self.counters.count_udp_received(len, self.host);
+
+            if probe_seq(&self.buffer[..len]) == Some(seq) {
+                self.echoed_tos = (self.buffer[TOS_FLAG_OFFSET] == 1).then_some(self.buffer[TOS_OFFSET]);
+                return Ok(Some(at));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_echo_reachability() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo_addr = echo.local_addr().unwrap();
+        tokio::spawn(run_echo(echo, PeerCounters::default()));
+        let counters = Arc::new(OwnCounters::default());
+        assert!(echo_reachable(echo_addr, &counters).await);
+
+        // Bound but never answering, like a host behind a firewall dropping UDP
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let start = Instant::now();
+        assert!(!echo_reachable(silent.local_addr().unwrap(), &counters).await);
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,83 @@
+/// DSCP marking preservation test over UDP probes
+use anyhow::Result;
+use chequer_common::{stats, QosResults, TrafficClassResult};
+use socket2::SockRef;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::probe::UdpProber;
+use crate::traffic::OwnCounters;
+
+/// Traffic classes probed, from best effort to voice
+pub const TRAFFIC_CLASSES: [(&str, u8); 4] = [
+    ("BE", 0),
+    ("CS1", 8),
+    ("AF41", 34),
+    ("EF", 46),
+];
+
+/// Payload size of a typical video packet
+const QOS_PROBE_SIZE: usize = 1200;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Probe each traffic class in turn and record the marking the host received
+///
+/// Classes are interleaved probe by probe so that changing conditions on the
+/// path affect all of them alike. Returns `None` if the first round got no
+/// echo from any class.
+pub async fn run_qos_test(host: SocketAddr, probes_per_class: usize, counters: &Arc<OwnCounters>) -> Result<Option<QosResults>> {
+    info!("Running DSCP marking test ({} classes, {} probes each)...", TRAFFIC_CLASSES.len(), probes_per_class);
+
+    let mut probers = Vec::with_capacity(TRAFFIC_CLASSES.len());
+    for &(name, dscp) in &TRAFFIC_CLASSES {
+        let prober = UdpProber::connect(host, counters).await?;
+        // DSCP is the upper six bits of the TOS byte
+        if let Err(e) = SockRef::from(prober.socket()).set_tos((dscp as u32) << 2) {
+            warn!("Cannot mark probes as {} (DSCP {}): {}", name, dscp, e);
+        }
+        probers.push(prober);
+    }
+
+    let mut rtts: Vec<Vec<f64>> = vec![Vec::new(); TRAFFIC_CLASSES.len()];
+    let mut received: Vec<HashMap<u8, usize>> = vec![HashMap::new(); TRAFFIC_CLASSES.len()];
+    for round in 0..probes_per_class {
+        for (i, prober) in probers.iter_mut().enumerate() {
+            if let Some(rtt) = prober.probe(QOS_PROBE_SIZE, PROBE_TIMEOUT).await? {
+                rtts[i].push(rtt.as_secs_f64() * 1000.0);
+                if let Some(tos) = prober.echoed_tos() {
+                    *received[i].entry(tos >> 2).or_default() += 1;
+                }
+            }
+        }
+
+        // A round without a single echo means UDP is not getting through; stop rather than time out every probe
+        if round == 0 && rtts.iter().all(|r| r.is_empty()) {
+            warn!("No UDP echoes received from {}; skipping DSCP results", host);
+            return Ok(None);
+        }
+    }
+
+    let classes: Vec<TrafficClassResult> = TRAFFIC_CLASSES.iter()
+        .zip(rtts.iter().zip(&received))
+        .map(|(&(name, dscp), (rtts, received))| TrafficClassResult {
+            name: name.to_string(),
+            dscp,
+            received_dscp: received.iter().max_by_key(|(_, &count)| count).map(|(&dscp, _)| dscp),
+            sent: probes_per_class,
+            lost: probes_per_class - rtts.len(),
+            avg_rtt_ms: (!rtts.is_empty()).then(|| stats::mean(rtts)),
+            median_rtt_ms: (!rtts.is_empty()).then(|| stats::percentile(rtts, 50.0)),
+            p95_rtt_ms: (!rtts.is_empty()).then(|| stats::percentile(rtts, 95.0)),
+        })
+        .collect();
+
+    for class in &classes {
+        info!("DSCP {} ({}): received as {:?}, {} of {} lost",
+              class.dscp, class.name, class.received_dscp, class.lost, class.sent);
+    }
+
+    Ok(Some(QosResults { classes }))
+}
@@ -0,0 +1,90 @@
+/// Send time computation for latency probe schedules
+use anyhow::{bail, Result};
+use chequer_common::ProbeSchedule;
+use rand::Rng;
+use std::time::Duration;
+
+/// Compute the send time of each probe relative to the start of the test
+///
+/// Returns `None` for `ProbeSchedule::Sequential`, whose send times depend on the replies,
+/// and an error for bursts that do not finish before the next one starts.
+pub fn send_offsets(schedule: ProbeSchedule, interval_ms: u64, count: usize) -> Result<Option<Vec<Duration>>> {
+    let interval = Duration::from_millis(interval_ms);
+
+    let offsets = match schedule {
+        ProbeSchedule::Sequential => return Ok(None),
+        ProbeSchedule::FixedRate => (0..count).map(|i| interval * i as u32).collect(),
+        ProbeSchedule::Poisson => {
+            let mut rng = rand::thread_rng();
+            let mut next = Duration::ZERO;
+            (0..count)
+                .map(|_| {
+                    let offset = next;
+                    // Inverse transform sampling of the exponential distribution
+                    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+                    next += interval.mul_f64(-u.ln());
+                    offset
+                })
+                .collect()
+        }
+        ProbeSchedule::Burst { rate_hz, burst_size } => {
+            let spacing = Duration::from_secs_f64(1.0 / rate_hz.max(1) as f64);
+            let burst_size = burst_size.max(1);
+            // Overlapping bursts would send out of order and leave no gap between them
+            if spacing * burst_size as u32 >= interval {
+                bail!(
+                    "A burst of {} probes at {} Hz takes {:.1}ms, not less than the {}ms burst interval",
+                    burst_size, rate_hz, (spacing * burst_size as u32).as_secs_f64() * 1000.0, interval_ms
+                );
+            }
+            (0..count)
+                .map(|i| interval * (i / burst_size) as u32 + spacing * (i % burst_size) as u32)
+                .collect()
+        }
+    };
+
+    Ok(Some(offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_rate_ignores_rtt() {
+        let offsets = send_offsets(ProbeSchedule::FixedRate, 10, 4).unwrap().unwrap();
+        assert_eq!(offsets, vec![
+            Duration::ZERO,
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            Duration::from_millis(30),
+        ]);
+        assert!(send_offsets(ProbeSchedule::Sequential, 10, 4).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_poisson_mean_interval() {
+        let offsets = send_offsets(ProbeSchedule::Poisson, 10, 5000).unwrap().unwrap();
+        assert!(offsets.windows(2).all(|w| w[1] >= w[0]));
+        let mean_gap_ms = offsets.last().unwrap().as_secs_f64() * 1000.0 / 4999.0;
+        assert!((mean_gap_ms - 10.0).abs() < 1.0, "mean gap {}", mean_gap_ms);
+    }
+
+    #[test]
+    fn test_burst_spacing() {
+        let schedule = ProbeSchedule::Burst { rate_hz: 1000, burst_size: 3 };
+        let offsets = send_offsets(schedule, 100, 6).unwrap().unwrap();
+        let ms: Vec<u128> = offsets.iter().map(|d| d.as_millis()).collect();
+        assert_eq!(ms, vec![0, 1, 2, 100, 101, 102]);
+    }
+
+    #[test]
+    fn test_bursts_must_fit_their_interval() {
+        // Ten probes at 1 kHz fill a 10ms interval completely
+        assert!(send_offsets(ProbeSchedule::Burst { rate_hz: 1000, burst_size: 10 }, 10, 20).is_err());
+        assert!(send_offsets(ProbeSchedule::Burst { rate_hz: 1000, burst_size: 50 }, 10, 100).is_err());
+
+        let offsets = send_offsets(ProbeSchedule::Burst { rate_hz: 1000, burst_size: 5 }, 10, 20).unwrap().unwrap();
+        assert!(offsets.windows(2).all(|w| w[1] > w[0]));
+    }
+}
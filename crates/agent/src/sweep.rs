@@ -0,0 +1,182 @@
+/// Packet size sweep and link capacity estimation over UDP probes
+use anyhow::Result;
+use chequer_common::{stats, LinkCapacityResults, PacketSizePoint};
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::probe::UdpProber;
+
+/// Payload sizes from input-sized packets up to a full 1500 byte MTU datagram
+pub const SWEEP_SIZES: [usize; 9] = [64, 128, 256, 512, 768, 1024, 1200, 1400, 1472];
+
+/// Number of back-to-back packet pairs sent for the dispersion estimate
+const PACKET_PAIRS: usize = 20;
+
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Sweep probe sizes, fit RTT against size and measure packet-pair dispersion
+///
+/// Stops at the first size that gets no echo at all, since larger sizes will
+/// only time out too; packet pairs then use the largest size that got through.
+pub async fn run_packet_size_sweep(prober: &mut UdpProber, probes_per_size: usize) -> Result<LinkCapacityResults> {
+    info!("Running packet size sweep ({} sizes, {} probes each)...", SWEEP_SIZES.len(), probes_per_size);
+
+    let mut points = Vec::with_capacity(SWEEP_SIZES.len());
+    for &size in &SWEEP_SIZES {
+        let mut rtts = Vec::with_capacity(probes_per_size);
+        for _ in 0..probes_per_size {
+            if let Some(rtt) = prober.probe(size, PROBE_TIMEOUT).await? {
+                rtts.push(rtt.as_secs_f64() * 1000.0);
+            }
+        }
+
+        let received = !rtts.is_empty();
+        points.push(PacketSizePoint {
+            payload_bytes: size,
+            min_rtt_ms: received.then(|| rtts.iter().cloned().fold(f64::INFINITY, f64::min)),
+            median_rtt_ms: received.then(|| stats::percentile(&rtts, 50.0)),
+            sent: probes_per_size,
+            lost: probes_per_size - rtts.len(),
+        });
+        if !received {
+            warn!("No echoes received for {} byte probes; skipping larger sizes", size);
+            break;
+        }
+    }
+
+    let largest = points.iter().rev().find(|p| p.min_rtt_ms.is_some()).map(|p| p.payload_bytes);
+    let mut dispersions = Vec::with_capacity(PACKET_PAIRS);
+    if let Some(largest) = largest {
+        for _ in 0..PACKET_PAIRS {
+            if let Some(gap) = prober.probe_pair(largest, PROBE_TIMEOUT).await? {
+                dispersions.push(gap.as_secs_f64());
+            }
+        }
+    }
+
+    let (base_rtt_ms, per_byte_ms) = fit_size_rtt(&points).unwrap_or((0.0, 0.0));
+    let results = LinkCapacityResults {
+        base_rtt_ms,
+        per_byte_us: per_byte_ms * 1000.0,
+        estimated_capacity_mbps: capacity_from_slope(per_byte_ms),
+        packet_pair_capacity_mbps: largest.and_then(|size| capacity_from_dispersion(size, &dispersions)),
+        points,
+    };
+
+    info!("Packet size sweep complete - {:.3}us/byte, capacity: {:?} Mbps (fit), {:?} Mbps (packet pair)",
+          results.per_byte_us, results.estimated_capacity_mbps, results.packet_pair_capacity_mbps);
+
+    Ok(results)
+}
+
+/// Least-squares fit of minimum RTT (ms) against payload size, returning (intercept, slope)
+pub fn fit_size_rtt(points: &[PacketSizePoint]) -> Option<(f64, f64)> {
+    let valid: Vec<(f64, f64)> = points.iter()
+        .filter_map(|p| p.min_rtt_ms.map(|rtt| (p.payload_bytes as f64, rtt)))
+        .collect();
+    if valid.len() < 2 {
+        return None;
+    }
+
+    let n = valid.len() as f64;
+    let mean_x = valid.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = valid.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = valid.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let sxy: f64 = valid.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    Some((mean_y - slope * mean_x, slope))
+}
+
+/// Capacity implied by the round-trip per-byte delay
+///
+/// Echoed probes cross the bottleneck in both directions, so each byte is
+/// serialized twice per round trip.
+pub fn capacity_from_slope(per_byte_ms: f64) -> Option<f64> {
+    if per_byte_ms <= 0.0 {
+        return None;
+    }
+    let seconds_per_bit = per_byte_ms / 1000.0 / 2.0 / 8.0;
+    Some(1.0 / seconds_per_bit / 1_000_000.0)
+}
+
+/// Capacity from the median arrival gap of back-to-back packet pairs
+pub fn capacity_from_dispersion(size: usize, gaps_secs: &[f64]) -> Option<f64> {
+    let positive: Vec<f64> = gaps_secs.iter().cloned().filter(|&g| g > 0.0).collect();
+    if positive.is_empty() {
+        return None;
+    }
+    let gap = stats::percentile(&positive, 50.0);
+    Some(size as f64 * 8.0 / gap / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(size: usize, min_rtt_ms: Option<f64>) -> PacketSizePoint {
+        PacketSizePoint {
+            payload_bytes: size,
+            min_rtt_ms,
+            median_rtt_ms: min_rtt_ms,
+            sent: 10,
+            lost: if min_rtt_ms.is_some() { 0 } else { 10 },
+        }
+    }
+
+    #[test]
+    fn test_fit_recovers_serialization_delay() {
+        // 20 Mbps bottleneck: 0.4us per byte each way, 0.8us per byte round trip
+        let points: Vec<PacketSizePoint> = SWEEP_SIZES.iter()
+            .map(|&size| point(size, Some(2.0 + size as f64 * 0.0008)))
+            .collect();
+
+        let (intercept, slope) = fit_size_rtt(&points).unwrap();
+        assert!((intercept - 2.0).abs() < 1e-9);
+        assert!((slope - 0.0008).abs() < 1e-12);
+
+        let capacity = capacity_from_slope(slope).unwrap();
+        assert!((capacity - 20.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_capacity_from_slope() {
+        // 100 Mbps each way: 80ns per byte per direction, 160ns per byte round trip
+        let capacity = capacity_from_slope(0.00016).unwrap();
+        assert!((capacity - 100.0).abs() < 1e-6);
+        assert!(capacity_from_slope(0.0).is_none());
+        assert!(capacity_from_slope(-0.001).is_none());
+    }
+
+    #[test]
+    fn test_capacity_from_dispersion() {
+        // 1472 bytes arriving 117.76us apart is 100 Mbps
+        let capacity = capacity_from_dispersion(1472, &[0.00011776, 0.0, 0.00011776, 0.0002]).unwrap();
+        assert!((capacity - 100.0).abs() < 1e-6);
+        assert!(capacity_from_dispersion(1472, &[0.0]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_stops_when_nothing_echoes() {
+        // A bound socket that never answers, like a host behind a firewall
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let counters = Default::default();
+        let mut prober = UdpProber::connect(silent.local_addr().unwrap(), &counters).await.unwrap();
+
+        let started = std::time::Instant::now();
+        let results = run_packet_size_sweep(&mut prober, 2).await.unwrap();
+        assert_eq!(results.points.len(), 1);
+        assert_eq!(results.packet_pair_capacity_mbps, None);
+        assert!(started.elapsed() < PROBE_TIMEOUT * 3, "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_fit_skips_lost_sizes() {
+        let points = vec![point(64, Some(1.0)), point(512, None), point(1472, Some(2.0))];
+        let (_, slope) = fit_size_rtt(&points).unwrap();
+        assert!((slope - 1.0 / 1408.0).abs() < 1e-12);
+        assert!(fit_size_rtt(&points[..1]).is_none());
+    }
+}
@@ -0,0 +1,179 @@
+/// OS and hardware environment probe from procfs, sysfs and os-release
+use chequer_common::{DisplayInfo, GpuInfo, PowerState, SystemEnvironment};
+use std::fs;
+use std::path::Path;
+use crate::power;
+
+/// DMI board names of the Steam Deck LCD and OLED models
+const STEAM_DECK_BOARDS: [&str; 2] = ["Jupiter", "Galileo"];
+
+/// Describe this machine
+pub fn probe() -> SystemEnvironment {
+    read_environment(Path::new("/"))
+}
+
+/// Read the environment from files under `root`
+pub fn read_environment(root: &Path) -> SystemEnvironment {
+    let read = |path: &str| fs::read_to_string(root.join(path)).ok();
+    let read_trimmed = |path: &str| read(path).map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+
+    let cpuinfo = read("proc/cpuinfo").unwrap_or_default();
+    let board_name = read_trimmed("sys/class/dmi/id/board_name");
+
+    SystemEnvironment {
+        os: read("etc/os-release")
+            .or_else(|| read("usr/lib/os-release"))
+            .and_then(|contents| os_release_name(&contents)),
+        kernel: read_trimmed("proc/sys/kernel/osrelease"),
+        cpu_model: cpuinfo.lines()
+            .find_map(|line| line.strip_prefix("model name"))
+            .and_then(|rest| rest.split_once(':'))
+            .map(|(_, model)| model.trim().to_string()),
+        cpu_threads: Some(cpuinfo.lines().filter(|line| line.starts_with("processor")).count())
+            .filter(|&count| count > 0),
+        cpu_governor: read_trimmed("sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+        memory_mb: read("proc/meminfo")
+            .and_then(|meminfo| meminfo_total_kb(&meminfo))
+            .map(|kb| kb / 1024),
+        gpus: read_gpus(root),
+        displays: read_displays(root),
+        steam_deck: board_name.as_deref().is_some_and(|name| STEAM_DECK_BOARDS.contains(&name)),
+        board_name,
+        power: Some(power::read_power(root)).filter(|state| *state != PowerState::default()),
+    }
+}
+
+/// PRETTY_NAME, or NAME, from an os-release file
+fn os_release_name(contents: &str) -> Option<String> {
+    let value = |key: &str| contents.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim().trim_matches('"').to_string());
+    value("PRETTY_NAME").or_else(|| value("NAME"))
+}
+
+fn meminfo_total_kb(meminfo: &str) -> Option<u64> {
+    meminfo.lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|kb| kb.parse().ok())
+}
+
+/// DRM cards with a PCI device, e.g. `card0`; connectors like `card0-eDP-1` are skipped
+fn read_gpus(root: &Path) -> Vec<GpuInfo> {
+    let Ok(entries) = fs::read_dir(root.join("sys/class/drm")) else {
+        return Vec::new();
+    };
+
+    let mut gpus: Vec<GpuInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let card = entry.file_name().to_string_lossy().into_owned();
+            if !card.starts_with("card") || card.contains('-') {
+                return None;
+            }
+            let device = entry.path().join("device");
+            let vendor_id = fs::read_to_string(device.join("vendor")).ok()?.trim().to_string();
+            Some(GpuInfo {
+                vendor: pci_vendor_name(&vendor_id).map(str::to_string).unwrap_or(vendor_id),
+                device_id: fs::read_to_string(device.join("device")).ok().map(|id| id.trim().to_string()),
+                driver: fs::read_link(device.join("driver")).ok()
+                    .and_then(|target| Some(target.file_name()?.to_string_lossy().into_owned())),
+                card,
+            })
+        })
+        .collect();
+
+    gpus.sort_by(|a, b| a.card.cmp(&b.card));
+    gpus
+}
+
+fn pci_vendor_name(vendor_id: &str) -> Option<&'static str> {
+    match vendor_id {
+        "0x1002" => Some("AMD"),
+        "0x10de" => Some("NVIDIA"),
+        "0x8086" => Some("Intel"),
+        _ => None,
+    }
+}
+
+/// Connected DRM connectors and their modes
+fn read_displays(root: &Path) -> Vec<DisplayInfo> {
+    let Ok(entries) = fs::read_dir(root.join("sys/class/drm")) else {
+        return Vec::new();
+    };
+
+    let mut displays: Vec<DisplayInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Connectors are named after their card, e.g. card0-eDP-1
+            let (_, connector) = name.strip_prefix("card")?.split_once('-')?;
+            let status = fs::read_to_string(entry.path().join("status")).ok()?;
+            if status.trim() != "connected" {
+                return None;
+            }
+
+            let mut modes: Vec<String> = Vec::new();
+            for mode in fs::read_to_string(entry.path().join("modes")).unwrap_or_default().lines() {
+                if !modes.iter().any(|m| m == mode) {
+                    modes.push(mode.to_string());
+                }
+            }
+            Some(DisplayInfo { connector: connector.to_string(), modes })
+        })
+        .collect();
+
+    displays.sort_by(|a, b| a.connector.cmp(&b.connector));
+    displays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn fixture(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sysenv").join(path)
+    }
+
+    #[test]
+    fn test_read_steam_deck_environment() {
+        let env = read_environment(&fixture("steamdeck"));
+
+        assert_eq!(env.os.as_deref(), Some("SteamOS"));
+        assert_eq!(env.kernel.as_deref(), Some("6.5.0-valve22-1-neptune-65"));
+        assert_eq!(env.cpu_model.as_deref(), Some("AMD Custom APU 0405"));
+        assert_eq!(env.cpu_threads, Some(8));
+        assert_eq!(env.cpu_governor.as_deref(), Some("schedutil"));
+        assert_eq!(env.memory_mb, Some(14_825));
+        assert_eq!(env.board_name.as_deref(), Some("Jupiter"));
+        assert!(env.steam_deck);
+
+        assert_eq!(env.gpus.len(), 1);
+        assert_eq!(env.gpus[0].card, "card0");
+        assert_eq!(env.gpus[0].vendor, "AMD");
+        assert_eq!(env.gpus[0].device_id.as_deref(), Some("0x163f"));
+        assert_eq!(env.gpus[0].driver.as_deref(), Some("amdgpu"));
+
+        // The disconnected DisplayPort output is left out; duplicate modes are collapsed
+        assert_eq!(env.displays.len(), 1);
+        assert_eq!(env.displays[0].connector, "eDP-1");
+        assert_eq!(env.displays[0].modes, vec!["800x1280", "640x480"]);
+    }
+
+    #[test]
+    fn test_missing_files_leave_fields_empty() {
+        let env = read_environment(&fixture("does-not-exist"));
+        assert!(env.os.is_none() && env.cpu_threads.is_none() && env.memory_mb.is_none());
+        assert!(env.gpus.is_empty() && env.displays.is_empty());
+        assert!(!env.steam_deck);
+        assert!(env.power.is_none());
+    }
+
+    #[test]
+    fn test_os_release_name() {
+        assert_eq!(os_release_name("NAME=\"Arch Linux\"\nPRETTY_NAME=\"Arch Linux\"\n").as_deref(), Some("Arch Linux"));
+        assert_eq!(os_release_name("NAME=Fedora\n").as_deref(), Some("Fedora"));
+        assert_eq!(os_release_name("ID=debian\n"), None);
+    }
+}
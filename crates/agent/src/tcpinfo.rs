@@ -0,0 +1,70 @@
+/// Kernel TCP statistics of a connected socket (`TCP_INFO`)
+use chequer_common::TcpInfoSnapshot;
+use std::os::unix::io::RawFd;
+use tracing::debug;
+
+/// Read `TCP_INFO` for `fd`, `None` where the kernel does not provide it
+pub fn read_tcp_info(fd: RawFd) -> Option<TcpInfoSnapshot> {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let mut info: libc::tcp_info = std::mem::zeroed();
+        let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+        let result = libc::getsockopt(
+            fd,
+            libc::IPPROTO_TCP,
+            libc::TCP_INFO,
+            &mut info as *mut _ as *mut libc::c_void,
+            &mut len,
+        );
+        if result != 0 {
+            debug!("Cannot read TCP_INFO: {}", std::io::Error::last_os_error());
+            return None;
+        }
+
+        // The kernel reports times in microseconds
+        Some(TcpInfoSnapshot {
+            rtt_ms: info.tcpi_rtt as f64 / 1000.0,
+            rttvar_ms: info.tcpi_rttvar as f64 / 1000.0,
+            rto_ms: info.tcpi_rto as f64 / 1000.0,
+            snd_cwnd: info.tcpi_snd_cwnd,
+            total_retrans: info.tcpi_total_retrans,
+            lost: info.tcpi_lost,
+            bytes_sent: info.tcpi_bytes_sent,
+            bytes_received: info.tcpi_bytes_received,
+            segs_out: info.tcpi_segs_out,
+            segs_in: info.tcpi_segs_in,
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        debug!("TCP_INFO is not supported on this platform (fd {})", fd);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::os::unix::io::AsRawFd;
+
+    #[test]
+    fn test_read_tcp_info_of_loopback_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+
+        client.write_all(b"ping").unwrap();
+        let mut buffer = [0u8; 4];
+        server.read_exact(&mut buffer).unwrap();
+
+        let info = read_tcp_info(client.as_raw_fd()).expect("no TCP_INFO");
+        assert!(info.rtt_ms > 0.0);
+        assert!(info.rto_ms >= info.rtt_ms);
+        assert!(info.snd_cwnd > 0);
+        assert_eq!(info.total_retrans, 0);
+        assert_eq!(info.bytes_sent, 4);
+        assert!(info.segs_out >= 2);
+    }
+}
@@ -0,0 +1,219 @@
+/// UDP probe round trips timed with kernel software timestamps (`SO_TIMESTAMPING`)
+use anyhow::Result;
+use chequer_common::KernelRttResults;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+use crate::traffic::OwnCounters;
+
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::time::Instant;
+#[cfg(target_os = "linux")]
+use tokio::io::Interest;
+#[cfg(target_os = "linux")]
+use tokio::net::UdpSocket;
+#[cfg(target_os = "linux")]
+use crate::probe::{self, PROBE_HEADER_LEN};
+
+#[cfg(target_os = "linux")]
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Send `count` probes `interval` apart and time each one in the application and in the kernel
+///
+/// The kernel RTT runs from the TX software timestamp, taken as the probe is
+/// handed to the driver, to the RX software timestamp of its echo. Returns
+/// `None` if the kernel provided no timestamps.
+#[cfg(target_os = "linux")]
+pub async fn run_kernel_rtt_test(host: SocketAddr, count: usize, interval: Duration, counters: &Arc<OwnCounters>) -> Result<Option<KernelRttResults>> {
+    info!("Running kernel-timestamped UDP RTT test ({} probes)...", count);
+
+    let local: SocketAddr = if host.is_ipv4() { "0.0.0.0:0".parse()? } else { "[::]:0".parse()? };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(host).await?;
+    if let Err(e) = enable_timestamping(&socket) {
+        warn!("Kernel timestamping is not available: {}", e);
+        return Ok(None);
+    }
+
+    // With SOF_TIMESTAMPING_OPT_ID the kernel numbers datagrams from zero, matching the sequence numbers
+    let mut app_rtts: HashMap<u32, f64> = HashMap::new();
+    let mut rx_stamps: HashMap<u32, Duration> = HashMap::new();
+    let mut tx_stamps: HashMap<u32, Duration> = HashMap::new();
+    let mut buffer = vec![0u8; PROBE_HEADER_LEN];
+
+    let mut ticker = tokio::time::interval(interval);
+    for seq in 0..count as u32 {
+        ticker.tick().await;
+        let start = Instant::now();
+        counters.count_udp_sent(socket.send(&probe::probe_packet(seq, PROBE_HEADER_LEN)).await?, host);
+
+        let deadline = tokio::time::Instant::from_std(start + PROBE_TIMEOUT);
+        while let Ok(received) = tokio::time::timeout_at(deadline, recv_with_timestamp(&socket, &mut buffer)).await {
+            let (len, rx) = match received {
+                Ok(received) => received,
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => break,
+                Err(e) => return Err(e.into()),
+            };
+            let at = Instant::now();
+            counters.count_udp_received(len, host);
+            if probe::probe_seq(&buffer[..len]) == Some(seq) {
+                app_rtts.insert(seq, at.duration_since(start).as_secs_f64() * 1000.0);
+                if let Some(rx) = rx {
+                    rx_stamps.insert(seq, rx);
+                }
+                break;
+            }
+        }
+
+        tx_stamps.extend(take_tx_timestamps(&socket));
+    }
+    tx_stamps.extend(take_tx_timestamps(&socket));
+
+    let mut app_rtt_ms = Vec::new();
+    let mut kernel_rtt_ms = Vec::new();
+    for seq in 0..count as u32 {
+        if let (Some(&app), Some(rx), Some(tx)) = (app_rtts.get(&seq), rx_stamps.get(&seq), tx_stamps.get(&seq)) {
+            app_rtt_ms.push(app);
+            kernel_rtt_ms.push(rx.saturating_sub(*tx).as_secs_f64() * 1000.0);
+        }
+    }
+
+    if kernel_rtt_ms.is_empty() {
+        warn!("No kernel timestamps received from {} echoes; skipping kernel RTT results", app_rtts.len());
+        return Ok(None);
+    }
+
+    let results = KernelRttResults { sent: count, app_rtt_ms, kernel_rtt_ms };
+    let overhead = results.stack_overhead_ms();
+    info!("Kernel RTT test complete - {} of {} probes timestamped, median stack overhead {:.3}ms",
+          results.kernel_rtt_ms.len(), count, chequer_common::stats::percentile(&overhead, 50.0));
+
+    Ok(Some(results))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn run_kernel_rtt_test(host: SocketAddr, count: usize, interval: Duration, _counters: &Arc<OwnCounters>) -> Result<Option<KernelRttResults>> {
+    warn!("Kernel timestamping is only supported on Linux; skipping {} probes to {} at {:?}", count, host, interval);
+    Ok(None)
+}
+
+/// Ask for software TX and RX timestamps, with TX timestamps numbered and without the payload
+#[cfg(target_os = "linux")]
+fn enable_timestamping(socket: &UdpSocket) -> std::io::Result<()> {
+    let flags: libc::c_uint = libc::SOF_TIMESTAMPING_SOFTWARE
+        | libc::SOF_TIMESTAMPING_TX_SOFTWARE
+        | libc::SOF_TIMESTAMPING_RX_SOFTWARE
+        | libc::SOF_TIMESTAMPING_OPT_ID
+        | libc::SOF_TIMESTAMPING_OPT_TSONLY;
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMPING,
+            &flags as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&flags) as libc::socklen_t,
+        )
+    };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receive a datagram with its RX software timestamp
+#[cfg(target_os = "linux")]
+async fn recv_with_timestamp(socket: &UdpSocket, buffer: &mut [u8]) -> std::io::Result<(usize, Option<Duration>)> {
+    socket.async_io(Interest::READABLE, || {
+        let (len, stamp, _) = recvmsg_timestamped(socket.as_raw_fd(), buffer, 0)?;
+        Ok((len, stamp))
+    }).await
+}
+
+/// Drain the error queue of TX software timestamps, keyed by datagram number
+#[cfg(target_os = "linux")]
+fn take_tx_timestamps(socket: &UdpSocket) -> Vec<(u32, Duration)> {
+    let mut stamps = Vec::new();
+    let mut buffer = [0u8; 64];
+    while let Ok((_, Some(stamp), Some(id))) =
+        recvmsg_timestamped(socket.as_raw_fd(), &mut buffer, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT)
+    {
+        stamps.push((id, stamp));
+    }
+    stamps
+}
+
+/// `recvmsg` returning the software timestamp and, for error queue messages, the datagram number
+#[cfg(target_os = "linux")]
+fn recvmsg_timestamped(fd: libc::c_int, buffer: &mut [u8], flags: libc::c_int) -> std::io::Result<(usize, Option<Duration>, Option<u32>)> {
+    unsafe {
+        let mut control = [0u8; 256];
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let len = libc::recvmsg(fd, &mut msg, flags);
+        if len < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut stamp = None;
+        let mut id = None;
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            let (level, kind) = ((*cmsg).cmsg_level, (*cmsg).cmsg_type);
+            if level == libc::SOL_SOCKET && kind == libc::SCM_TIMESTAMPING {
+                // struct scm_timestamping: software, deprecated and hardware timestamps
+                let stamps: [libc::timespec; 3] = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const _);
+                let software = stamps[0];
+                if software.tv_sec != 0 || software.tv_nsec != 0 {
+                    stamp = Some(Duration::new(software.tv_sec as u64, software.tv_nsec as u32));
+                }
+            } else if (level == libc::IPPROTO_IP && kind == libc::IP_RECVERR)
+                || (level == libc::IPPROTO_IPV6 && kind == libc::IPV6_RECVERR)
+            {
+                let err: libc::sock_extended_err = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const _);
+                if err.ee_origin == libc::SO_EE_ORIGIN_TIMESTAMPING {
+                    id = Some(err.ee_data);
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+
+        Ok((len as usize, stamp, id))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::traffic::PeerCounters;
+
+    #[tokio::test]
+    async fn test_kernel_rtt_within_application_rtt() {
+        let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let host = echo.local_addr().unwrap();
+        let server = tokio::spawn(probe::run_echo(echo, PeerCounters::default()));
+
+        let counters = Arc::new(OwnCounters::default());
+        let results = run_kernel_rtt_test(host, 20, Duration::from_millis(1), &counters).await.unwrap()
+            .expect("no kernel timestamps on loopback");
+        server.abort();
+
+        assert_eq!(results.sent, 20);
+        assert_eq!(results.app_rtt_ms.len(), results.kernel_rtt_ms.len());
+        assert!(!results.kernel_rtt_ms.is_empty());
+        // The kernel stamps after the application sends and before it wakes up
+        assert!(results.stack_overhead_ms().iter().all(|&overhead| overhead >= 0.0));
+    }
+}
@@ -0,0 +1,306 @@
+/// Background traffic accounting from `/proc/net/dev`, with Chequer's own traffic counted separately
+use chequer_common::{InterfaceTraffic, TrafficPhase, TrafficResults};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::tcpinfo;
+
+/// Length of the quiet windows before and after the tests
+pub const QUIET_WINDOW: Duration = Duration::from_millis(500);
+
+const ETHERNET_HEADER_BYTES: u64 = 14;
+
+const UDP_HEADER_BYTES: u64 = 8;
+
+/// TCP header with the timestamp option
+const TCP_HEADER_BYTES: u64 = 32;
+
+/// Segments of a TCP connection opened and closed without data: SYN, ACK, FIN
+/// and the last ACK from the connecting side; SYN-ACK, FIN and ACK back
+pub const TCP_CHECK_SEGMENTS: (u64, u64) = (4, 3);
+
+/// Ethernet and IP headers of a packet to or from `peer`
+///
+/// IPv4-mapped addresses on a dual-stack socket travel as IPv4.
+fn frame_overhead(peer: SocketAddr) -> u64 {
+    let ip_header = match peer.ip().to_canonical() {
+        IpAddr::V4(_) => 20,
+        IpAddr::V6(_) => 40,
+    };
+    ETHERNET_HEADER_BYTES + ip_header
+}
+
+/// Chequer's own traffic in one run, shared by every socket the run opens
+#[derive(Debug, Default)]
+pub struct OwnCounters {
+    rx_bytes: AtomicU64,
+    tx_bytes: AtomicU64,
+}
+
+impl OwnCounters {
+    /// Count a datagram payload sent to `peer`
+    pub fn count_udp_sent(&self, len: usize, peer: SocketAddr) {
+        self.tx_bytes.fetch_add(len as u64 + UDP_HEADER_BYTES + frame_overhead(peer), Ordering::Relaxed);
+    }
+
+    /// Count a datagram payload received from `peer`
+    pub fn count_udp_received(&self, len: usize, peer: SocketAddr) {
+        self.rx_bytes.fetch_add(len as u64 + UDP_HEADER_BYTES + frame_overhead(peer), Ordering::Relaxed);
+    }
+
+    /// Count TCP segments without payload exchanged with `peer`
+    pub fn count_tcp_segments(&self, sent: u64, received: u64, peer: SocketAddr) {
+        let segment = TCP_HEADER_BYTES + frame_overhead(peer);
+        self.tx_bytes.fetch_add(sent * segment, Ordering::Relaxed);
+        self.rx_bytes.fetch_add(received * segment, Ordering::Relaxed);
+    }
+
+    /// Bytes counted so far, headers included
+    pub fn totals(&self) -> DevCounters {
+        DevCounters {
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Host side: the counters of each connected client's run, by client address
+///
+/// Shared sockets such as the UDP echo serve every client, so each datagram
+/// is counted for the run of the client it came from, if one is connected.
+#[derive(Debug, Clone, Default)]
+pub struct PeerCounters(Arc<Mutex<HashMap<IpAddr, Arc<OwnCounters>>>>);
+
+impl PeerCounters {
+    /// Count traffic with `peer` in `counters` until the returned registration is dropped
+    pub fn register(&self, peer: IpAddr, counters: Arc<OwnCounters>) -> PeerRegistration {
+        let peer = peer.to_canonical();
+        self.0.lock().unwrap().insert(peer, Arc::clone(&counters));
+        PeerRegistration { peers: self.clone(), peer, counters }
+    }
+
+    /// Counters of the run `peer` belongs to, if it is connected
+    pub fn get(&self, peer: IpAddr) -> Option<Arc<OwnCounters>> {
+        self.0.lock().unwrap().get(&peer.to_canonical()).cloned()
+    }
+}
+
+/// Keeps a client's counters registered while it is connected
+pub struct PeerRegistration {
+    peers: PeerCounters,
+    peer: IpAddr,
+    counters: Arc<OwnCounters>,
+}
+
+impl Drop for PeerRegistration {
+    fn drop(&mut self) {
+        let mut peers = self.peers.0.lock().unwrap();
+        // A later client from the same address took the entry over; leave it
+        if peers.get(&self.peer).is_some_and(|current| Arc::ptr_eq(current, &self.counters)) {
+            peers.remove(&self.peer);
+        }
+    }
+}
+
+/// Received and transmitted bytes of one interface
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DevCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Byte counters per interface from /proc/net/dev, loopback left out
+pub fn parse_proc_net_dev(contents: &str) -> Vec<(String, DevCounters)> {
+    contents
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, fields) = line.split_once(':')?;
+            let name = name.trim();
+            // Loopback traffic never reaches the network
+            if name == "lo" {
+                return None;
+            }
+            let fields: Vec<u64> = fields.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+            let counters = DevCounters { rx_bytes: *fields.first()?, tx_bytes: *fields.get(8)? };
+            Some((name.to_string(), counters))
+        })
+        .collect()
+}
+
+/// Interface and own counters at one checkpoint
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub at: Instant,
+    pub interfaces: Vec<(String, DevCounters)>,
+    /// This process's traffic so far, headers included
+    pub own: DevCounters,
+}
+
+/// Traffic between two snapshots
+pub fn phase_between(name: &str, before: &Snapshot, after: &Snapshot) -> TrafficPhase {
+    let interfaces = after.interfaces.iter()
+        .filter_map(|(iface, end)| {
+            // Interfaces that appeared in between have no starting point
+            let (_, start) = before.interfaces.iter().find(|(other, _)| other == iface)?;
+            Some(InterfaceTraffic {
+                name: iface.clone(),
+                rx_bytes: end.rx_bytes.saturating_sub(start.rx_bytes),
+                tx_bytes: end.tx_bytes.saturating_sub(start.tx_bytes),
+            })
+        })
+        .collect();
+
+    TrafficPhase {
+        name: name.to_string(),
+        duration_ms: after.at.duration_since(before.at).as_secs_f64() * 1000.0,
+        interfaces,
+        own_rx_bytes: after.own.rx_bytes.saturating_sub(before.own.rx_bytes),
+        own_tx_bytes: after.own.tx_bytes.saturating_sub(before.own.tx_bytes),
+    }
+}
+
+/// Records the traffic of each phase of a run, one checkpoint at the end of each
+pub struct TrafficMonitor {
+    root: PathBuf,
+    counters: Arc<OwnCounters>,
+    connection: Option<(RawFd, SocketAddr)>,
+    last: Snapshot,
+    phases: Vec<TrafficPhase>,
+}
+
+impl TrafficMonitor {
+    /// Start accounting from now, with the run's own traffic in `counters`
+    pub fn start(counters: Arc<OwnCounters>) -> Self {
+        Self::start_at(PathBuf::from("/"), counters)
+    }
+
+    fn start_at(root: PathBuf, counters: Arc<OwnCounters>) -> Self {
+        let last = snapshot(&root, &counters, None);
+        Self { root, counters, connection: None, last, phases: Vec::new() }
+    }
+
+    /// Count the traffic of the control connection to `peer` as our own, from its first segment
+    pub fn set_connection(&mut self, fd: RawFd, peer: SocketAddr) {
+        self.connection = Some((fd, peer));
+    }
+
+    /// End the current phase as `name` and start the next one
+    pub fn checkpoint(&mut self, name: &str) {
+        let now = snapshot(&self.root, &self.counters, self.connection);
+        self.phases.push(phase_between(name, &self.last, &now));
+        self.last = now;
+    }
+
+    /// The phases recorded so far
+    pub fn finish(self) -> TrafficResults {
+        TrafficResults { phases: self.phases }
+    }
+}
+
+fn snapshot(root: &Path, counters: &OwnCounters, connection: Option<(RawFd, SocketAddr)>) -> Snapshot {
+    let interfaces = fs::read_to_string(root.join("proc/net/dev"))
+        .map(|contents| parse_proc_net_dev(&contents))
+        .unwrap_or_default();
+
+    let tcp = connection.and_then(|(fd, peer)| {
+        let segment = TCP_HEADER_BYTES + frame_overhead(peer);
+        tcpinfo::read_tcp_info(fd).map(|info| DevCounters {
+            rx_bytes: info.bytes_received + info.segs_in as u64 * segment,
+            tx_bytes: info.bytes_sent + info.segs_out as u64 * segment,
+        })
+    }).unwrap_or_default();
+    let udp = counters.totals();
+    let own = DevCounters {
+        rx_bytes: tcp.rx_bytes + udp.rx_bytes,
+        tx_bytes: tcp.tx_bytes + udp.tx_bytes,
+    };
+
+    Snapshot { at: Instant::now(), interfaces, own }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/traffic").join(name)
+    }
+
+    fn fixture_snapshot(name: &str, at: Instant, own: DevCounters) -> Snapshot {
+        let contents = fs::read_to_string(fixture(name)).unwrap();
+        Snapshot { at, interfaces: parse_proc_net_dev(&contents), own }
+    }
+
+    #[test]
+    fn test_parse_proc_net_dev() {
+        let interfaces = parse_proc_net_dev(&fs::read_to_string(fixture("net-dev-before.txt")).unwrap());
+        let names: Vec<&str> = interfaces.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["wlan0", "enp4s0"]);
+        assert_eq!(interfaces[0].1, DevCounters { rx_bytes: 1_843_226_331, tx_bytes: 97_112_540 });
+    }
+
+    #[test]
+    fn test_phase_between_fixture_snapshots() {
+        let start = Instant::now();
+        let before = fixture_snapshot("net-dev-before.txt", start, DevCounters { rx_bytes: 1_000, tx_bytes: 2_000 });
+        let after = fixture_snapshot("net-dev-after.txt", start + Duration::from_secs(2), DevCounters { rx_bytes: 301_000, tx_bytes: 302_000 });
+        let phase = phase_between("loss", &before, &after);
+
+        assert_eq!(phase.name, "loss");
+        assert_eq!(phase.duration_ms, 2000.0);
+        assert_eq!((phase.own_rx_bytes, phase.own_tx_bytes), (300_000, 300_000));
+
+        // A 12.5 MB download alongside our 300 kB in each direction
+        let wlan0 = &phase.interfaces[0];
+        assert_eq!((wlan0.rx_bytes, wlan0.tx_bytes), (12_800_000, 420_000));
+        assert_eq!(phase.foreign(wlan0, true), (12_500_000, 120_000));
+        // The cable is unplugged; nothing moved
+        assert_eq!(phase.foreign(&phase.interfaces[1], false), (0, 0));
+    }
+
+    #[test]
+    fn test_monitor_counts_own_datagrams() {
+        let counters = Arc::new(OwnCounters::default());
+        let mut monitor = TrafficMonitor::start_at(fixture("missing"), Arc::clone(&counters));
+        counters.count_udp_sent(100, "192.0.2.1:27031".parse().unwrap());
+        counters.count_udp_received(100, "[2001:db8::1]:27031".parse().unwrap());
+        counters.count_tcp_segments(1, 0, "[::ffff:192.0.2.1]:27036".parse().unwrap());
+        monitor.checkpoint("latency");
+        let results = monitor.finish();
+
+        let phase = &results.phases[0];
+        assert!(phase.interfaces.is_empty());
+        // Ethernet, IPv4 and UDP, then a header-only segment over IPv4 despite the mapped address
+        assert_eq!(phase.own_tx_bytes, 100 + 14 + 20 + 8 + 14 + 20 + 32);
+        // Ethernet, IPv6 and UDP
+        assert_eq!(phase.own_rx_bytes, 100 + 14 + 40 + 8);
+    }
+
+    #[test]
+    fn test_peer_counters_keep_clients_apart() {
+        let peers = PeerCounters::default();
+        let first: IpAddr = "192.0.2.1".parse().unwrap();
+        let second: IpAddr = "192.0.2.2".parse().unwrap();
+        let first_counters = Arc::new(OwnCounters::default());
+        let registration = peers.register(first, Arc::clone(&first_counters));
+        let _second = peers.register(second, Arc::new(OwnCounters::default()));
+
+        let counters = peers.get("::ffff:192.0.2.1".parse().unwrap()).unwrap();
+        assert!(Arc::ptr_eq(&counters, &first_counters));
+        assert!(!Arc::ptr_eq(&peers.get(second).unwrap(), &first_counters));
+
+        // A reconnect from the same address outlives the old connection's registration
+        let reconnected = peers.register(first, Arc::new(OwnCounters::default()));
+        drop(registration);
+        assert!(peers.get(first).is_some());
+        drop(reconnected);
+        assert!(peers.get(first).is_none());
+        assert!(peers.get(second).is_some());
+    }
+}
@@ -0,0 +1,340 @@
+/// Hardware video decode and encode capabilities through VA-API, or `vainfo` output as a fallback
+use chequer_common::{VideoProfile, VideoResults};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::debug;
+
+/// VAProfile values from va.h of the H.264, HEVC and AV1 profiles, with their names and codec
+const PROFILES: [(i32, &str, &str); 12] = [
+    (13, "VAProfileH264ConstrainedBaseline", "h264"),
+    (6, "VAProfileH264Main", "h264"),
+    (7, "VAProfileH264High", "h264"),
+    (36, "VAProfileH264High10", "h264"),
+    (17, "VAProfileHEVCMain", "hevc"),
+    (18, "VAProfileHEVCMain10", "hevc"),
+    (23, "VAProfileHEVCMain12", "hevc"),
+    (24, "VAProfileHEVCMain422_10", "hevc"),
+    (26, "VAProfileHEVCMain444", "hevc"),
+    (27, "VAProfileHEVCMain444_10", "hevc"),
+    (32, "VAProfileAV1Profile0", "av1"),
+    (33, "VAProfileAV1Profile1", "av1"),
+];
+
+/// Detect the hardware decoders and encoders of this machine
+///
+/// Loads the VA-API driver and may run `vainfo`, so call it from a blocking context.
+pub fn probe() -> Option<VideoResults> {
+    let (driver, profiles) = render_nodes(Path::new("/dev/dri")).iter()
+        .find_map(|node| vaapi::query(node))
+        .or_else(|| run_vainfo().map(|output| parse_vainfo(&output)))?;
+    Some(video_results(driver, profiles))
+}
+
+/// Assemble results from a driver name and its profiles
+pub fn video_results(driver: Option<String>, profiles: Vec<VideoProfile>) -> VideoResults {
+    let mut supported_codecs: Vec<String> = Vec::new();
+    for profile in profiles.iter().filter(|p| !p.encode) {
+        if !supported_codecs.contains(&profile.codec) {
+            supported_codecs.push(profile.codec.clone());
+        }
+    }
+    VideoResults { supported_codecs, decode_fps: None, driver, profiles }
+}
+
+/// DRM render nodes, e.g. /dev/dri/renderD128, in order
+fn render_nodes(dri: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dri) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("renderD"))
+        .map(|entry| entry.path())
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+fn run_vainfo() -> Option<String> {
+    // The DRM display works without a desktop session; `--all` adds the picture size limits
+    [&["--display", "drm", "--all"][..], &[]].iter().find_map(|args| {
+        let output = Command::new("vainfo").args(*args).output()
+            .map_err(|e| debug!("Cannot run vainfo: {}", e))
+            .ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    })
+}
+
+/// Add a profile, keeping one entry per profile and direction with the largest picture size
+fn add_profile(profiles: &mut Vec<VideoProfile>, profile: VideoProfile) {
+    match profiles.iter_mut().find(|p| p.profile == profile.profile && p.encode == profile.encode) {
+        Some(existing) => {
+            if profile.max_width.unwrap_or(0) > existing.max_width.unwrap_or(0) {
+                *existing = profile;
+            }
+        }
+        None => profiles.push(profile),
+    }
+}
+
+/// Codec of a profile and whether an entrypoint encodes; `None` for anything else
+fn classify(profile: &str, entrypoint: &str) -> Option<(&'static str, bool)> {
+    let (_, _, codec) = PROFILES.iter().find(|(_, name, _)| *name == profile)?;
+    let encode = match entrypoint {
+        "VAEntrypointVLD" => false,
+        "VAEntrypointEncSlice" | "VAEntrypointEncSliceLP" => true,
+        _ => return None,
+    };
+    Some((codec, encode))
+}
+
+/// Driver and profiles from `vainfo` or `vainfo --all` output
+pub fn parse_vainfo(output: &str) -> (Option<String>, Vec<VideoProfile>) {
+    let mut driver = None;
+    let mut profiles: Vec<VideoProfile> = Vec::new();
+    // Profile being described by the attribute lines of `--all`
+    let mut current: Option<VideoProfile> = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some((_, version)) = line.split_once("Driver version:") {
+            driver = Some(version.trim().to_string());
+            continue;
+        }
+
+        // "VAProfileH264Main/VAEntrypointVLD" starts a block in `--all` output
+        if let Some((profile, entrypoint)) = line.split_once('/').filter(|(p, _)| p.starts_with("VAProfile")) {
+            profiles.extend(current.take());
+            current = classify(profile, entrypoint).map(|(codec, encode)| VideoProfile {
+                codec: codec.to_string(),
+                profile: profile.to_string(),
+                encode,
+                max_width: None,
+                max_height: None,
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':').map(|(k, v)| (k.trim(), v.trim())) else {
+            continue;
+        };
+        if key.starts_with("VAProfile") {
+            // "VAProfileH264Main : VAEntrypointVLD" in the plain listing
+            if let Some((codec, encode)) = classify(key, value) {
+                profiles.push(VideoProfile {
+                    codec: codec.to_string(),
+                    profile: key.to_string(),
+                    encode,
+                    max_width: None,
+                    max_height: None,
+                });
+            }
+        } else if let Some(profile) = current.as_mut() {
+            match key {
+                "VAConfigAttribMaxPictureWidth" => profile.max_width = value.parse().ok(),
+                "VAConfigAttribMaxPictureHeight" => profile.max_height = value.parse().ok(),
+                _ => {}
+            }
+        }
+    }
+    profiles.extend(current);
+
+    let mut merged = Vec::new();
+    for profile in profiles {
+        add_profile(&mut merged, profile);
+    }
+    (driver, merged)
+}
+
+/// Minimal libva bindings, loaded at runtime so that machines without it still run
+mod vaapi {
+    use super::{add_profile, PROFILES};
+    use chequer_common::VideoProfile;
+    use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    type VADisplay = *mut c_void;
+    type MessageCallback = Option<unsafe extern "C" fn(*mut c_void, *const c_char)>;
+
+    #[repr(C)]
+    struct VAConfigAttrib {
+        kind: c_int,
+        value: c_uint,
+    }
+
+    const VA_STATUS_SUCCESS: c_int = 0;
+    const VA_ATTRIB_NOT_SUPPORTED: c_uint = 0x8000_0000;
+    const VA_ENTRYPOINT_VLD: c_int = 1;
+    const VA_ENTRYPOINT_ENC_SLICE: c_int = 6;
+    const VA_ENTRYPOINT_ENC_SLICE_LP: c_int = 8;
+    const VA_CONFIG_ATTRIB_MAX_PICTURE_WIDTH: c_int = 18;
+    const VA_CONFIG_ATTRIB_MAX_PICTURE_HEIGHT: c_int = 19;
+
+    /// A dlopen handle, closed on drop
+    struct Library(*mut c_void);
+
+    impl Library {
+        fn open(name: &CStr) -> Option<Self> {
+            let handle = unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+            (!handle.is_null()).then_some(Self(handle))
+        }
+
+        /// Look up a function; `T` must be the `extern "C" fn` type matching its C signature
+        unsafe fn symbol<T: Copy>(&self, name: &CStr) -> Option<T> {
+            let symbol = libc::dlsym(self.0, name.as_ptr());
+            (!symbol.is_null()).then(|| std::mem::transmute_copy(&symbol))
+        }
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            unsafe { libc::dlclose(self.0) };
+        }
+    }
+
+    /// Driver name and H.264, HEVC and AV1 profiles of the driver behind `render_node`
+    pub fn query(render_node: &Path) -> Option<(Option<String>, Vec<VideoProfile>)> {
+        let va = Library::open(c"libva.so.2")?;
+        let va_drm = Library::open(c"libva-drm.so.2")?;
+        let node = File::options().read(true).write(true).open(render_node).ok()?;
+
+        unsafe {
+            let get_display: unsafe extern "C" fn(c_int) -> VADisplay = va_drm.symbol(c"vaGetDisplayDRM")?;
+            let initialize: unsafe extern "C" fn(VADisplay, *mut c_int, *mut c_int) -> c_int = va.symbol(c"vaInitialize")?;
+            let terminate: unsafe extern "C" fn(VADisplay) -> c_int = va.symbol(c"vaTerminate")?;
+            let vendor_string: unsafe extern "C" fn(VADisplay) -> *const c_char = va.symbol(c"vaQueryVendorString")?;
+            let max_entrypoints: unsafe extern "C" fn(VADisplay) -> c_int = va.symbol(c"vaMaxNumEntrypoints")?;
+            let query_entrypoints: unsafe extern "C" fn(VADisplay, c_int, *mut c_int, *mut c_int) -> c_int =
+                va.symbol(c"vaQueryConfigEntrypoints")?;
+            let get_attributes: unsafe extern "C" fn(VADisplay, c_int, c_int, *mut VAConfigAttrib, c_int) -> c_int =
+                va.symbol(c"vaGetConfigAttributes")?;
+
+            let display = get_display(node.as_raw_fd());
+            if display.is_null() {
+                return None;
+            }
+            // libva logs to stderr unless its callbacks are cleared
+            for name in [c"vaSetInfoCallback", c"vaSetErrorCallback"] {
+                let set_callback: Option<unsafe extern "C" fn(VADisplay, MessageCallback, *mut c_void) -> MessageCallback> = va.symbol(name);
+                if let Some(set_callback) = set_callback {
+                    set_callback(display, None, std::ptr::null_mut());
+                }
+            }
+
+            let (mut major, mut minor) = (0, 0);
+            if initialize(display, &mut major, &mut minor) != VA_STATUS_SUCCESS {
+                terminate(display);
+                return None;
+            }
+            let vendor = vendor_string(display);
+            let driver = (!vendor.is_null()).then(|| CStr::from_ptr(vendor).to_string_lossy().into_owned());
+
+            let mut profiles = Vec::new();
+            let mut entrypoints = vec![0; max_entrypoints(display).max(0) as usize];
+            for &(value, name, codec) in &PROFILES {
+                let mut count = 0;
+                // Fails with VA_STATUS_ERROR_UNSUPPORTED_PROFILE for profiles the driver lacks
+                if query_entrypoints(display, value, entrypoints.as_mut_ptr(), &mut count) != VA_STATUS_SUCCESS {
+                    continue;
+                }
+                for &entrypoint in &entrypoints[..count.max(0) as usize] {
+                    let encode = match entrypoint {
+                        VA_ENTRYPOINT_VLD => false,
+                        VA_ENTRYPOINT_ENC_SLICE | VA_ENTRYPOINT_ENC_SLICE_LP => true,
+                        _ => continue,
+                    };
+                    let mut attributes = [
+                        VAConfigAttrib { kind: VA_CONFIG_ATTRIB_MAX_PICTURE_WIDTH, value: 0 },
+                        VAConfigAttrib { kind: VA_CONFIG_ATTRIB_MAX_PICTURE_HEIGHT, value: 0 },
+                    ];
+                    let limits = get_attributes(display, value, entrypoint, attributes.as_mut_ptr(), 2) == VA_STATUS_SUCCESS;
+                    let limit = |attribute: &VAConfigAttrib| {
+                        (limits && attribute.value != VA_ATTRIB_NOT_SUPPORTED && attribute.value > 0).then_some(attribute.value)
+                    };
+                    add_profile(&mut profiles, VideoProfile {
+                        codec: codec.to_string(),
+                        profile: name.to_string(),
+                        encode,
+                        max_width: limit(&attributes[0]),
+                        max_height: limit(&attributes[1]),
+                    });
+                }
+            }
+
+            terminate(display);
+            Some((driver, profiles))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> (Option<String>, Vec<VideoProfile>) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/video").join(name);
+        parse_vainfo(&fs::read_to_string(path).unwrap())
+    }
+
+    fn find<'a>(profiles: &'a [VideoProfile], name: &str, encode: bool) -> Option<&'a VideoProfile> {
+        profiles.iter().find(|p| p.profile == name && p.encode == encode)
+    }
+
+    #[test]
+    fn test_steam_deck_decodes_av1_and_encodes_hevc() {
+        let (driver, profiles) = fixture("vainfo-steamdeck.txt");
+        assert!(driver.is_some_and(|d| d.starts_with("Mesa Gallium driver 23.1.3 for AMD Custom GPU 0405")));
+
+        let results = video_results(None, profiles);
+        assert_eq!(results.supported_codecs, vec!["h264", "hevc", "av1"]);
+        assert_eq!(results.encode_codecs(), vec!["h264", "hevc"]);
+
+        let av1 = find(&results.profiles, "VAProfileAV1Profile0", false).unwrap();
+        assert_eq!((av1.max_width, av1.max_height), (Some(8192), Some(4352)));
+        let hevc_encode = find(&results.profiles, "VAProfileHEVCMain", true).unwrap();
+        assert_eq!((hevc_encode.max_width, hevc_encode.max_height), (Some(4096), Some(2304)));
+        // MPEG-2, VP9 and JPEG are not streaming codecs
+        assert!(results.profiles.iter().all(|p| ["h264", "hevc", "av1"].contains(&p.codec.as_str())));
+    }
+
+    #[test]
+    fn test_plain_listing_has_no_sizes() {
+        let (driver, profiles) = fixture("vainfo-intel-iris-xe.txt");
+        assert_eq!(driver.as_deref(), Some("Intel iHD driver for Intel(R) Gen Graphics - 24.1.0 ()"));
+
+        let results = video_results(driver, profiles);
+        assert_eq!(results.supported_codecs, vec!["h264", "hevc", "av1"]);
+        // Low-power encoders count as encoders
+        assert_eq!(results.encode_codecs(), vec!["h264", "hevc"]);
+        assert!(results.profiles.iter().all(|p| p.max_width.is_none()));
+        assert!(find(&results.profiles, "VAProfileHEVCMain444_10", true).is_some());
+        // Screen content coding profiles are left out
+        assert!(results.profiles.iter().all(|p| !p.profile.contains("Scc")));
+    }
+
+    #[test]
+    fn test_nvidia_decodes_only() {
+        let (_, profiles) = fixture("vainfo-nvidia-rtx3070.txt");
+        let results = video_results(None, profiles);
+        assert_eq!(results.supported_codecs, vec!["h264", "hevc", "av1"]);
+        assert!(results.encode_codecs().is_empty());
+    }
+
+    #[test]
+    fn test_polaris_has_no_av1() {
+        let (_, profiles) = fixture("vainfo-radeon-rx580.txt");
+        let results = video_results(None, profiles);
+        assert_eq!(results.supported_codecs, vec!["h264", "hevc"]);
+        assert_eq!(results.encode_codecs(), vec!["h264", "hevc"]);
+        let hevc = find(&results.profiles, "VAProfileHEVCMain10", false).unwrap();
+        assert_eq!((hevc.max_width, hevc.max_height), (Some(4096), Some(2304)));
+    }
+
+    #[test]
+    fn test_probe_without_render_nodes() {
+        assert!(render_nodes(Path::new("/nonexistent/dri")).is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+/// Timer wake-up latency test of the local OS, in the style of cyclictest
+use chequer_common::SystemResults;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Sleep period between wake-ups
+pub const WAKEUP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Sleep until absolute deadlines `interval` apart for `duration` and record how late each wake-up was
+///
+/// Blocks the calling thread. It runs at normal priority on purpose: the
+/// Steam client does too, so it sees the same delays.
+pub fn run_wakeup_test(duration: Duration, interval: Duration) -> SystemResults {
+    info!("Measuring timer wake-up latency for {:.1}s...", duration.as_secs_f64());
+
+    let count = (duration.as_nanos() / interval.as_nanos().max(1)) as usize;
+    let mut latencies_us = Vec::with_capacity(count);
+    let start = Instant::now();
+    for i in 1..=count {
+        let deadline = start + interval * i as u32;
+        sleep_until(deadline);
+        latencies_us.push(Instant::now().saturating_duration_since(deadline).as_secs_f64() * 1e6);
+    }
+
+    let results = SystemResults::from_samples(interval.as_secs_f64() * 1e6, &latencies_us);
+    info!("Wake-up latency - Avg: {:.0}us, P99: {:.0}us, Max: {:.0}us",
+          results.avg_us, results.p99_us, results.max_us);
+    results
+}
+
+/// Sleep on an absolute CLOCK_MONOTONIC deadline, which `Instant` also uses on Linux
+#[cfg(target_os = "linux")]
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    let mut target: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut target) };
+
+    // Turn the deadline into a timespec by offsetting the current clock reading
+    let remaining = deadline.saturating_duration_since(now);
+    let nanos = target.tv_nsec as u64 + remaining.subsec_nanos() as u64;
+    target.tv_sec += remaining.as_secs() as libc::time_t + (nanos / 1_000_000_000) as libc::time_t;
+    target.tv_nsec = (nanos % 1_000_000_000) as _;
+
+    // EINTR restarts with the same absolute deadline
+    while unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &target, std::ptr::null_mut()) } == libc::EINTR {}
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sleep_until(deadline: Instant) {
+    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wakeups_are_never_early() {
+        let results = run_wakeup_test(Duration::from_millis(100), WAKEUP_INTERVAL);
+        assert_eq!(results.samples, 100);
+        assert!(results.min_us >= 0.0);
+        assert!(results.max_us >= results.p99_us && results.p99_us >= results.p50_us);
+        assert_eq!(results.histogram.iter().map(|b| b.count).sum::<usize>(), 100);
+    }
+}
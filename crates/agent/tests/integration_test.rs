@@ -1,11 +1,42 @@
-use chequer_agent::{Host, Client};
-use chequer_common::{TestConfig, Message};
+use chequer_agent::{discovery, Host, Client};
+use chequer_agent::traffic::{OwnCounters, PeerCounters};
+use chequer_common::{Message, PortSpec, PortState, ProbeSchedule, TestConfig, TestResults, Transport};
 use tokio::time::{sleep, Duration};
 
+/// Start a host on `port` and run one client against it
+async fn run_against_host(port: u16, config: TestConfig) -> TestResults {
+    let addr = format!("127.0.0.1:{}", port);
+    let host = Host::new(addr.clone()).with_discovery_port(None).with_any_ports(true);
+    
+    tokio::spawn(async move {
+        host.run().await.expect("Host failed");
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    Client::new(addr).with_config(config).run().await.expect("Client failed")
+}
+
+/// A short latency test with every other test off, for tests to enable their own
+fn latency_only() -> TestConfig {
+    TestConfig {
+        latency_samples: 10,
+        latency_interval_ms: 5,
+        latency_warmup_samples: 0,
+        packet_size_sweep: false,
+        mtu_discovery: false,
+        loss_test: false,
+        qos_test: false,
+        port_check: false,
+        traffic_check: false,
+        ..TestConfig::default()
+    }
+}
+
 #[tokio::test]
 async fn test_host_client_latency() {
     // Start host in background
-    let host = Host::new("127.0.0.1:17777".to_string());
+    let host = Host::new("127.0.0.1:17777".to_string()).with_discovery_port(None);
     
     tokio::spawn(async move {
         host.run().await.expect("Host failed");
@@ -20,6 +51,14 @@ async fn test_host_client_latency() {
             latency_samples: 10,
             latency_interval_ms: 5,
             bandwidth_duration_secs: 0,
+            latency_warmup_samples: 0,
+            packet_size_sweep: false,
+            mtu_discovery: false,
+            loss_test: false,
+            qos_test: false,
+            port_check: false,
+            traffic_check: false,
+            ..TestConfig::default()
         });
     
     let results = client.run().await.expect("Client failed");
@@ -35,16 +74,367 @@ async fn test_host_client_latency() {
     assert!(latency.jitter_ms >= 0.0);
 }
 
+#[tokio::test]
+async fn test_tcp_info_on_both_ends() {
+    let results = run_against_host(17810, latency_only()).await;
+    
+    let tcp = results.latency.expect("no latency results").tcp_info.expect("no TCP_INFO");
+    assert!(tcp.after.rtt_ms > 0.0);
+    assert_eq!(tcp.retransmits(), 0);
+    
+    // The host reports its own view of the control connection
+    let host = results.host.expect("no host results");
+    assert_eq!(host.info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(host.tcp_info.expect("no host TCP_INFO").retransmits(), 0);
+}
+
+#[tokio::test]
+async fn test_host_clock_offset() {
+    let latency = run_against_host(17811, latency_only()).await.latency.expect("no latency results");
+    
+    // Both ends read the same clock, so the estimated offset is within the round trip
+    let offset = latency.host_clock_offset_ms.expect("no host clock offset");
+    assert!(offset.abs() < 10.0, "{}", offset);
+}
+
+#[tokio::test]
+async fn test_packet_size_sweep() {
+    let results = run_against_host(17812, TestConfig { packet_size_sweep: true, ..latency_only() }).await;
+    
+    // The host's UDP echo answers every size
+    let link = results.link_capacity.expect("no link capacity results");
+    assert_eq!(link.points.len(), chequer_agent::sweep::SWEEP_SIZES.len());
+    assert!(link.points.iter().all(|p| p.min_rtt_ms.is_some()));
+}
+
+#[tokio::test]
+async fn test_path_mtu_discovery() {
+    let results = run_against_host(17813, TestConfig { mtu_discovery: true, ..latency_only() }).await;
+    
+    // Loopback carries jumbo frames, so the search runs up to its ceiling
+    let mtu = results.mtu.expect("no path MTU results");
+    assert_eq!(mtu.max_payload_bytes, mtu.search_ceiling_bytes);
+    assert_eq!(mtu.path_mtu(), chequer_agent::mtu::JUMBO_MTU);
+}
+
+#[tokio::test]
+async fn test_loss_stream() {
+    let results = run_against_host(17814, TestConfig { loss_test: true, ..latency_only() }).await;
+    
+    let loss = results.loss.expect("no loss results");
+    assert_eq!(loss.sent, TestConfig::default().loss_probes);
+    assert!(loss.loss_percent() < 5.0);
+}
+
+#[tokio::test]
+async fn test_dscp_markings() {
+    let results = run_against_host(17815, TestConfig { qos_test: true, ..latency_only() }).await;
+    
+    // Loopback keeps every marking
+    let qos = results.qos.expect("no DSCP results");
+    assert_eq!(qos.classes.len(), chequer_agent::qos::TRAFFIC_CLASSES.len());
+    assert!(qos.classes.iter().all(|c| c.preserved() == Some(true)), "{:?}", qos.classes);
+}
+
+#[tokio::test]
+async fn test_scheduling_latency() {
+    let config = TestConfig { scheduling_test: true, scheduling_test_secs: 1, ..latency_only() };
+    let system = run_against_host(17816, config).await.system.expect("no scheduling results");
+    assert_eq!(system.samples, 1000);
+}
+
+#[tokio::test]
+async fn test_network_interfaces() {
+    let results = run_against_host(17817, latency_only()).await;
+    
+    // Both ends of a loopback test run over lo
+    let host = results.host.expect("no host results");
+    for network in [results.client_network.as_ref(), Some(&host.network)] {
+        let network = network.expect("no network environment");
+        assert_eq!(network.active().map(|i| i.kind), Some(chequer_common::InterfaceKind::Loopback));
+    }
+}
+
+#[tokio::test]
+async fn test_system_environment() {
+    let results = run_against_host(17818, latency_only()).await;
+    
+    // Both ends describe the machine they ran on, here the same one
+    let host = results.host.expect("no host results");
+    let client_env = results.client_environment.expect("no client environment");
+    assert!(client_env.kernel.is_some());
+    assert_eq!(client_env.kernel, host.environment.kernel);
+}
+
+#[tokio::test]
+async fn test_pressure_sampling() {
+    // Long enough for a few samples at the pressure interval
+    let results = run_against_host(17819, TestConfig { latency_samples: 50, ..latency_only() }).await;
+    
+    // Both peers sampled pressure on a UTC timeline covering the latency test
+    let latency_start = results.latency.expect("no latency results").started_at_ms.expect("no latency start time");
+    let host = results.host.expect("no host results");
+    for pressure in [results.client_pressure.as_ref(), host.pressure.as_ref()] {
+        let samples = &pressure.expect("no pressure samples").samples;
+        assert!(samples.first().is_some_and(|s| s.timestamp_ms < latency_start + 1000.0));
+        assert!(samples.last().is_some_and(|s| s.timestamp_ms > latency_start));
+    }
+}
+
+#[tokio::test]
+async fn test_traffic_accounting() {
+    let results = run_against_host(17820, TestConfig { loss_test: true, traffic_check: true, ..latency_only() }).await;
+    let loss_bytes = (TestConfig::default().loss_probes * chequer_agent::loss::LOSS_PROBE_SIZE) as u64;
+    
+    // The client counts each test as a phase, with its own datagrams accounted for
+    let traffic = results.client_traffic.expect("no client traffic");
+    let phase = |name: &str| traffic.phases.iter().find(|p| p.name == name);
+    assert!(phase("before").is_some() && phase("after").is_some());
+    assert!(phase("loss").expect("no loss phase").own_tx_bytes >= loss_bytes);
+    
+    // The host counts the client's probes on its shared echo socket too
+    let host_traffic = results.host.expect("no host results").traffic.expect("no host traffic");
+    assert_eq!(host_traffic.phases.len(), 1);
+    assert!(host_traffic.phases[0].own_rx_bytes >= loss_bytes);
+}
+
+#[tokio::test]
+async fn test_power_sampling() {
+    // The quiet window before the tests gives the sampler time for its first reading
+    let results = run_against_host(17821, TestConfig { traffic_check: true, ..latency_only() }).await;
+    
+    // The client's power state was sampled from the start of the run
+    let latency_start = results.latency.expect("no latency results").started_at_ms.expect("no latency start time");
+    let power = results.client_power.expect("no power samples");
+    assert!(power.samples.first().is_some_and(|s| s.timestamp_ms < latency_start));
+}
+
+#[tokio::test]
+async fn test_port_check() {
+    // Stand-ins for ports a running Steam instance already holds
+    let _steam_tcp = std::net::TcpListener::bind("0.0.0.0:17782").unwrap();
+    let _steam_udp = std::net::UdpSocket::bind("0.0.0.0:17782").unwrap();
+    
+    let config = TestConfig {
+        port_check: true,
+        ports: PortSpec::parse_list("udp:17780-17782,tcp:17780-17782").unwrap(),
+        ..latency_only()
+    };
+    let results = run_against_host(17822, config).await;
+    
+    // Ports the host opened are reachable; in-use TCP is checked passively, in-use UDP only for refusals
+    let ports = results.ports.expect("no port check results");
+    assert_eq!(ports.ports.len(), 6);
+    for result in &ports.ports {
+        let expected = match (result.port.transport, result.port.port) {
+            (Transport::Udp, 17782) => (PortState::InUse, None),
+            (Transport::Tcp, 17782) => (PortState::InUse, Some(true)),
+            _ => (PortState::Listening, Some(true)),
+        };
+        assert_eq!((result.host_state.clone(), result.reachable), expected, "{}", result.port);
+    }
+}
+
+#[tokio::test]
+async fn test_warmup_samples_are_recorded_but_excluded() {
+    let host = Host::new("127.0.0.1:17778".to_string());
+    
+    tokio::spawn(async move {
+        host.run().await.expect("Host failed");
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    let client = Client::new("127.0.0.1:17778".to_string())
+        .with_config(TestConfig {
+            latency_samples: 10,
+            latency_interval_ms: 1,
+            latency_warmup_samples: 3,
+            latency_outlier_threshold: Some(3.5),
+            loss_test: false,
+            port_check: false,
+            traffic_check: false,
+            ..TestConfig::default()
+        });
+    
+    let results = client.run().await.expect("Client failed");
+    let latency = results.latency.unwrap();
+    
+    assert_eq!(latency.samples.len(), 13);
+    assert_eq!(latency.send_offsets_ms.len(), 13);
+    assert_eq!(latency.warmup_samples, 3);
+    assert_eq!(latency.measured_samples().len(), 10);
+    assert_eq!(latency.filtered_samples().len(), 10 - latency.outlier_indices.len());
+    assert!(latency.outlier_indices.iter().all(|&i| i >= 3));
+}
+
+#[tokio::test]
+async fn test_pipelined_probe_schedules() {
+    let host = Host::new("127.0.0.1:17779".to_string());
+    
+    tokio::spawn(async move {
+        host.run().await.expect("Host failed");
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    let schedules = [
+        ProbeSchedule::FixedRate,
+        ProbeSchedule::Poisson,
+        ProbeSchedule::Burst { rate_hz: 1000, burst_size: 4 },
+    ];
+    for schedule in schedules {
+        let client = Client::new("127.0.0.1:17779".to_string())
+            .with_config(TestConfig {
+                latency_samples: 20,
+                latency_interval_ms: 5,
+                latency_warmup_samples: 0,
+                probe_schedule: schedule,
+                loss_test: false,
+                port_check: false,
+                traffic_check: false,
+                ..TestConfig::default()
+            });
+        
+        let latency = client.run().await.expect("Client failed").latency.unwrap();
+        
+        assert_eq!(latency.schedule, schedule);
+        assert_eq!(latency.samples.len(), 20);
+        assert!(latency.send_offsets_ms.windows(2).all(|w| w[1] >= w[0]));
+        assert!(latency.min_ms > 0.0);
+    }
+}
+
+#[tokio::test]
+async fn test_precise_mode() {
+    let host = Host::new("127.0.0.1:17785".to_string()).with_discovery_port(None);
+    
+    tokio::spawn(async move {
+        host.run().await.expect("Host failed");
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    for schedule in [ProbeSchedule::Sequential, ProbeSchedule::FixedRate] {
+        let client = Client::new("127.0.0.1:17785".to_string())
+            .with_config(TestConfig {
+                latency_samples: 20,
+                latency_interval_ms: 2,
+                latency_warmup_samples: 2,
+                probe_schedule: schedule,
+                packet_size_sweep: false,
+                mtu_discovery: false,
+                loss_test: false,
+                qos_test: false,
+                port_check: false,
+                precise: true,
+                traffic_check: false,
+                ..TestConfig::default()
+            });
+        
+        let latency = client.run().await.expect("Client failed").latency.unwrap();
+        
+        assert_eq!(latency.samples.len(), 22);
+        assert!(latency.send_offsets_ms.windows(2).all(|w| w[1] >= w[0]));
+        assert!(latency.min_ms > 0.0);
+        let precise = latency.precise.expect("no precise timing");
+        assert!(precise.pinned_cpu.is_some());
+        assert_eq!(precise.fifo_priority, None);
+    }
+}
+
 #[tokio::test]
 async fn test_message_serialization() {
     use chrono::Utc;
     
-    let ping = Message::Ping { timestamp: Utc::now() };
+    let ping = Message::Ping { timestamp: Utc::now(), seq: 7 };
     let serialized = serde_json::to_string(&ping).unwrap();
     let deserialized: Message = serde_json::from_str(&serialized).unwrap();
     
     match deserialized {
-        Message::Ping { .. } => (),
+        Message::Ping { seq, .. } => assert_eq!(seq, 7),
+        _ => panic!("Wrong message type"),
+    }
+}
+
+#[tokio::test]
+async fn test_lan_discovery() {
+    let host = Host::new("127.0.0.1:17783".to_string()).with_discovery_port(Some(17784));
+    
+    tokio::spawn(async move {
+        host.run().await.expect("Host failed");
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    // Unicast to loopback exercises the same probe and answer as broadcast
+    let target = "127.0.0.1:17784".parse().unwrap();
+    let counters = std::sync::Arc::new(OwnCounters::default());
+    let hosts = discovery::discover_at(&[target], Duration::from_millis(500), &counters).await.unwrap();
+    
+    assert_eq!(hosts.len(), 1);
+    // The probe and its answer count as the run's own traffic
+    let totals = counters.totals();
+    assert!(totals.tx_bytes > 0 && totals.rx_bytes > 0);
+    assert_eq!(hosts[0].address, "127.0.0.1:17783".parse().unwrap());
+    assert_eq!(hosts[0].info.version, env!("CARGO_PKG_VERSION"));
+    assert!(hosts[0].info.capabilities.contains(&"udp-echo".to_string()));
+    
+    // The advertised address accepts a client
+    let client = Client::new(hosts[0].address.to_string())
+        .with_config(TestConfig {
+            latency_samples: 3,
+            latency_warmup_samples: 0,
+            packet_size_sweep: false,
+            mtu_discovery: false,
+            loss_test: false,
+            qos_test: false,
+            port_check: false,
+            traffic_check: false,
+            ..TestConfig::default()
+        });
+    assert_eq!(client.run().await.unwrap().latency.unwrap().samples.len(), 3);
+}
+
+#[tokio::test]
+async fn test_lan_discovery_over_multicast() {
+    use chequer_common::HostInfo;
+    use socket2::SockRef;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    // Join the discovery group on loopback so the probe never leaves this machine
+    let responder = discovery::bind_responder(17786).unwrap();
+    if let Err(e) = SockRef::from(&responder).join_multicast_v4(&discovery::DISCOVERY_GROUP, &Ipv4Addr::LOCALHOST) {
+        eprintln!("skipping: cannot join {} on lo: {}", discovery::DISCOVERY_GROUP, e);
+        return;
+    }
+    let info = HostInfo {
+        name: "multicast-test".to_string(),
+        ip: None,
+        port: 17787,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        capabilities: Vec::new(),
+    };
+    tokio::spawn(discovery::run_responder(responder, info, PeerCounters::default()));
+
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let sock = SockRef::from(&client);
+    if let Err(e) = sock.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).and_then(|_| sock.set_multicast_loop_v4(true)) {
+        eprintln!("skipping: IP_MULTICAST_LOOP is not available on lo: {}", e);
+        return;
+    }
+
+    let request = serde_json::to_vec(&Message::DiscoveryRequest { version: "test".to_string() }).unwrap();
+    let group = SocketAddr::new(discovery::DISCOVERY_GROUP.into(), 17786);
+    client.send_to(&request, group).await.unwrap();
+
+    let mut buffer = vec![0u8; 2048];
+    let (len, _) = tokio::time::timeout(Duration::from_millis(500), client.recv_from(&mut buffer))
+        .await
+        .expect("no answer to the multicast probe")
+        .unwrap();
+    match serde_json::from_slice(&buffer[..len]).unwrap() {
+        Message::DiscoveryResponse { host } => assert_eq!((host.name.as_str(), host.port), ("multicast-test", 17787)),
         _ => panic!("Wrong message type"),
     }
 }
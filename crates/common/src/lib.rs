@@ -1,4 +1,5 @@
 pub mod protocol;
+pub mod stats;
 pub mod types;
 
 pub use protocol::*;
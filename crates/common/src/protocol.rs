@@ -1,41 +1,932 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use crate::stats;
+use crate::types::{PortFailure, PortSpec, PortState, ProbeSchedule, ReportConfig};
 
 /// Message types exchanged between client and host
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Message {
-    /// Ping request with timestamp
-    Ping { timestamp: DateTime<Utc> },
+    /// Ping request with timestamp and sequence number
+    Ping {
+        timestamp: DateTime<Utc>,
+        #[serde(default)]
+        seq: u64,
+    },
     
-    /// Pong response echoing the original timestamp
-    Pong { timestamp: DateTime<Utc> },
+    /// Pong response echoing the original timestamp and sequence number
+    Pong {
+        timestamp: DateTime<Utc>,
+        #[serde(default)]
+        seq: u64,
+        /// Host's clock when it answered, to estimate the offset between the peers' clocks
+        #[serde(default)]
+        host_time: Option<DateTime<Utc>>,
+    },
     
-    /// Test results from client to host
-    TestResults { results: TestResults },
+    /// Ask the host to open temporary listeners on these ports
+    PortCheckRequest { ports: Vec<PortSpec> },
+    
+    /// Host's state for each requested port, in request order
+    PortCheckReady { states: Vec<PortState> },
+    
+    /// Client finished checking; the host closes its temporary listeners
+    PortCheckDone,
+    
+    /// UDP discovery probe broadcast by clients looking for hosts
+    DiscoveryRequest { version: String },
+    
+    /// Host's answer to a discovery probe
+    DiscoveryResponse { host: HostInfo },
+    
+    /// Ask the host for its own probe data, sent after the client's tests
+    HostResultsRequest,
+    
+    /// Host's probe data, merged into the client's results
+    HostResults { results: Box<HostResults> },
+    
+    /// Test results from client to host, with the settings the client reports them with
+    TestResults {
+        results: Box<TestResults>,
+        #[serde(default)]
+        report_config: ReportConfig,
+    },
     
     /// Error message
     Error { message: String },
 }
 
+/// Host identity advertised in discovery responses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostInfo {
+    pub name: String,
+    /// Control address when the host listens on a single interface, otherwise the response's source
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    /// Control port
+    pub port: u16,
+    pub version: String,
+    /// Tests the host supports, e.g. "udp-echo"
+    pub capabilities: Vec<String>,
+}
+
 /// Collection of test results
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TestResults {
     pub latency: Option<LatencyResults>,
     pub bandwidth: Option<BandwidthResults>,
     pub video: Option<VideoResults>,
     pub audio: Option<AudioResults>,
+    pub link_capacity: Option<LinkCapacityResults>,
+    pub mtu: Option<MtuResults>,
+    pub loss: Option<LossResults>,
+    pub qos: Option<QosResults>,
+    pub ports: Option<PortCheckResults>,
+    pub client_network: Option<NetworkEnvironment>,
+    pub kernel_rtt: Option<KernelRttResults>,
+    pub system: Option<SystemResults>,
+    pub client_environment: Option<SystemEnvironment>,
+    /// What the host measured and probed on its side
+    pub host: Option<HostResults>,
+    /// Client CPU load and stalls while the tests ran
+    pub client_pressure: Option<PressureResults>,
+    /// Client suspends and wall-clock steps while the tests ran
+    #[serde(default)]
+    pub client_clock_events: Vec<ClockEvent>,
+    /// Client interface traffic before, during and after each test
+    #[serde(default)]
+    pub client_traffic: Option<TrafficResults>,
+    /// Client battery, CPU clock and temperatures while the tests ran
+    #[serde(default)]
+    pub client_power: Option<PowerResults>,
 }
 
-/// Network latency test results
+/// Probe data and measurements contributed by the host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostResults {
+    pub info: HostInfo,
+    pub environment: SystemEnvironment,
+    /// Host's network interfaces, with the one carrying the control connection marked
+    pub network: NetworkEnvironment,
+    /// Host side of the control connection, from accepting it to this request
+    pub tcp_info: Option<TcpInfoResults>,
+    /// Host CPU load and stalls while the client's tests ran
+    #[serde(default)]
+    pub pressure: Option<PressureResults>,
+    /// Host suspends and wall-clock steps while the client's tests ran
+    #[serde(default)]
+    pub clock_events: Vec<ClockEvent>,
+    /// Host interface traffic while the client's tests ran
+    #[serde(default)]
+    pub traffic: Option<TrafficResults>,
+    /// Host hardware video encoders and decoders
+    #[serde(default)]
+    pub video: Option<VideoResults>,
+}
+
+/// A discontinuity between the system clocks during the tests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockEvent {
+    pub kind: ClockEventKind,
+    /// Interval it happened in, UTC milliseconds on the test's monotonic timeline
+    pub from_ms: f64,
+    pub to_ms: f64,
+    /// Time asleep, or size of the wall-clock step (negative when set back)
+    pub magnitude_ms: f64,
+}
+
+impl ClockEvent {
+    /// The same event on a clock `offset_ms` behind the one it was recorded on
+    pub fn shifted(&self, offset_ms: f64) -> Self {
+        Self { from_ms: self.from_ms - offset_ms, to_ms: self.to_ms - offset_ms, ..self.clone() }
+    }
+}
+
+/// Kind of clock discontinuity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockEventKind {
+    /// The machine slept: boot time advanced but the monotonic clock did not
+    Suspend,
+    /// The wall clock was set, e.g. stepped by NTP
+    ClockStep,
+}
+
+/// CPU load and pressure stalls sampled at a fixed interval during the tests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PressureResults {
+    pub interval_ms: f64,
+    pub samples: Vec<PressureSample>,
+}
+
+impl PressureResults {
+    /// The sample whose interval contains `timestamp_ms`
+    pub fn sample_at(&self, timestamp_ms: f64) -> Option<&PressureSample> {
+        let index = self.samples.partition_point(|s| s.timestamp_ms < timestamp_ms);
+        let sample = self.samples.get(index)?;
+        (sample.timestamp_ms - timestamp_ms <= self.interval_ms).then_some(sample)
+    }
+}
+
+/// CPU load and stall shares over the interval ending at `timestamp_ms`
+///
+/// The stall shares are the PSI `some` lines: the part of the interval in which
+/// at least one task waited for the resource. `None` where PSI is not available.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureSample {
+    /// UTC milliseconds since the Unix epoch
+    pub timestamp_ms: f64,
+    /// Share of CPU time not idle, from /proc/stat
+    pub cpu_busy_percent: Option<f64>,
+    pub cpu_some_percent: Option<f64>,
+    pub memory_some_percent: Option<f64>,
+    pub io_some_percent: Option<f64>,
+}
+
+impl PressureSample {
+    /// Names of the resources in the order `stalls` returns them
+    pub const RESOURCES: [&'static str; 3] = ["CPU", "memory", "IO"];
+
+    /// Stall share per resource, in the order of `RESOURCES`
+    pub fn stalls(&self) -> [Option<f64>; 3] {
+        [self.cpu_some_percent, self.memory_some_percent, self.io_some_percent]
+    }
+}
+
+/// Interface traffic over the phases of a run, from `/proc/net/dev`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrafficResults {
+    pub phases: Vec<TrafficPhase>,
+}
+
+/// Traffic during one phase, e.g. a quiet window or a test
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficPhase {
+    /// "before", "after" or the test, e.g. "latency"
+    pub name: String,
+    pub duration_ms: f64,
+    /// Bytes moved on each interface except loopback
+    pub interfaces: Vec<InterfaceTraffic>,
+    /// Chequer's own bytes on the wire, headers included
+    pub own_rx_bytes: u64,
+    pub own_tx_bytes: u64,
+}
+
+impl TrafficPhase {
+    /// Received and transmitted bytes on `interface` that were not Chequer's
+    ///
+    /// Our own traffic is only subtracted on the interface that carried the tests.
+    pub fn foreign(&self, interface: &InterfaceTraffic, carries_tests: bool) -> (u64, u64) {
+        if carries_tests {
+            (interface.rx_bytes.saturating_sub(self.own_rx_bytes), interface.tx_bytes.saturating_sub(self.own_tx_bytes))
+        } else {
+            (interface.rx_bytes, interface.tx_bytes)
+        }
+    }
+}
+
+/// Bytes one interface moved during a phase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceTraffic {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Network latency test results
+///
+/// `samples` holds every recorded RTT. The statistics are computed over the
+/// filtered view: without the warm-up samples and, if enabled, the outliers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LatencyResults {
     pub min_ms: f64,
     pub max_ms: f64,
     pub avg_ms: f64,
+    /// Standard deviation of RTT
     pub jitter_ms: f64,
     pub packet_loss_percent: f64,
     pub samples: Vec<f64>,
+    #[serde(default)]
+    pub p50_ms: f64,
+    #[serde(default)]
+    pub p95_ms: f64,
+    #[serde(default)]
+    pub p99_ms: f64,
+    #[serde(default)]
+    pub p999_ms: f64,
+    /// Median absolute deviation
+    #[serde(default)]
+    pub mad_ms: f64,
+    /// Interquartile range (P75 - P25)
+    #[serde(default)]
+    pub iqr_ms: f64,
+    /// Samples above `spike_threshold_ms`
+    #[serde(default)]
+    pub spike_count: usize,
+    /// Spike threshold, a multiple of the median
+    #[serde(default)]
+    pub spike_threshold_ms: f64,
+    /// Longest run of consecutive spike samples
+    #[serde(default)]
+    pub longest_spike_streak: usize,
+    /// RFC 3550 interarrival jitter over consecutive RTTs
+    #[serde(default)]
+    pub rfc3550_jitter_ms: f64,
+    /// Mean absolute RFC 5481 IPDV (RTT change between consecutive probes)
+    #[serde(default)]
+    pub ipdv_mean_ms: f64,
+    /// 99th percentile of absolute IPDV
+    #[serde(default)]
+    pub ipdv_p99_ms: f64,
+    /// 99th percentile of RFC 5481 PDV (RTT above the minimum)
+    #[serde(default)]
+    pub pdv_p99_ms: f64,
+    /// Send time of each sample relative to the first probe
+    #[serde(default)]
+    pub send_offsets_ms: Vec<f64>,
+    /// Leading samples recorded during warm-up and excluded from the statistics
+    #[serde(default)]
+    pub warmup_samples: usize,
+    /// Modified z-score threshold used for outlier classification, if enabled
+    #[serde(default)]
+    pub outlier_threshold: Option<f64>,
+    /// Indices into `samples` classified as outliers and excluded from the statistics
+    #[serde(default)]
+    pub outlier_indices: Vec<usize>,
+    /// Indices into `samples` in flight across a suspend or clock step, excluded from the statistics
+    #[serde(default)]
+    pub invalid_indices: Vec<usize>,
+    /// How the probes were scheduled
+    #[serde(default)]
+    pub schedule: ProbeSchedule,
+    /// Kernel statistics of the control connection around the test
+    #[serde(default)]
+    pub tcp_info: Option<TcpInfoResults>,
+    /// Thread settings granted in high-precision mode, `None` in normal mode
+    #[serde(default)]
+    pub precise: Option<PreciseTiming>,
+    /// UTC milliseconds since the Unix epoch that `send_offsets_ms` count from
+    #[serde(default)]
+    pub started_at_ms: Option<f64>,
+    /// How far the host's clock was ahead of the client's, estimated from the probe replies
+    #[serde(default)]
+    pub host_clock_offset_ms: Option<f64>,
+}
+
+impl LatencyResults {
+    /// Compute latency statistics from round-trip samples in milliseconds
+    pub fn from_samples(samples: Vec<f64>, packet_loss_percent: f64) -> Self {
+        let mut results = Self {
+            packet_loss_percent,
+            samples,
+            ..Self::default()
+        };
+        results.update_statistics();
+        results
+    }
+
+    /// Attach the send time of each sample
+    pub fn with_send_offsets(mut self, send_offsets_ms: Vec<f64>) -> Self {
+        self.send_offsets_ms = send_offsets_ms;
+        self
+    }
+
+    /// Record the UTC time the send offsets count from, to line samples up with other timelines
+    pub fn with_start_time(mut self, started_at_ms: f64) -> Self {
+        self.started_at_ms = Some(started_at_ms);
+        self
+    }
+
+    /// Record the host's clock offset, to move host timelines onto `started_at_ms`'s clock
+    pub fn with_host_clock_offset(mut self, offset_ms: f64) -> Self {
+        self.host_clock_offset_ms = Some(offset_ms);
+        self
+    }
+
+    /// Send offset and RTT of each measured sample not invalidated by a clock event
+    ///
+    /// Empty if no send offsets were recorded.
+    pub fn valid_timeline(&self) -> Vec<(f64, f64)> {
+        self.measured_send_offsets().iter()
+            .zip(self.measured_samples())
+            .enumerate()
+            .filter(|(i, _)| !self.invalid_indices.contains(&(i + self.warmup_samples)))
+            .map(|(_, (&offset, &rtt))| (offset, rtt))
+            .collect()
+    }
+
+    /// Record the kernel's view of the connection before and after the test
+    pub fn with_tcp_info(mut self, before: TcpInfoSnapshot, after: TcpInfoSnapshot) -> Self {
+        self.tcp_info = Some(TcpInfoResults { before, after });
+        self
+    }
+
+    /// Record the thread settings the probes ran with in high-precision mode
+    pub fn with_precise_timing(mut self, timing: PreciseTiming) -> Self {
+        self.precise = Some(timing);
+        self
+    }
+
+    /// Record the probe schedule used for the samples
+    pub fn with_schedule(mut self, schedule: ProbeSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Average probe rate over the measured samples, zero without send offsets
+    pub fn probe_rate_hz(&self) -> f64 {
+        let offsets = self.measured_send_offsets();
+        match (offsets.first(), offsets.last()) {
+            (Some(first), Some(last)) if last > first => {
+                (offsets.len() - 1) as f64 * 1000.0 / (last - first)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Exclude the first `count` samples from the statistics
+    pub fn with_warmup(mut self, count: usize) -> Self {
+        self.warmup_samples = count.min(self.samples.len());
+        self.classify_outliers();
+        self.update_statistics();
+        self
+    }
+
+    /// Exclude samples whose modified z-score exceeds `threshold` from the statistics
+    pub fn with_outlier_filter(mut self, threshold: f64) -> Self {
+        self.outlier_threshold = Some(threshold);
+        self.classify_outliers();
+        self.update_statistics();
+        self
+    }
+
+    /// Exclude samples in flight during any of `events`, which must be on the `started_at_ms` timeline
+    pub fn with_clock_events(mut self, events: &[ClockEvent]) -> Self {
+        let Some(start) = self.started_at_ms else {
+            return self;
+        };
+        if self.send_offsets_ms.len() != self.samples.len() {
+            return self;
+        }
+        self.invalid_indices = (0..self.samples.len())
+            .filter(|&i| {
+                let sent = start + self.send_offsets_ms[i];
+                let received = sent + self.samples[i];
+                events.iter().any(|event| event.from_ms <= received && event.to_ms >= sent)
+            })
+            .collect();
+        self.update_statistics();
+        self
+    }
+
+    /// Samples recorded after the warm-up phase, outliers included
+    pub fn measured_samples(&self) -> &[f64] {
+        &self.samples[self.warmup_samples.min(self.samples.len())..]
+    }
+
+    /// Send offsets matching `measured_samples`, empty if none were recorded
+    pub fn measured_send_offsets(&self) -> &[f64] {
+        if self.send_offsets_ms.len() != self.samples.len() {
+            return &[];
+        }
+        &self.send_offsets_ms[self.warmup_samples.min(self.samples.len())..]
+    }
+
+    /// Samples the statistics are computed from
+    pub fn filtered_samples(&self) -> Vec<f64> {
+        self.samples.iter()
+            .enumerate()
+            .skip(self.warmup_samples)
+            .filter(|(i, _)| !self.outlier_indices.contains(i) && !self.invalid_indices.contains(i))
+            .map(|(_, &x)| x)
+            .collect()
+    }
+
+    fn classify_outliers(&mut self) {
+        self.outlier_indices = match self.outlier_threshold {
+            Some(threshold) => stats::modified_z_outliers(self.measured_samples(), threshold)
+                .into_iter()
+                .map(|i| i + self.warmup_samples)
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    fn update_statistics(&mut self) {
+        let samples = self.filtered_samples();
+        let p50_ms = stats::percentile(&samples, 50.0);
+        let spike_threshold_ms = p50_ms * stats::SPIKE_MEDIAN_MULTIPLE;
+        let ipdv_abs: Vec<f64> = stats::ipdv(&samples).iter().map(|d| d.abs()).collect();
+
+        self.min_ms = samples.iter().cloned().reduce(f64::min).unwrap_or(0.0);
+        self.max_ms = samples.iter().cloned().reduce(f64::max).unwrap_or(0.0);
+        self.avg_ms = stats::mean(&samples);
+        // Jitter as the standard deviation of RTT
+        self.jitter_ms = stats::std_dev(&samples);
+        self.p50_ms = p50_ms;
+        self.p95_ms = stats::percentile(&samples, 95.0);
+        self.p99_ms = stats::percentile(&samples, 99.0);
+        self.p999_ms = stats::percentile(&samples, 99.9);
+        self.mad_ms = stats::median_absolute_deviation(&samples);
+        self.iqr_ms = stats::interquartile_range(&samples);
+        self.spike_count = stats::count_above(&samples, spike_threshold_ms);
+        self.spike_threshold_ms = spike_threshold_ms;
+        self.longest_spike_streak = stats::longest_streak_above(&samples, spike_threshold_ms);
+        self.rfc3550_jitter_ms = stats::rfc3550_jitter(&samples);
+        self.ipdv_mean_ms = stats::mean(&ipdv_abs);
+        self.ipdv_p99_ms = stats::percentile(&ipdv_abs, 99.0);
+        self.pdv_p99_ms = stats::percentile(&stats::pdv(&samples), 99.0);
+    }
+}
+
+/// Timer wake-up latency of the client OS, measured like cyclictest
+///
+/// A thread sleeps until absolute deadlines `interval_us` apart and records
+/// how late it woke up, which is how long a ready thread waits for a CPU.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemResults {
+    pub interval_us: f64,
+    pub samples: usize,
+    pub min_us: f64,
+    pub avg_us: f64,
+    pub p50_us: f64,
+    pub p99_us: f64,
+    pub p999_us: f64,
+    pub max_us: f64,
+    /// Wake-ups per latency bucket
+    pub histogram: Vec<WakeupBucket>,
+}
+
+/// Wake-ups with a latency up to `upper_us` and above the previous bucket's bound
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WakeupBucket {
+    /// Upper bound, `None` for the last, open bucket
+    pub upper_us: Option<f64>,
+    pub count: usize,
+}
+
+impl SystemResults {
+    /// Histogram bucket bounds in microseconds, roughly logarithmic
+    pub const BUCKET_BOUNDS_US: [f64; 10] = [10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 16667.0];
+
+    /// Summarize wake-up latencies in microseconds
+    pub fn from_samples(interval_us: f64, latencies_us: &[f64]) -> Self {
+        let mut histogram: Vec<WakeupBucket> = Self::BUCKET_BOUNDS_US.iter()
+            .map(|&upper| WakeupBucket { upper_us: Some(upper), count: 0 })
+            .chain(std::iter::once(WakeupBucket { upper_us: None, count: 0 }))
+            .collect();
+        for &latency in latencies_us {
+            let bucket = Self::BUCKET_BOUNDS_US.iter()
+                .position(|&upper| latency <= upper)
+                .unwrap_or(Self::BUCKET_BOUNDS_US.len());
+            histogram[bucket].count += 1;
+        }
+
+        Self {
+            interval_us,
+            samples: latencies_us.len(),
+            min_us: latencies_us.iter().cloned().reduce(f64::min).unwrap_or(0.0),
+            avg_us: stats::mean(latencies_us),
+            p50_us: stats::percentile(latencies_us, 50.0),
+            p99_us: stats::percentile(latencies_us, 99.0),
+            p999_us: stats::percentile(latencies_us, 99.9),
+            max_us: latencies_us.iter().cloned().fold(0.0, f64::max),
+            histogram,
+        }
+    }
+}
+
+/// Packet size sweep and link capacity estimate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCapacityResults {
+    /// RTT per probe payload size
+    pub points: Vec<PacketSizePoint>,
+    /// RTT at zero payload from the linear fit of minimum RTT against size
+    pub base_rtt_ms: f64,
+    /// Round-trip serialization delay per payload byte (slope of the fit)
+    pub per_byte_us: f64,
+    /// Bottleneck capacity implied by the per-byte delay
+    pub estimated_capacity_mbps: Option<f64>,
+    /// Bottleneck capacity from the dispersion of back-to-back packet pairs
+    pub packet_pair_capacity_mbps: Option<f64>,
+}
+
+impl LinkCapacityResults {
+    /// Best available capacity estimate, preferring packet-pair dispersion
+    pub fn capacity_mbps(&self) -> Option<f64> {
+        self.packet_pair_capacity_mbps.or(self.estimated_capacity_mbps)
+    }
+}
+
+/// RTT statistics for one probe payload size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketSizePoint {
+    pub payload_bytes: usize,
+    /// `None` when every probe of this size was lost
+    pub min_rtt_ms: Option<f64>,
+    pub median_rtt_ms: Option<f64>,
+    pub sent: usize,
+    pub lost: usize,
+}
+
+/// Path MTU discovery results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtuResults {
+    /// Largest UDP payload that reached the host and came back
+    pub max_payload_bytes: usize,
+    /// IP and UDP header bytes added to each payload
+    pub header_bytes: usize,
+    /// Largest payload the search tried
+    pub search_ceiling_bytes: usize,
+    /// Whether the Don't Fragment bit was set, without it the search finds the fragmentation limit instead
+    pub dont_fragment: bool,
+    /// Whether a full 1500 byte datagram arrived when fragmentation was allowed
+    ///
+    /// Only checked when the path MTU is below 1500 bytes.
+    pub fragmented_delivery: Option<bool>,
+}
+
+impl MtuResults {
+    /// Path MTU implied by the largest payload
+    pub fn path_mtu(&self) -> usize {
+        self.max_payload_bytes + self.header_bytes
+    }
+}
+
+/// UDP loss stream results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LossResults {
+    /// Probes sent, with sequence numbers `0..sent`
+    pub sent: usize,
+    /// Gap between probes
+    pub interval_ms: f64,
+    pub payload_bytes: usize,
+    /// Sequence numbers of probes whose echo never arrived, ascending
+    pub lost_seqs: Vec<u32>,
+}
+
+impl LossResults {
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        self.lost_seqs.len() as f64 / self.sent as f64 * 100.0
+    }
+
+    /// Per-probe loss indicator in send order
+    pub fn lost_sequence(&self) -> Vec<bool> {
+        let mut lost = vec![false; self.sent];
+        for &seq in &self.lost_seqs {
+            if let Some(slot) = lost.get_mut(seq as usize) {
+                *slot = true;
+            }
+        }
+        lost
+    }
+}
+
+/// DSCP marking preservation results, one entry per traffic class
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QosResults {
+    pub classes: Vec<TrafficClassResult>,
+}
+
+/// Probes sent with one DSCP marking
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficClassResult {
+    /// Short class name, e.g. "EF"
+    pub name: String,
+    /// DSCP value the probes were sent with
+    pub dscp: u8,
+    /// DSCP value the host saw most often, `None` if the host could not read it
+    pub received_dscp: Option<u8>,
+    pub sent: usize,
+    pub lost: usize,
+    pub avg_rtt_ms: Option<f64>,
+    #[serde(default)]
+    pub median_rtt_ms: Option<f64>,
+    pub p95_rtt_ms: Option<f64>,
+}
+
+impl TrafficClassResult {
+    /// Whether the marking arrived unchanged, `None` if unknown
+    pub fn preserved(&self) -> Option<bool> {
+        self.received_dscp.map(|dscp| dscp == self.dscp)
+    }
+
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        self.lost as f64 / self.sent as f64 * 100.0
+    }
+}
+
+/// Reachability of the host's Remote Play ports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortCheckResults {
+    pub ports: Vec<PortCheckResult>,
+}
+
+/// Reachability of one port
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortCheckResult {
+    pub port: PortSpec,
+    pub host_state: PortState,
+    /// Whether the client got through, `None` if the port could not be tested
+    pub reachable: Option<bool>,
+    /// Why the check did not get through, `None` if it did or was not run
+    pub failure: Option<PortFailure>,
+}
+
+/// OS and hardware of one side of the test
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SystemEnvironment {
+    /// Distribution name from os-release, e.g. "SteamOS"
+    pub os: Option<String>,
+    pub kernel: Option<String>,
+    pub cpu_model: Option<String>,
+    pub cpu_threads: Option<usize>,
+    /// cpufreq scaling governor of CPU 0
+    pub cpu_governor: Option<String>,
+    pub memory_mb: Option<u64>,
+    pub gpus: Vec<GpuInfo>,
+    /// Connected displays
+    pub displays: Vec<DisplayInfo>,
+    /// DMI board name, e.g. "Jupiter"
+    pub board_name: Option<String>,
+    pub steam_deck: bool,
+    /// Power supply and thermal state when the environment was probed
+    #[serde(default)]
+    pub power: Option<PowerState>,
+}
+
+/// Battery, power supply, CPU clock and thermal state at one moment
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerState {
+    /// Whether an AC adapter is connected, `None` if none is reported
+    pub ac_online: Option<bool>,
+    /// Charge of the system battery, if there is one
+    pub battery_percent: Option<f64>,
+    /// System battery status from sysfs, e.g. "Discharging"
+    pub battery_status: Option<String>,
+    /// Current frequency averaged over the CPUs
+    pub cpu_freq_mhz: Option<f64>,
+    /// Highest frequency the CPUs support
+    pub cpu_max_freq_mhz: Option<f64>,
+    /// Firmware power profile, e.g. "low-power"
+    pub platform_profile: Option<String>,
+    pub temperatures: Vec<Temperature>,
+}
+
+impl PowerState {
+    /// Whether the machine ran from its battery
+    ///
+    /// Only with a system battery that is discharging, or with no AC adapter
+    /// online to run from instead.
+    pub fn on_battery(&self) -> bool {
+        let has_battery = self.battery_percent.is_some() || self.battery_status.is_some();
+        has_battery && (self.battery_status.as_deref() == Some("Discharging") || self.ac_online != Some(true))
+    }
+
+    /// The hottest sensor
+    pub fn hottest(&self) -> Option<&Temperature> {
+        self.temperatures.iter().max_by(|a, b| a.celsius.total_cmp(&b.celsius))
+    }
+
+    /// A sensor at its throttling point, if any
+    pub fn throttling(&self) -> Option<&Temperature> {
+        self.temperatures.iter().find(|t| t.is_throttling())
+    }
+}
+
+/// One temperature sensor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Temperature {
+    /// Thermal zone type or hwmon chip and label, e.g. "acpitz" or "amdgpu edge"
+    pub sensor: String,
+    pub celsius: f64,
+    /// Temperature at which the kernel or firmware starts throttling, if known
+    pub throttle_celsius: Option<f64>,
+}
+
+impl Temperature {
+    /// Whether the sensor reached its throttling point
+    pub fn is_throttling(&self) -> bool {
+        self.throttle_celsius.is_some_and(|limit| self.celsius >= limit)
+    }
+}
+
+/// Power and thermal state sampled at a fixed interval during the tests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PowerResults {
+    pub interval_ms: f64,
+    pub samples: Vec<PowerSample>,
+}
+
+/// Power and thermal state at `timestamp_ms`, UTC milliseconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerSample {
+    pub timestamp_ms: f64,
+    pub state: PowerState,
+}
+
+/// A GPU from /sys/class/drm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuInfo {
+    /// DRM card name, e.g. "card0"
+    pub card: String,
+    /// Vendor name, or the PCI vendor ID if unknown
+    pub vendor: String,
+    /// PCI device ID, e.g. "0x1435"
+    pub device_id: Option<String>,
+    /// Kernel driver, e.g. "amdgpu"
+    pub driver: Option<String>,
+}
+
+/// A connected display
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisplayInfo {
+    /// DRM connector, e.g. "eDP-1"
+    pub connector: String,
+    /// Supported modes, preferred first, e.g. "1280x800"
+    pub modes: Vec<String>,
+}
+
+/// Network interfaces of one side of the test
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkEnvironment {
+    pub interfaces: Vec<InterfaceInfo>,
+    /// Interface carrying the test traffic, if it could be identified
+    pub active_interface: Option<String>,
+}
+
+impl NetworkEnvironment {
+    /// The interface carrying the test traffic
+    pub fn active(&self) -> Option<&InterfaceInfo> {
+        let name = self.active_interface.as_ref()?;
+        self.interfaces.iter().find(|i| &i.name == name)
+    }
+}
+
+/// Kind of network interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceKind {
+    Ethernet,
+    Wifi,
+    Loopback,
+    /// Bridges, tunnels, VPNs and other interfaces without hardware
+    Virtual,
+}
+
+/// One network interface
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub kind: InterfaceKind,
+    /// Operational state from sysfs, e.g. "up"
+    pub operstate: String,
+    pub mtu: Option<u32>,
+    /// Negotiated link speed, Ethernet only
+    pub speed_mbps: Option<u32>,
+    pub wifi: Option<WifiInfo>,
+}
+
+/// WiFi link state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WifiInfo {
+    pub ssid: Option<String>,
+    pub frequency_mhz: Option<u32>,
+    pub signal_dbm: Option<f64>,
+    pub noise_dbm: Option<f64>,
+    /// Transmit bitrate
+    pub bitrate_mbps: Option<f64>,
+    pub power_save: Option<bool>,
+}
+
+impl WifiInfo {
+    /// Band name derived from the frequency, e.g. "5 GHz"
+    pub fn band(&self) -> Option<&'static str> {
+        match self.frequency_mhz? {
+            2400..=2500 => Some("2.4 GHz"),
+            4900..=5900 => Some("5 GHz"),
+            5925..=7125 => Some("6 GHz"),
+            _ => None,
+        }
+    }
+}
+
+/// UDP probe RTTs measured by the application and from kernel software timestamps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KernelRttResults {
+    pub sent: usize,
+    /// Application-level RTT of each probe that got both kernel timestamps
+    pub app_rtt_ms: Vec<f64>,
+    /// RTT of the same probes from the TX to the RX software timestamp
+    pub kernel_rtt_ms: Vec<f64>,
+}
+
+impl KernelRttResults {
+    /// Local stack overhead of each probe: application minus kernel RTT
+    pub fn stack_overhead_ms(&self) -> Vec<f64> {
+        self.app_rtt_ms.iter()
+            .zip(&self.kernel_rtt_ms)
+            .map(|(app, kernel)| app - kernel)
+            .collect()
+    }
+}
+
+/// Settings the busy-polling probe thread of high-precision mode was granted
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PreciseTiming {
+    /// CPU the probe thread was pinned to
+    pub pinned_cpu: Option<usize>,
+    /// SCHED_FIFO priority, if requested and granted
+    pub fifo_priority: Option<i32>,
+    /// Requested settings the system refused, with the reason
+    pub denied: Vec<String>,
+}
+
+/// Kernel TCP statistics of a socket, read with `TCP_INFO`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TcpInfoSnapshot {
+    /// Smoothed RTT
+    pub rtt_ms: f64,
+    pub rttvar_ms: f64,
+    /// Retransmission timeout
+    pub rto_ms: f64,
+    /// Congestion window in segments
+    pub snd_cwnd: u32,
+    /// Segments retransmitted over the lifetime of the connection
+    pub total_retrans: u32,
+    /// Segments currently presumed lost
+    pub lost: u32,
+    /// Payload bytes sent and received over the lifetime of the connection
+    #[serde(default)]
+    pub bytes_sent: u64,
+    #[serde(default)]
+    pub bytes_received: u64,
+    /// Segments sent and received, pure ACKs included
+    #[serde(default)]
+    pub segs_out: u32,
+    #[serde(default)]
+    pub segs_in: u32,
+}
+
+/// `TCP_INFO` of the control connection before and after the latency test
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TcpInfoResults {
+    pub before: TcpInfoSnapshot,
+    pub after: TcpInfoSnapshot,
+}
+
+impl TcpInfoResults {
+    /// Segments retransmitted during the test
+    pub fn retransmits(&self) -> u32 {
+        self.after.total_retrans.saturating_sub(self.before.total_retrans)
+    }
 }
 
 /// Bandwidth test results
@@ -46,10 +937,44 @@ pub struct BandwidthResults {
 }
 
 /// Video codec and performance results
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VideoResults {
+    /// Codecs with a hardware decoder, e.g. "hevc"
     pub supported_codecs: Vec<String>,
     pub decode_fps: Option<f64>,
+    /// VA-API driver, e.g. "Mesa Gallium driver 23.1.3 for AMD Custom GPU 0405"
+    #[serde(default)]
+    pub driver: Option<String>,
+    /// Hardware decode and encode profiles of H.264, HEVC and AV1
+    #[serde(default)]
+    pub profiles: Vec<VideoProfile>,
+}
+
+impl VideoResults {
+    /// Codecs with a hardware encoder, in the order first seen
+    pub fn encode_codecs(&self) -> Vec<&str> {
+        let mut codecs: Vec<&str> = Vec::new();
+        for profile in self.profiles.iter().filter(|p| p.encode) {
+            if !codecs.contains(&profile.codec.as_str()) {
+                codecs.push(&profile.codec);
+            }
+        }
+        codecs
+    }
+}
+
+/// One VA-API profile and entrypoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoProfile {
+    /// "h264", "hevc" or "av1"
+    pub codec: String,
+    /// VA-API profile name, e.g. "VAProfileHEVCMain10"
+    pub profile: String,
+    /// Encode entrypoint rather than decode
+    pub encode: bool,
+    /// Largest picture the profile handles, if the driver reports it
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
 }
 
 /// Audio system results
@@ -58,3 +983,75 @@ pub struct AudioResults {
     pub output_devices: Vec<String>,
     pub sample_rate: Option<u32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_and_outliers_excluded_from_statistics() {
+        let mut samples = vec![40.0, 25.0];
+        samples.extend([5.0, 5.2, 4.8, 5.1, 90.0, 4.9, 5.0]);
+
+        let raw = LatencyResults::from_samples(samples.clone(), 0.0);
+        assert_eq!(raw.max_ms, 90.0);
+
+        let filtered = LatencyResults::from_samples(samples, 0.0)
+            .with_warmup(2)
+            .with_outlier_filter(3.5);
+        assert_eq!(filtered.samples.len(), 9);
+        assert_eq!(filtered.measured_samples().len(), 7);
+        assert_eq!(filtered.outlier_indices, vec![6]);
+        assert_eq!(filtered.filtered_samples().len(), 6);
+        assert_eq!(filtered.max_ms, 5.2);
+        assert_eq!(filtered.min_ms, 4.8);
+    }
+
+    #[test]
+    fn test_empty_statistics_are_serializable() {
+        // A warm-up covering every sample leaves nothing to summarize
+        let lat = LatencyResults::from_samples(vec![5.0, 6.0], 100.0).with_warmup(2);
+        assert_eq!((lat.min_ms, lat.max_ms), (0.0, 0.0));
+
+        let results = Box::new(TestResults { latency: Some(lat), ..TestResults::default() });
+        let message = Message::TestResults { results, report_config: ReportConfig::default() };
+        assert!(serde_json::to_string(&message).is_ok());
+    }
+
+    #[test]
+    fn test_samples_across_a_suspend_are_invalid() {
+        let start = 1_700_000_000_000.0;
+        let mut samples = vec![5.0; 10];
+        samples[4] = 30_005.0;
+        let offsets = (0..10).map(|i| i as f64 * 100.0).collect();
+        let suspend = ClockEvent { kind: ClockEventKind::Suspend, from_ms: start + 420.0, to_ms: start + 520.0, magnitude_ms: 30_000.0 };
+
+        let lat = LatencyResults::from_samples(samples, 0.0)
+            .with_send_offsets(offsets)
+            .with_start_time(start)
+            .with_clock_events(std::slice::from_ref(&suspend));
+        // Sent at 400ms and 500ms, both in flight during the window
+        assert_eq!(lat.invalid_indices, vec![4, 5]);
+        assert_eq!(lat.max_ms, 5.0);
+        assert_eq!(lat.filtered_samples().len(), 8);
+
+        // The same suspend recorded by a host whose clock runs 10s ahead
+        let on_host = ClockEvent { from_ms: suspend.from_ms + 10_000.0, to_ms: suspend.to_ms + 10_000.0, ..suspend.clone() };
+        assert_eq!(on_host.shifted(10_000.0), suspend);
+    }
+    #[test]
+    fn test_on_battery_needs_a_battery() {
+        let battery = |ac_online, status: &str| PowerState {
+            ac_online,
+            battery_percent: Some(80.0),
+            battery_status: Some(status.to_string()),
+            ..PowerState::default()
+        };
+        // A desktop without an AC adapter or battery in sysfs
+        assert!(!PowerState { ac_online: Some(false), ..PowerState::default() }.on_battery());
+        assert!(battery(Some(true), "Discharging").on_battery());
+        assert!(battery(Some(false), "Not charging").on_battery());
+        assert!(battery(None, "Unknown").on_battery());
+        assert!(!battery(Some(true), "Full").on_battery());
+    }
+}
@@ -0,0 +1,164 @@
+//! Statistics helpers shared by the agent and the report engine
+
+/// Samples above this multiple of the median count as spikes
+pub const SPIKE_MEDIAN_MULTIPLE: f64 = 3.0;
+
+/// Calculate percentile value (nearest rank) from unsorted data
+pub fn percentile(data: &[f64], p: f64) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let idx = ((sorted.len() - 1) as f64 * p / 100.0).round() as usize;
+    sorted[idx]
+}
+
+/// Arithmetic mean, zero for empty data
+pub fn mean(data: &[f64]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    data.iter().sum::<f64>() / data.len() as f64
+}
+
+/// Population standard deviation
+pub fn std_dev(data: &[f64]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let avg = mean(data);
+    let variance = data.iter()
+        .map(|&x| (x - avg).powi(2))
+        .sum::<f64>() / data.len() as f64;
+    variance.sqrt()
+}
+
+/// Median absolute deviation from the median
+pub fn median_absolute_deviation(data: &[f64]) -> f64 {
+    let median = percentile(data, 50.0);
+    let deviations: Vec<f64> = data.iter().map(|&x| (x - median).abs()).collect();
+    percentile(&deviations, 50.0)
+}
+
+/// Interquartile range (P75 - P25)
+pub fn interquartile_range(data: &[f64]) -> f64 {
+    percentile(data, 75.0) - percentile(data, 25.0)
+}
+
+/// Count samples above `threshold`
+pub fn count_above(data: &[f64], threshold: f64) -> usize {
+    data.iter().filter(|&&x| x > threshold).count()
+}
+
+/// Length of the longest run of consecutive samples above `threshold`
+pub fn longest_streak_above(data: &[f64], threshold: f64) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for &x in data {
+        if x > threshold {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// RFC 3550 interarrival jitter: J += (|D(i-1,i)| - J) / 16
+///
+/// Applied to round-trip samples, D is the change in RTT between consecutive probes.
+pub fn rfc3550_jitter(data: &[f64]) -> f64 {
+    data.windows(2).fold(0.0, |jitter, pair| {
+        let d = (pair[1] - pair[0]).abs();
+        jitter + (d - jitter) / 16.0
+    })
+}
+
+/// RFC 5481 IP packet delay variation: delay difference between consecutive packets
+pub fn ipdv(data: &[f64]) -> Vec<f64> {
+    data.windows(2).map(|pair| pair[1] - pair[0]).collect()
+}
+
+/// RFC 5481 packet delay variation: delay of each packet above the minimum delay
+pub fn pdv(data: &[f64]) -> Vec<f64> {
+    let min = data.iter().cloned().fold(f64::INFINITY, f64::min);
+    data.iter().map(|&x| x - min).collect()
+}
+
+/// Indices of outliers by modified z-score (Iglewicz and Hoaglin)
+///
+/// Uses the median absolute deviation, falling back to the mean absolute
+/// deviation when more than half of the samples are identical.
+pub fn modified_z_outliers(data: &[f64], threshold: f64) -> Vec<usize> {
+    let median = percentile(data, 50.0);
+    let mad = median_absolute_deviation(data);
+    let scale = if mad > 0.0 {
+        mad / 0.6745
+    } else {
+        1.253314 * mean(&data.iter().map(|&x| (x - median).abs()).collect::<Vec<_>>())
+    };
+    if scale == 0.0 {
+        return Vec::new();
+    }
+
+    data.iter()
+        .enumerate()
+        .filter(|(_, &x)| ((x - median) / scale).abs() > threshold)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        let data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+        assert_eq!(percentile(&data, 50.0), 6.0);
+        assert_eq!(percentile(&data, 95.0), 10.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn test_spread() {
+        let data = vec![1.0, 2.0, 3.0, 4.0, 100.0];
+        assert_eq!(median_absolute_deviation(&data), 1.0);
+        assert_eq!(interquartile_range(&data), 2.0);
+        assert_eq!(std_dev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), 2.0);
+    }
+
+    #[test]
+    fn test_spikes_and_streaks() {
+        let data = vec![1.0, 5.0, 6.0, 1.0, 7.0, 1.0, 8.0, 9.0, 9.5];
+        assert_eq!(count_above(&data, 3.0), 6);
+        assert_eq!(longest_streak_above(&data, 3.0), 3);
+        assert_eq!(longest_streak_above(&[], 3.0), 0);
+    }
+
+    #[test]
+    fn test_delay_variation() {
+        assert_eq!(rfc3550_jitter(&[10.0, 10.0, 10.0]), 0.0);
+        // A single 16ms step moves the smoothed jitter by 1ms
+        assert_eq!(rfc3550_jitter(&[10.0, 26.0]), 1.0);
+        assert_eq!(rfc3550_jitter(&[10.0, 26.0, 26.0]), 0.9375);
+
+        assert_eq!(ipdv(&[10.0, 12.0, 9.0]), vec![2.0, -3.0]);
+        assert_eq!(pdv(&[10.0, 12.0, 9.0]), vec![1.0, 3.0, 0.0]);
+        assert!(ipdv(&[10.0]).is_empty());
+    }
+
+    #[test]
+    fn test_modified_z_outliers() {
+        let data = vec![10.0, 10.5, 9.5, 10.2, 9.8, 10.1, 45.0, 9.9];
+        assert_eq!(modified_z_outliers(&data, 3.5), vec![6]);
+        // Identical samples except one: MAD is zero, mean absolute deviation takes over
+        let flat = vec![5.0, 5.0, 5.0, 5.0, 5.0, 60.0];
+        assert_eq!(modified_z_outliers(&flat, 3.5), vec![5]);
+        assert!(modified_z_outliers(&[5.0; 4], 3.5).is_empty());
+    }
+}
@@ -32,12 +32,207 @@ impl Status {
     pub const RESET: &'static str = "\x1b[0m";
 }
 
+/// How latency probes are scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ProbeSchedule {
+    /// Wait `latency_interval_ms` after each reply before the next probe
+    #[default]
+    Sequential,
+    /// Send a probe every `latency_interval_ms` on an absolute schedule, regardless of RTT
+    FixedRate,
+    /// Exponentially distributed gaps averaging `latency_interval_ms`, avoiding aliasing
+    Poisson,
+    /// Bursts of `burst_size` probes at `rate_hz`, one burst every `latency_interval_ms`
+    Burst { rate_hz: u32, burst_size: usize },
+}
+
+impl std::fmt::Display for ProbeSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProbeSchedule::Sequential => write!(f, "sequential"),
+            ProbeSchedule::FixedRate => write!(f, "fixed-rate"),
+            ProbeSchedule::Poisson => write!(f, "poisson"),
+            ProbeSchedule::Burst { rate_hz, burst_size } => {
+                write!(f, "burst ({} × {} Hz)", burst_size, rate_hz)
+            }
+        }
+    }
+}
+
+/// Which peer of a test something was measured on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Client,
+    Host,
+}
+
+impl Side {
+    /// Capitalized name, for the start of a sentence or a table label
+    pub fn title(self) -> &'static str {
+        match self {
+            Side::Client => "Client",
+            Side::Host => "Host",
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Client => write!(f, "client"),
+            Side::Host => write!(f, "host"),
+        }
+    }
+}
+
+/// Transport protocol of a port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl std::fmt::Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Transport::Tcp => write!(f, "TCP"),
+            Transport::Udp => write!(f, "UDP"),
+        }
+    }
+}
+
+/// A port to check for reachability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortSpec {
+    pub transport: Transport,
+    pub port: u16,
+}
+
+impl PortSpec {
+    /// Ports used by Steam Remote Play: UDP 27031-27036 and TCP 27036-27037
+    pub fn steam_remote_play() -> Vec<PortSpec> {
+        let udp = (27031..=27036).map(|port| PortSpec { transport: Transport::Udp, port });
+        let tcp = (27036..=27037).map(|port| PortSpec { transport: Transport::Tcp, port });
+        udp.chain(tcp).collect()
+    }
+
+    /// Parse a list such as `udp:27031-27036,tcp:27036-27037`
+    pub fn parse_list(list: &str) -> Result<Vec<PortSpec>, String> {
+        let mut ports = Vec::new();
+        for item in list.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (transport, range) = item.split_once(':')
+                .ok_or_else(|| format!("expected <tcp|udp>:<port>[-<port>], got '{}'", item))?;
+            let transport = match transport.to_ascii_lowercase().as_str() {
+                "tcp" => Transport::Tcp,
+                "udp" => Transport::Udp,
+                other => return Err(format!("unknown transport '{}'", other)),
+            };
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            let parse = |port: &str| port.trim().parse::<u16>()
+                .map_err(|_| format!("invalid port '{}'", port));
+            let (first, last) = (parse(first)?, parse(last)?);
+            if first == 0 || first > last {
+                return Err(format!("invalid port range '{}'", range));
+            }
+            ports.extend((first..=last).map(|port| PortSpec { transport, port }));
+        }
+        Ok(ports)
+    }
+}
+
+impl std::fmt::Display for PortSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.transport, self.port)
+    }
+}
+
+/// What the host could do with a port during the reachability check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "detail", rename_all = "snake_case")]
+pub enum PortState {
+    /// The host opened a temporary listener
+    Listening,
+    /// Another process, usually Steam, already has the port; checked passively
+    InUse,
+    /// The listener could not be opened for another reason
+    Unavailable(String),
+}
+
+/// Why a reachability check did not get through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum PortFailure {
+    /// The host actively rejected it, with a TCP reset or ICMP port unreachable
+    Refused,
+    /// A TCP connection attempt got no answer
+    TimedOut,
+    /// UDP probes got no echo
+    NoEcho,
+    /// Any other error, e.g. no route to the host
+    Other(String),
+}
+
+impl std::fmt::Display for PortFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortFailure::Refused => write!(f, "refused"),
+            PortFailure::TimedOut => write!(f, "timed out"),
+            PortFailure::NoEcho => write!(f, "no echo"),
+            PortFailure::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
 /// Diagnostic test configuration
+///
+/// Fields missing from serialized configs take their values from `Default`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TestConfig {
     pub latency_samples: usize,
     pub latency_interval_ms: u64,
     pub bandwidth_duration_secs: u64,
+    /// Probes sent before `latency_samples`, recorded but excluded from statistics
+    pub latency_warmup_samples: usize,
+    /// Modified z-score above which samples are classified as outliers (disabled if `None`)
+    pub latency_outlier_threshold: Option<f64>,
+    /// Probe scheduling mode for the latency test
+    pub probe_schedule: ProbeSchedule,
+    /// Run the UDP packet size sweep and link capacity estimate
+    pub packet_size_sweep: bool,
+    /// Probes sent for each payload size in the sweep
+    pub sweep_probes_per_size: usize,
+    /// Search for the largest UDP payload that passes without fragmentation
+    pub mtu_discovery: bool,
+    /// Send a fixed-rate UDP stream and record which probes are lost
+    pub loss_test: bool,
+    /// Probes sent in the loss stream
+    pub loss_probes: usize,
+    /// Gap between loss stream probes
+    pub loss_interval_ms: u64,
+    /// Send probes with several DSCP markings and check what arrives
+    pub qos_test: bool,
+    /// Probes sent per traffic class
+    pub qos_probes_per_class: usize,
+    /// Check that the host's Remote Play ports are reachable
+    pub port_check: bool,
+    /// Ports to check, Steam Remote Play's by default
+    pub ports: Vec<PortSpec>,
+    /// Measure UDP probe RTTs with kernel software timestamps as well
+    pub kernel_timestamps: bool,
+    /// Run latency probes from a pinned, busy-polling thread instead of the async runtime
+    pub precise: bool,
+    /// In precise mode, also request SCHED_FIFO for the probe thread
+    pub realtime: bool,
+    /// Measure the client's timer wake-up latency before the network tests; off by default as it takes seconds
+    pub scheduling_test: bool,
+    /// How long the wake-up latency test runs
+    pub scheduling_test_secs: u64,
+    /// Account for other traffic on the interfaces before, during and after the tests
+    pub traffic_check: bool,
 }
 
 impl Default for TestConfig {
@@ -46,6 +241,70 @@ impl Default for TestConfig {
             latency_samples: 100,
             latency_interval_ms: 10,
             bandwidth_duration_secs: 10,
+            latency_warmup_samples: 5,
+            latency_outlier_threshold: None,
+            probe_schedule: ProbeSchedule::Sequential,
+            packet_size_sweep: true,
+            sweep_probes_per_size: 10,
+            mtu_discovery: true,
+            loss_test: true,
+            loss_probes: 1000,
+            loss_interval_ms: 2,
+            qos_test: true,
+            qos_probes_per_class: 20,
+            port_check: true,
+            ports: PortSpec::steam_remote_play(),
+            kernel_timestamps: false,
+            precise: false,
+            realtime: false,
+            scheduling_test: false,
+            scheduling_test_secs: 5,
+            traffic_check: true,
+        }
+    }
+}
+
+/// Analysis settings for report generation, sent along with the results so the host reports alike
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportConfig {
+    /// Length of each time window for windowed latency statistics
+    pub window_ms: f64,
+    /// Video frame rate used to estimate corrupted frames from packet loss
+    pub frame_rate: f64,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 1000.0,
+            frame_rate: 60.0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_port_list() {
+        let ports = PortSpec::parse_list("udp:27031-27036, TCP:27036-27037").unwrap();
+        assert_eq!(ports, PortSpec::steam_remote_play());
+        assert_eq!(PortSpec::parse_list("tcp:80").unwrap(), vec![PortSpec { transport: Transport::Tcp, port: 80 }]);
+        assert!(PortSpec::parse_list("27036").is_err());
+        assert!(PortSpec::parse_list("sctp:80").is_err());
+        assert!(PortSpec::parse_list("udp:27036-27031").is_err());
+        assert!(PortSpec::parse_list("udp:70000").is_err());
+    }
+
+    #[test]
+    fn test_missing_config_fields_take_defaults() {
+        let config: TestConfig = serde_json::from_str(r#"{ "latency_samples": 10 }"#).unwrap();
+        let defaults = TestConfig::default();
+        assert_eq!(config.latency_samples, 10);
+        assert_eq!(config.packet_size_sweep, defaults.packet_size_sweep);
+        assert_eq!(config.loss_probes, defaults.loss_probes);
+        assert_eq!(config.scheduling_test_secs, defaults.scheduling_test_secs);
+        assert_eq!(config.ports, defaults.ports);
+    }
+}
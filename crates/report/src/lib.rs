@@ -1,9 +1,21 @@
-use chequer_common::{Status, TestResults, LatencyResults};
+use chequer_common::{stats, ClockEventKind, PowerState, Status, TestResults, LatencyResults, BandwidthResults, LinkCapacityResults, MtuResults, QosResults, TrafficClassResult, PortCheckResults, NetworkEnvironment, InterfaceInfo, SystemEnvironment, InterfaceKind, PortFailure, PortState, Side, Transport, VideoResults, AudioResults, SystemResults};
 use serde::{Deserialize, Serialize};
 use crossterm::style::{Color, Stylize};
 
+mod loss;
+mod periodicity;
+mod pressure;
+mod traffic;
 mod visualization;
-use visualization::{sparkline, percentile, draw_box};
+mod windows;
+use visualization::{sparkline, histogram, draw_box};
+
+pub use loss::{LossPattern, LossRun};
+pub use periodicity::PeriodicSpikes;
+pub use pressure::PressureCorrelation;
+pub use traffic::ForeignTraffic;
+pub use windows::LatencyWindow;
+pub use chequer_common::ReportConfig;
 
 /// Diagnostic report with analyzed results
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,45 +23,291 @@ pub struct DiagnosticReport {
     pub overall_status: Status,
     pub latency_status: Option<Status>,
     pub bandwidth_status: Option<Status>,
+    /// Status of the link capacity estimate from the packet size sweep
+    #[serde(default)]
+    pub link_status: Option<Status>,
+    /// Status of the path MTU, `None` when it could not be measured with the Don't Fragment bit
+    #[serde(default)]
+    pub mtu_status: Option<Status>,
+    /// Status of the UDP loss stream, judged by the share of corrupted frames
+    #[serde(default)]
+    pub loss_status: Option<Status>,
+    /// Status of DSCP marking preservation, `None` when the host could not read the markings
+    #[serde(default)]
+    pub qos_status: Option<Status>,
+    /// Status of the port reachability check, `None` when no port could be tested
+    #[serde(default)]
+    pub ports_status: Option<Status>,
+    /// Status of the interfaces carrying the traffic on both sides
+    #[serde(default)]
+    pub network_status: Option<Status>,
+    /// Status of the client's timer wake-up latency, a local cause separate from the network
+    #[serde(default)]
+    pub system_status: Option<Status>,
+    /// Status of other traffic on either peer's interfaces while the tests ran
+    #[serde(default)]
+    pub traffic_status: Option<Status>,
+    /// Status of the client's power supply and temperatures while the tests ran
+    #[serde(default)]
+    pub power_status: Option<Status>,
     pub video_status: Option<Status>,
     pub audio_status: Option<Status>,
     pub recommendations: Vec<String>,
+    /// Latency spikes recurring at a fixed period, if any
+    #[serde(default)]
+    pub periodic_spikes: Option<PeriodicSpikes>,
+    /// Window length used for `latency_windows`
+    #[serde(default)]
+    pub window_ms: f64,
+    /// Latency statistics per time window
+    #[serde(default)]
+    pub latency_windows: Vec<LatencyWindow>,
+    /// Loss burst structure of the UDP loss stream
+    #[serde(default)]
+    pub loss_pattern: Option<LossPattern>,
+    /// Pressure sources on either peer that coincided with most latency spikes
+    #[serde(default)]
+    pub pressure_correlations: Vec<PressureCorrelation>,
+    /// Traffic on either peer's interfaces that was not Chequer's
+    #[serde(default)]
+    pub background_traffic: Vec<ForeignTraffic>,
     pub raw_results: TestResults,
 }
 
 impl DiagnosticReport {
     /// Generate report from test results
     pub fn from_results(results: TestResults) -> Self {
+        Self::from_results_with_config(results, &ReportConfig::default())
+    }
+
+    /// Generate report from test results with custom analysis settings
+    pub fn from_results_with_config(results: TestResults, config: &ReportConfig) -> Self {
         let mut recommendations = Vec::new();
         
         // Analyze latency
-        let latency_status = results.latency.as_ref().map(|lat| {
+        let mut latency_status = results.latency.as_ref().map(|lat| {
             analyze_latency(lat, &mut recommendations)
         });
 
-        // TODO: Analyze bandwidth, video, audio
-        let bandwidth_status = None;
-        let video_status = None;
-        let audio_status = None;
+        let periodic_spikes = results.latency.as_ref()
+            .and_then(periodicity::detect_periodic_spikes);
+        if let Some(periodic) = &periodic_spikes {
+            recommendations.push(format!(
+                "Latency spikes of +{:.1}ms repeat every {:.1}s, the signature of WiFi background scans or power-save wake-ups. Disable WiFi power saving (e.g. `iw dev wlan0 set power_save off`).",
+                periodic.amplitude_ms, periodic.period_ms / 1000.0
+            ));
+            latency_status = latency_status.map(|s| worst(s, Status::Yellow));
+        }
+
+        // A short burst of bad latency must not be averaged away.
+        // Windows shorter than the probe interval are widened to it.
+        let window_ms = results.latency.as_ref()
+            .map_or(config.window_ms, |lat| windows::clamp_window_ms(lat, config.window_ms));
+        let latency_windows = results.latency.as_ref()
+            .map(|lat| windows::window_series(lat, window_ms))
+            .unwrap_or_default();
+        let degraded: Vec<&LatencyWindow> = latency_windows.iter()
+            .filter(|w| w.is_degraded())
+            .collect();
+        if let Some(worst_window) = degraded.iter().max_by(|a, b| a.avg_ms.total_cmp(&b.avg_ms)) {
+            recommendations.push(format!(
+                "{} of {} {:.1}s windows were degraded; the worst at {:.1}s averaged {:.1}ms. Look for interference or background traffic at that time.",
+                degraded.len(), latency_windows.len(), window_ms / 1000.0,
+                worst_window.start_ms / 1000.0, worst_window.avg_ms
+            ));
+            let window_status = degraded.iter().map(|w| w.status).fold(Status::Green, worst);
+            latency_status = latency_status.map(|s| worst(s, window_status));
+        }
+
+        // Retransmissions on the control connection surface as spikes of about one RTO
+        if let Some(tcp) = results.latency.as_ref().and_then(|lat| lat.tcp_info.as_ref()) {
+            let retransmits = tcp.retransmits();
+            if retransmits > 0 {
+                recommendations.push(format!(
+                    "The kernel retransmitted {} segment{} during the latency test (RTO {:.0}ms). Spikes near the RTO are packet loss on the path, not queueing.",
+                    retransmits, if retransmits == 1 { "" } else { "s" }, tcp.after.rto_ms
+                ));
+                latency_status = latency_status.map(|s| worst(s, Status::Yellow));
+            }
+        }
+        if let Some(tcp) = results.host.as_ref().and_then(|host| host.tcp_info.as_ref()) {
+            let retransmits = tcp.retransmits();
+            if retransmits > 0 {
+                recommendations.push(format!(
+                    "The host retransmitted {} segment{} to the client during the tests. Loss on the host-to-client direction is what the video stream takes.",
+                    retransmits, if retransmits == 1 { "" } else { "s" }
+                ));
+                latency_status = latency_status.map(|s| worst(s, Status::Yellow));
+            }
+        }
+
+        // Samples across a suspend or clock step are already excluded; say why
+        let host_clock_events = results.host.iter().flat_map(|host| &host.clock_events);
+        let clock_events = results.client_clock_events.iter().map(|event| (Side::Client, event))
+            .chain(host_clock_events.map(|event| (Side::Host, event)));
+        for (side, event) in clock_events {
+            recommendations.push(match event.kind {
+                ClockEventKind::Suspend => format!(
+                    "The {} was suspended for {:.1}s during the tests; latency samples across it were excluded. Keep it awake while testing.",
+                    side, event.magnitude_ms / 1000.0
+                ),
+                ClockEventKind::ClockStep => format!(
+                    "The {}'s wall clock was stepped by {:+.1}s during the tests (NTP or a manual change); latency samples across it were excluded.",
+                    side, event.magnitude_ms / 1000.0
+                ),
+            });
+        }
+
+        // Probe RTTs measured in the application include local scheduling; the kernel's do not
+        let mut local_delay = false;
+        if let Some(kernel) = &results.kernel_rtt {
+            let overhead = kernel.stack_overhead_ms();
+            let overhead_p95 = stats::percentile(&overhead, 95.0);
+            if overhead_p95 > 1.0 {
+                recommendations.push(format!(
+                    "Local stack overhead reaches {:.2}ms at P95 against a kernel RTT of {:.2}ms, so scheduling on this device inflates application-level latency. Close background apps or use a performance power profile.",
+                    overhead_p95, stats::percentile(&kernel.kernel_rtt_ms, 50.0)
+                ));
+                local_delay = true;
+            }
+        }
+
+        let bandwidth_status = results.bandwidth.as_ref().map(|bw| {
+            analyze_bandwidth(bw, &mut recommendations)
+        });
+
+        let link_status = results.link_capacity.as_ref().and_then(|link| {
+            analyze_link(link, &mut recommendations)
+        });
+
+        let mtu_status = results.mtu.as_ref().and_then(|mtu| {
+            analyze_mtu(mtu, &mut recommendations)
+        });
+
+        let loss_pattern = results.loss.as_ref()
+            .and_then(|loss| loss::loss_pattern(loss, config.frame_rate));
+        let loss_status = loss_pattern.as_ref().map(|pattern| {
+            analyze_loss(pattern, &mut recommendations)
+        });
+
+        let qos_status = results.qos.as_ref().and_then(|qos| {
+            analyze_qos(qos, &mut recommendations)
+        });
+
+        let ports_status = results.ports.as_ref().and_then(|ports| {
+            analyze_ports(ports, &mut recommendations)
+        });
+
+        let host_network = results.host.as_ref().map(|host| &host.network);
+        let network_status = [(Side::Client, results.client_network.as_ref()), (Side::Host, host_network)]
+            .into_iter()
+            .filter_map(|(side, env)| analyze_network(side, env?, &mut recommendations))
+            .reduce(worst);
+
+        let system_status = results.system.as_ref().map(|system| {
+            analyze_system(system, &mut recommendations)
+        });
+        local_delay |= system_status.is_some_and(|s| s != Status::Green);
+
+        // Handhelds clock down on battery and when hot, which shows up as a slower stream
+        let wifi_power_save = results.client_network.as_ref()
+            .and_then(|network| network.active()?.wifi.as_ref()?.power_save)
+            .unwrap_or(false);
+        let power_status = analyze_power(&client_power_states(&results), wifi_power_save, &mut recommendations);
+
+        // Spikes that line up with stalls on a peer are a local problem, not the network's
+        let host_pressure = results.host.as_ref().and_then(|host| host.pressure.as_ref());
+        let pressure_correlations: Vec<PressureCorrelation> = match &results.latency {
+            Some(lat) => [(Side::Client, results.client_pressure.as_ref()), (Side::Host, host_pressure)]
+                .into_iter()
+                .filter_map(|(side, pressure)| Some(pressure::correlate_spikes(lat, side, pressure?)))
+                .flatten()
+                .collect(),
+            None => Vec::new(),
+        };
+        for correlation in &pressure_correlations {
+            let fix = match correlation.resource.as_str() {
+                "CPU" => "Close CPU-heavy background work there, such as shader compilation or game updates",
+                "memory" => "That machine is reclaiming or swapping memory; close programs to free RAM",
+                _ => "Disk activity there, such as downloads or updates, is stalling the streaming processes; pause it",
+            };
+            recommendations.push(format!(
+                "{} of {} latency spikes coincided with {} {} pressure. {}.",
+                correlation.coinciding, correlation.spikes, correlation.side, correlation.resource, fix
+            ));
+        }
+        local_delay |= !pressure_correlations.is_empty();
+
+        // Said once for all of the findings above
+        if local_delay {
+            recommendations.push("Part of the measured delay comes from the devices themselves, not the network; fix the local causes above before changing the network.".to_string());
+        }
+
+        // Other traffic on the network competes with the probes and skews every test
+        let client_traffic = results.client_traffic.as_ref().map(|traffic| (traffic, results.client_network.as_ref()));
+        let host_traffic = results.host.as_ref().and_then(|host| Some((host.traffic.as_ref()?, Some(&host.network))));
+        let traffic_sides = [(Side::Client, client_traffic), (Side::Host, host_traffic)];
+        let traffic_measured = traffic_sides.iter().any(|(_, side)| side.is_some());
+        let background_traffic: Vec<ForeignTraffic> = traffic_sides.into_iter()
+            .filter_map(|(side, traffic)| {
+                let (traffic, network) = traffic?;
+                let active = network.and_then(|network| network.active_interface.as_deref());
+                Some(traffic::foreign_traffic(side, traffic, active))
+            })
+            .flatten()
+            .collect();
+        let busy: Vec<&ForeignTraffic> = background_traffic.iter().filter(|f| f.is_busy()).collect();
+        for foreign in &busy {
+            recommendations.push(format!(
+                "The {}'s {} carried {} of other traffic, up to {:.1} Mbit/s {}. Pause downloads, streams and backups on that network, then test again.",
+                foreign.side, foreign.interface, traffic::format_bytes(foreign.rx_bytes + foreign.tx_bytes),
+                foreign.peak_mbps, phase_label(&foreign.peak_phase)
+            ));
+        }
+        let traffic_status = traffic_measured.then_some(if busy.is_empty() { Status::Green } else { Status::Yellow });
+
+        let mut video_status = results.video.as_ref().map(|video| {
+            analyze_video(video, &mut recommendations)
+        });
+        let host_video = results.host.as_ref().and_then(|host| host.video.as_ref());
+        if let (Some(client), Some(host)) = (&results.video, host_video) {
+            if let Some(status) = analyze_codec_pairing(client, host, &mut recommendations) {
+                video_status = video_status.map(|s| worst(s, status));
+            }
+        }
+
+        let audio_status = results.audio.as_ref().map(|audio| {
+            analyze_audio(audio, &mut recommendations)
+        });
 
         // Determine overall status (worst of all tests)
-        let overall_status = [latency_status, bandwidth_status, video_status, audio_status]
+        let overall_status = [latency_status, bandwidth_status, link_status, mtu_status, loss_status, qos_status, ports_status, network_status, system_status, traffic_status, power_status, video_status, audio_status]
             .iter()
             .filter_map(|s| *s)
-            .max_by_key(|s| match s {
-                Status::Red => 3,
-                Status::Yellow => 2,
-                Status::Green => 1,
-            })
-            .unwrap_or(Status::Green);
+            .fold(Status::Green, worst);
 
         Self {
             overall_status,
             latency_status,
             bandwidth_status,
+            link_status,
+            mtu_status,
+            loss_status,
+            qos_status,
+            ports_status,
+            network_status,
+            system_status,
+            traffic_status,
+            power_status,
             video_status,
             audio_status,
             recommendations,
+            periodic_spikes,
+            window_ms,
+            latency_windows,
+            loss_pattern,
+            pressure_correlations,
+            background_traffic,
             raw_results: results,
         }
     }
@@ -77,28 +335,40 @@ impl DiagnosticReport {
         content.push(format!("{} Overall Status: {}", status_emoji, status_text));
         content.push(String::new());
 
+        // What each side was measured on
+        let host = self.raw_results.host.as_ref();
+        let environments = [
+            (Side::Client, self.raw_results.client_environment.as_ref().map(describe_environment)),
+            (Side::Host, host.map(|host| {
+                let mut lines = vec![format!("{} (chequer {})", host.info.name, host.info.version)];
+                lines.extend(describe_environment(&host.environment));
+                lines
+            })),
+        ];
+        if environments.iter().any(|(_, lines)| lines.is_some()) {
+            content.push("Measured On".bold().to_string());
+            content.push(String::new());
+            for (side, lines) in environments {
+                let Some(lines) = lines else {
+                    content.push(format!("  {:<8} not reported", format!("{}:", side.title())));
+                    continue;
+                };
+                for (i, line) in lines.into_iter().enumerate() {
+                    let label = if i == 0 { format!("{}:", side.title()) } else { String::new() };
+                    content.push(format!("  {:<8} {}", label, line));
+                }
+            }
+            content.push(String::new());
+        }
+
         // Latency section with visualization
         if let Some(lat) = &self.raw_results.latency {
             let status = self.latency_status.unwrap_or(Status::Green);
-            let status_emoji = match status {
-                Status::Green => "🟢",
-                Status::Yellow => "🟡",
-                Status::Red => "🔴",
-            };
-            
-            let status_text = format!("{:?}", status)
-                .with(match status {
-                    Status::Green => Color::Green,
-                    Status::Yellow => Color::Yellow,
-                    Status::Red => Color::Red,
-                });
-            content.push(format!("{} Network Latency: {}", status_emoji, status_text));
+            content.push(section_header("Network Latency", status));
             content.push(String::new());
 
             // Create sparkline visualization
-            let spark = sparkline(&lat.samples, 48);
-            let p50 = percentile(&lat.samples, 50.0);
-            let p95 = percentile(&lat.samples, 95.0);
+            let spark = sparkline(lat.measured_samples(), 48);
             
             // Draw chart with proper alignment
             content.push(format!("  {:>6.1} ┤{}", lat.min_ms, spark));
@@ -119,13 +389,383 @@ impl DiagnosticReport {
                 lat.min_ms, lat.max_ms, lat.avg_ms
             ));
             content.push(format!(
-                "  P50: {:>6.2}ms │ P95: {:>6.2}ms │ Jitter: {:>5.2}ms",
-                p50, p95, lat.jitter_ms
+                "  P50: {:>6.2}ms │ P95: {:>6.2}ms │ P99: {:>6.2}ms",
+                lat.p50_ms, lat.p95_ms, lat.p99_ms
+            ));
+            content.push(format!(
+                "  P99.9: {:>6.2}ms │ MAD: {:>6.2}ms │ IQR: {:>6.2}ms",
+                lat.p999_ms, lat.mad_ms, lat.iqr_ms
+            ));
+            content.push(format!(
+                "  Jitter (RFC 3550): {:>5.2}ms {} │ RTT StdDev: {:>5.2}ms",
+                lat.rfc3550_jitter_ms, "◀ status".with(Color::Cyan), lat.jitter_ms
+            ));
+            content.push(format!(
+                "  IPDV mean: {:>5.2}ms │ IPDV P99: {:>5.2}ms │ PDV P99: {:>5.2}ms",
+                lat.ipdv_mean_ms, lat.ipdv_p99_ms, lat.pdv_p99_ms
+            ));
+            content.push(format!(
+                "  Spikes: {:>3} (>{:.2}ms) │ Streak: {:>3}",
+                lat.spike_count, lat.spike_threshold_ms, lat.longest_spike_streak
+            ));
+            content.push(format!(
+                "  Samples: {:>3} │ Warm-up: {:>2} │ Outliers: {:>2} │ Loss: {:>4.1}%",
+                lat.measured_samples().len(), lat.warmup_samples,
+                lat.outlier_indices.len(), lat.packet_loss_percent
+            ));
+            if !lat.invalid_indices.is_empty() {
+                content.push(format!(
+                    "  {} {} samples across a suspend or clock step excluded",
+                    "Invalid:".with(Color::Yellow), lat.invalid_indices.len()
+                ));
+            }
+            content.push(format!(
+                "  Schedule: {} │ Rate: {:.1} Hz",
+                lat.schedule, lat.probe_rate_hz()
+            ));
+            if let Some(precise) = &lat.precise {
+                let cpu = precise.pinned_cpu
+                    .map(|cpu| format!("CPU {}", cpu))
+                    .unwrap_or_else(|| "unpinned".to_string());
+                let priority = precise.fifo_priority
+                    .map(|priority| format!("SCHED_FIFO {}", priority))
+                    .unwrap_or_else(|| "normal priority".to_string());
+                content.push(format!("  Precise: busy-poll │ {} │ {}", cpu, priority));
+                for denied in &precise.denied {
+                    content.push(format!("  {} {}", "Not granted:".with(Color::Yellow), denied));
+                }
+            }
+            if let Some(tcp) = &lat.tcp_info {
+                content.push(format!(
+                    "  Kernel sRTT: {:>5.2}ms ± {:.2}ms │ Retransmits: {} │ cwnd: {}",
+                    tcp.after.rtt_ms, tcp.after.rttvar_ms, tcp.retransmits(), tcp.after.snd_cwnd
+                ));
+            }
+            if let Some(periodic) = &self.periodic_spikes {
+                content.push(format!(
+                    "  {} +{:.1}ms every {:.2}s ({} spikes)",
+                    "Periodic spikes:".with(Color::Yellow),
+                    periodic.amplitude_ms, periodic.period_ms / 1000.0, periodic.spike_count
+                ));
+            }
+            for correlation in &self.pressure_correlations {
+                content.push(format!(
+                    "  {} {} of {} spikes during {} {} pressure",
+                    "Stalls:".with(Color::Yellow),
+                    correlation.coinciding, correlation.spikes, correlation.side, correlation.resource
+                ));
+            }
+            content.push(String::new());
+
+            // Time axis with degraded windows marked
+            if !self.latency_windows.is_empty() {
+                content.push(format!(
+                    "  {}",
+                    format!("Timeline ({:.1}s windows):", self.window_ms / 1000.0).with(Color::Cyan)
+                ));
+                content.extend(window_chart(&self.latency_windows, self.window_ms, 48));
+                content.push(String::new());
+            }
+
+            // Distribution of round-trip times
+            content.push(format!("  {}", "Distribution:".with(Color::Cyan)));
+            for bar in histogram(&lat.filtered_samples(), 8, 36) {
+                content.push(format!("  {}", bar));
+            }
+            content.push(String::new());
+        }
+
+        // Kernel timestamp section
+        if let Some(kernel) = &self.raw_results.kernel_rtt {
+            let overhead = kernel.stack_overhead_ms();
+            content.push(section_header("Kernel Timestamps", Status::Green));
+            content.push(String::new());
+            content.push(format!("  {:<22} {:>9} {:>9} {:>9}", "", "P50", "P95", "P99"));
+            for (label, data) in [
+                ("Application RTT", &kernel.app_rtt_ms),
+                ("Kernel RTT", &kernel.kernel_rtt_ms),
+                ("Local stack overhead", &overhead),
+            ] {
+                content.push(format!(
+                    "  {:<22} {:>7.3}ms {:>7.3}ms {:>7.3}ms",
+                    label, stats::percentile(data, 50.0), stats::percentile(data, 95.0), stats::percentile(data, 99.0)
+                ));
+            }
+            content.push(format!("  Timestamped: {} of {} probes", kernel.kernel_rtt_ms.len(), kernel.sent));
+            content.push(String::new());
+        }
+
+        // Bandwidth section
+        if let Some(bw) = &self.raw_results.bandwidth {
+            let status = self.bandwidth_status.unwrap_or(Status::Green);
+            content.push(section_header("Bandwidth", status));
+            content.push(String::new());
+            content.push(format!(
+                "  Download: {:>7.1} Mbps │ Upload: {:>7.1} Mbps",
+                bw.download_mbps, bw.upload_mbps
+            ));
+            content.push(String::new());
+        }
+
+        // Link capacity section
+        if let Some(link) = &self.raw_results.link_capacity {
+            let status = self.link_status.unwrap_or(Status::Green);
+            content.push(section_header("Link Capacity", status));
+            content.push(String::new());
+            let mbps = |v: Option<f64>| v
+                .map(|v| format!("{:.1} Mbps", v))
+                .unwrap_or_else(|| "n/a".to_string());
+            content.push(format!(
+                "  Size fit: {} │ Packet pair: {}",
+                mbps(link.estimated_capacity_mbps), mbps(link.packet_pair_capacity_mbps)
+            ));
+            content.push(format!(
+                "  Base RTT: {:.2}ms │ {:.3}us/byte",
+                link.base_rtt_ms, link.per_byte_us
+            ));
+            content.push(String::new());
+        }
+
+        // Path MTU section
+        if let Some(mtu) = &self.raw_results.mtu {
+            let status = self.mtu_status.unwrap_or(Status::Green);
+            content.push(section_header("Path MTU", status));
+            content.push(String::new());
+            let df = if mtu.dont_fragment { "DF set" } else { "DF not set" };
+            content.push(format!(
+                "  Path MTU: {} bytes │ Max UDP payload: {} bytes ({})",
+                mtu.path_mtu(), mtu.max_payload_bytes, df
+            ));
+            if let Some(delivered) = mtu.fragmented_delivery {
+                let outcome = if delivered { "delivered" } else { "dropped" };
+                content.push(format!("  Fragmented 1500 byte datagrams: {}", outcome));
+            }
+            content.push(String::new());
+        }
+
+        // Packet loss section
+        if let (Some(loss), Some(pattern)) = (&self.raw_results.loss, &self.loss_pattern) {
+            let status = self.loss_status.unwrap_or(Status::Green);
+            content.push(section_header("Packet Loss", status));
+            content.push(String::new());
+            content.push(format!(
+                "  Loss: {:.2}% ({}/{}) │ Mean burst: {:.1} packets",
+                pattern.loss_percent, loss.lost_seqs.len(), loss.sent, pattern.mean_burst_length
+            ));
+            content.push(format!(
+                "  Gilbert model: p(good→bad) {:.4} │ p(bad→good) {:.4}",
+                pattern.p_good_to_bad, pattern.p_bad_to_good
+            ));
+            if !pattern.runs.is_empty() {
+                let runs: Vec<String> = pattern.runs.iter()
+                    .map(|run| format!("{}×{}", run.length, run.count))
+                    .collect();
+                content.push(format!("  Loss runs (length×count): {}", runs.join(", ")));
+            }
+            content.push(format!(
+                "  Corrupted frames: {} of {} at {:.0} fps ({:.1}%)",
+                pattern.corrupted_frames, pattern.frames, pattern.frame_rate,
+                pattern.corrupted_frame_percent()
             ));
+            content.push(String::new());
+        }
+
+        // QoS marking section
+        if let Some(qos) = &self.raw_results.qos {
+            let status = self.qos_status.unwrap_or(Status::Green);
+            content.push(section_header("QoS Marking", status));
+            content.push(String::new());
             content.push(format!(
-                "  Samples: {:>3} │ Loss: {:>4.1}%",
-                lat.samples.len(), lat.packet_loss_percent
+                "  {:<6} {:>7} {:>10} {:>10} {:>8}",
+                "Class", "Sent as", "Received", "Avg RTT", "Loss"
             ));
+            for class in &qos.classes {
+                let received = match (class.received_dscp, class.preserved()) {
+                    (Some(dscp), Some(true)) => format!("{} ✓", dscp),
+                    (Some(dscp), _) => format!("{} ✗", dscp),
+                    (None, _) => "?".to_string(),
+                };
+                let rtt = class.avg_rtt_ms
+                    .map(|ms| format!("{:.2}ms", ms))
+                    .unwrap_or_else(|| "n/a".to_string());
+                content.push(format!(
+                    "  {:<6} {:>7} {:>10} {:>10} {:>7.1}%",
+                    class.name, class.dscp, received, rtt, class.loss_percent()
+                ));
+            }
+            content.push(String::new());
+        }
+
+        // Port reachability section
+        if let Some(ports) = &self.raw_results.ports {
+            let status = self.ports_status.unwrap_or(Status::Green);
+            content.push(section_header("Port Reachability", status));
+            content.push(String::new());
+            content.push(format!("  {:<10} {:<12} {}", "Port", "Host", "Reachable"));
+            for result in &ports.ports {
+                let host_state = match &result.host_state {
+                    PortState::Listening => "listening",
+                    PortState::InUse => "in use",
+                    PortState::Unavailable(_) => "unavailable",
+                };
+                let reachable = match (result.reachable, &result.host_state) {
+                    (Some(true), PortState::InUse) => "✓ (passive)".to_string(),
+                    (Some(true), _) => "✓".to_string(),
+                    (Some(false), _) => format!("✗ {}", result.failure.as_ref().map(ToString::to_string).unwrap_or_default()),
+                    (None, _) => "?".to_string(),
+                };
+                content.push(format!(
+                    "  {:<10} {:<12} {}",
+                    result.port.to_string(), host_state, reachable
+                ));
+            }
+            content.push(String::new());
+        }
+
+        // Network interfaces section
+        let sides = [(Side::Client, self.raw_results.client_network.as_ref()), (Side::Host, host.map(|host| &host.network))];
+        if sides.iter().any(|(_, env)| env.is_some()) {
+            let status = self.network_status.unwrap_or(Status::Green);
+            content.push(section_header("Network Interfaces", status));
+            content.push(String::new());
+            for (side, env) in sides {
+                let description = match env.map(|env| (env, env.active())) {
+                    Some((_, Some(iface))) => describe_interface(iface),
+                    Some((env, None)) => format!("unknown ({} interfaces)", env.interfaces.len()),
+                    None => "not reported".to_string(),
+                };
+                content.push(format!("  {:<8} {}", format!("{}:", side.title()), description));
+            }
+            content.push(String::new());
+        }
+
+        // Background traffic section
+        if let Some(status) = self.traffic_status {
+            content.push(section_header("Background Traffic", status));
+            content.push(String::new());
+            if self.background_traffic.is_empty() {
+                content.push("  No other traffic on either side".to_string());
+            }
+            for foreign in &self.background_traffic {
+                content.push(format!(
+                    "  {:<8} {} ↓{} ↑{} │ Peak {:.1} Mbit/s ({})",
+                    format!("{}:", foreign.side.title()), foreign.interface, traffic::format_bytes(foreign.rx_bytes),
+                    traffic::format_bytes(foreign.tx_bytes), foreign.peak_mbps, foreign.peak_phase
+                ));
+            }
+            content.push(String::new());
+        }
+
+        // System scheduling section
+        if let Some(system) = &self.raw_results.system {
+            let status = self.system_status.unwrap_or(Status::Green);
+            content.push(section_header("System Scheduling", status));
+            content.push(String::new());
+            content.push(format!(
+                "  Wake-up latency: Avg {:>6.0}us │ P99 {:>6.0}us │ Max {:>6.0}us",
+                system.avg_us, system.p99_us, system.max_us
+            ));
+            content.push(format!(
+                "  P50 {:>6.0}us │ P99.9 {:>6.0}us │ {} wake-ups every {:.0}us",
+                system.p50_us, system.p999_us, system.samples, system.interval_us
+            ));
+            let largest = system.histogram.iter().map(|b| b.count).max().unwrap_or(0).max(1);
+            for (i, bucket) in system.histogram.iter().enumerate().filter(|(_, b)| b.count > 0) {
+                let label = match (bucket.upper_us, i.checked_sub(1).and_then(|j| system.histogram[j].upper_us)) {
+                    (Some(upper), _) => format!("≤{:.0}us", upper),
+                    (None, Some(lower)) => format!(">{:.0}us", lower),
+                    (None, None) => "all".to_string(),
+                };
+                let width = (bucket.count as f64 / largest as f64 * 30.0).ceil() as usize;
+                content.push(format!("  {:>9} │{} {}", label, "█".repeat(width), bucket.count));
+            }
+            content.push(String::new());
+        }
+
+        // Power and thermal section
+        if let Some(status) = self.power_status {
+            let states = client_power_states(&self.raw_results);
+            content.push(section_header("Power & Thermal", status));
+            content.push(String::new());
+            content.push(format!("  Supply:    {}", describe_supply(&states)));
+            let clocks: Vec<f64> = states.iter().filter_map(|state| state.cpu_freq_mhz).collect();
+            if !clocks.is_empty() {
+                let max = states.iter().find_map(|state| state.cpu_max_freq_mhz)
+                    .map(|mhz| format!(" of {:.1} GHz", mhz / 1000.0))
+                    .unwrap_or_default();
+                content.push(format!(
+                    "  CPU clock: {:.1} GHz avg ({:.1}-{:.1} GHz){}",
+                    stats::mean(&clocks) / 1000.0,
+                    clocks.iter().cloned().fold(f64::INFINITY, f64::min) / 1000.0,
+                    clocks.iter().cloned().fold(0.0, f64::max) / 1000.0,
+                    max
+                ));
+            }
+            let hottest = states.iter()
+                .filter_map(|state| state.hottest())
+                .max_by(|a, b| a.celsius.total_cmp(&b.celsius));
+            if let Some(temperature) = hottest {
+                let limit = temperature.throttle_celsius
+                    .map(|limit| format!(" (throttles at {:.0}°C)", limit))
+                    .unwrap_or_default();
+                content.push(format!("  Hottest:   {} {:.0}°C{}", temperature.sensor, temperature.celsius, limit));
+            }
+            content.push(String::new());
+        }
+
+        // Video section
+        if let Some(video) = &self.raw_results.video {
+            let status = self.video_status.unwrap_or(Status::Green);
+            content.push(section_header("Video Decode", status));
+            content.push(String::new());
+            let codecs = if video.supported_codecs.is_empty() {
+                "none".to_string()
+            } else {
+                video.supported_codecs.join(", ")
+            };
+            let decode = video.decode_fps
+                .map(|fps| format!("{:.1} fps", fps))
+                .unwrap_or_else(|| "n/a".to_string());
+            content.push(format!("  Codecs: {} │ Decode: {}", codecs, decode));
+            let sizes: Vec<String> = video.supported_codecs.iter()
+                .filter_map(|codec| {
+                    let (width, height) = video.profiles.iter()
+                        .filter(|p| !p.encode && &p.codec == codec)
+                        .filter_map(|p| p.max_width.zip(p.max_height))
+                        .max()?;
+                    Some(format!("{} ≤{}x{}", codec_label(codec), width, height))
+                })
+                .collect();
+            if !sizes.is_empty() {
+                content.push(format!("  Max size: {}", sizes.join(" │ ")));
+            }
+            if let Some(driver) = &video.driver {
+                // Drop the build details Mesa appends in parentheses
+                content.push(format!("  Driver: {}", driver.split(" (").next().unwrap_or(driver)));
+            }
+            if let Some(host) = self.raw_results.host.as_ref().and_then(|host| host.video.as_ref()) {
+                let encoders: Vec<&str> = host.encode_codecs().into_iter().map(codec_label).collect();
+                let encoders = if encoders.is_empty() { "none through VA-API".to_string() } else { encoders.join(", ") };
+                content.push(format!("  Host encode: {}", encoders));
+            }
+            content.push(String::new());
+        }
+
+        // Audio section
+        if let Some(audio) = &self.raw_results.audio {
+            let status = self.audio_status.unwrap_or(Status::Green);
+            content.push(section_header("Audio Output", status));
+            content.push(String::new());
+            let rate = audio.sample_rate
+                .map(|rate| format!("{} Hz", rate))
+                .unwrap_or_else(|| "n/a".to_string());
+            content.push(format!(
+                "  Devices: {:>2} │ Sample rate: {}",
+                audio.output_devices.len(), rate
+            ));
+            for device in &audio.output_devices {
+                content.push(format!("    • {}", device));
+            }
             content.push(String::new());
         }
 
@@ -148,18 +788,674 @@ impl DiagnosticReport {
     }
 }
 
+/// Render one column per window, colored by status, with degraded windows marked below
+///
+/// Long runs merge neighbouring windows into one column, keeping the worst of each group.
+fn window_chart(windows: &[LatencyWindow], window_ms: f64, width: usize) -> Vec<String> {
+    let per_column = windows.len().div_ceil(width);
+    let columns: Vec<(f64, Status, bool)> = windows
+        .chunks(per_column)
+        .map(|group| (
+            group.iter().map(|w| w.avg_ms).fold(0.0, f64::max),
+            group.iter().map(|w| w.status).fold(Status::Green, worst),
+            group.iter().any(|w| w.is_degraded()),
+        ))
+        .collect();
+
+    let averages: Vec<f64> = columns.iter().map(|c| c.0).collect();
+    let mut chart = String::from("  ");
+    let mut markers = String::from("  ");
+    for (bar, &(_, status, degraded)) in sparkline(&averages, width).chars().zip(&columns) {
+        chart.push_str(&bar.to_string().with(status_color(status)).to_string());
+        markers.push(if degraded { '▲' } else { '─' });
+    }
+
+    let end_label = format!("{:.0}s", windows.len() as f64 * window_ms / 1000.0);
+    let gap = columns.len().saturating_sub(2 + end_label.len());
+    let axis = format!("  0s{}{}", " ".repeat(gap), end_label);
+
+    vec![chart, markers, axis]
+}
+
+fn status_color(status: Status) -> Color {
+    match status {
+        Status::Green => Color::Green,
+        Status::Yellow => Color::Yellow,
+        Status::Red => Color::Red,
+    }
+}
+
+/// Format a section heading with its traffic light
+fn section_header(label: &str, status: Status) -> String {
+    let status_emoji = match status {
+        Status::Green => "🟢",
+        Status::Yellow => "🟡",
+        Status::Red => "🔴",
+    };
+
+    let status_text = format!("{:?}", status).with(status_color(status));
+    format!("{} {}: {}", status_emoji, label, status_text)
+}
+
+/// Average RTT above which latency is red
+const LATENCY_RED_MS: f64 = 50.0;
+
+/// Average RTT above which latency is yellow
+const LATENCY_YELLOW_MS: f64 = 20.0;
+
+/// RFC 3550 jitter above which latency is yellow
+const JITTER_YELLOW_MS: f64 = 10.0;
+
+/// P99 RTT above which spikes make latency yellow
+pub(crate) const P99_YELLOW_MS: f64 = 50.0;
+
+/// Status of a set of RTTs from its average and jitter, for the whole run as for each window of it
+pub(crate) fn latency_status(avg_ms: f64, jitter_ms: f64) -> Status {
+    if avg_ms > LATENCY_RED_MS {
+        Status::Red
+    } else if avg_ms > LATENCY_YELLOW_MS || jitter_ms > JITTER_YELLOW_MS {
+        Status::Yellow
+    } else {
+        Status::Green
+    }
+}
+
+/// Latency status is driven by the average RTT, RFC 3550 jitter and the P99 tail
 fn analyze_latency(lat: &LatencyResults, recommendations: &mut Vec<String>) -> Status {
-    if lat.avg_ms > 50.0 {
+    let tail = if lat.p99_ms > P99_YELLOW_MS { Status::Yellow } else { Status::Green };
+    let status = worst(latency_status(lat.avg_ms, lat.rfc3550_jitter_ms), tail);
+    if status == Status::Red {
         recommendations.push("High latency detected. Check network congestion.".to_string());
         recommendations.push("Consider using wired connection instead of WiFi.".to_string());
-        Status::Red
-    } else if lat.avg_ms > 20.0 || lat.jitter_ms > 10.0 {
+    } else if lat.avg_ms > LATENCY_YELLOW_MS || lat.rfc3550_jitter_ms > JITTER_YELLOW_MS {
         recommendations.push("Moderate latency or jitter detected.".to_string());
-        if lat.jitter_ms > 10.0 {
-            recommendations.push("Enable QoS on your router for smoother streaming.".to_string());
+        if lat.rfc3550_jitter_ms > JITTER_YELLOW_MS {
+            recommendations.push(format!(
+                "RFC 3550 jitter is {:.1}ms. Enable QoS on your router for smoother streaming.",
+                lat.rfc3550_jitter_ms
+            ));
         }
+    } else if status == Status::Yellow {
+        recommendations.push(format!(
+            "Latency spikes reach {:.1}ms at P99 ({} spikes above {:.1}ms). Expect occasional stutter while streaming.",
+            lat.p99_ms, lat.spike_count, lat.spike_threshold_ms
+        ));
+    }
+    status
+}
+
+fn analyze_bandwidth(bw: &BandwidthResults, recommendations: &mut Vec<String>) -> Status {
+    let mut status = if bw.download_mbps < 15.0 {
+        recommendations.push(format!(
+            "Download bandwidth is only {:.1} Mbps. Remote Play needs at least 15 Mbps; lower the stream resolution or bitrate.",
+            bw.download_mbps
+        ));
+        Status::Red
+    } else if bw.download_mbps < 30.0 {
+        recommendations.push(format!(
+            "Download bandwidth of {:.1} Mbps limits 1080p60 streaming. Set the bandwidth limit in Steam to Automatic or below 30 Mbps.",
+            bw.download_mbps
+        ));
+        Status::Yellow
+    } else {
+        Status::Green
+    };
+
+    if bw.upload_mbps < 1.0 {
+        recommendations.push(format!(
+            "Upload bandwidth of {:.1} Mbps may delay controller input and voice chat.",
+            bw.upload_mbps
+        ));
+        status = worst(status, Status::Yellow);
+    }
+
+    status
+}
+
+fn analyze_link(link: &LinkCapacityResults, recommendations: &mut Vec<String>) -> Option<Status> {
+    let capacity = link.capacity_mbps()?;
+
+    let status = if capacity < 15.0 {
+        recommendations.push(format!(
+            "Link capacity is only about {:.1} Mbps. This is typical of 2.4 GHz WiFi at a low PHY rate; move closer to the access point or switch to 5 GHz or Ethernet.",
+            capacity
+        ));
+        Status::Red
+    } else if capacity < 30.0 {
+        recommendations.push(format!(
+            "Link capacity of about {:.1} Mbps leaves little headroom for 1080p60 streaming. Prefer 5 GHz WiFi or Ethernet.",
+            capacity
+        ));
         Status::Yellow
     } else {
         Status::Green
+    };
+
+    Some(status)
+}
+
+fn analyze_mtu(mtu: &MtuResults, recommendations: &mut Vec<String>) -> Option<Status> {
+    // Without DF the search measures what survives fragmentation, not the path MTU
+    if !mtu.dont_fragment {
+        return None;
     }
+
+    let path_mtu = mtu.path_mtu();
+    let status = if path_mtu >= 1500 {
+        Status::Green
+    } else if mtu.fragmented_delivery == Some(false) {
+        recommendations.push(format!(
+            "Path MTU is only {} bytes and full-size packets are dropped instead of fragmented. Large Remote Play packets will be black-holed; lower the MTU to {} on the host or fix the VPN/PPPoE hop.",
+            path_mtu, path_mtu
+        ));
+        Status::Red
+    } else {
+        recommendations.push(format!(
+            "Path MTU is {} bytes, below the standard 1500. Large Remote Play packets will be fragmented, usually by a VPN or PPPoE hop; avoid the tunnel or lower the MTU to {} on the host.",
+            path_mtu, path_mtu
+        ));
+        Status::Yellow
+    };
+
+    Some(status)
+}
+
+fn analyze_loss(pattern: &LossPattern, recommendations: &mut Vec<String>) -> Status {
+    let corrupted = pattern.corrupted_frame_percent();
+    let status = if corrupted >= 5.0 {
+        Status::Red
+    } else if corrupted >= 1.0 {
+        Status::Yellow
+    } else {
+        return Status::Green;
+    };
+
+    // Bursts wipe out whole frames, scattered losses are what error correction handles
+    if pattern.mean_burst_length >= 2.0 {
+        recommendations.push(format!(
+            "Packets are lost in bursts of {:.1} on average, corrupting {:.1}% of frames at {:.0} fps. Bursts point to WiFi interference or roaming; use 5 GHz, a fixed channel or Ethernet.",
+            pattern.mean_burst_length, corrupted, pattern.frame_rate
+        ));
+    } else {
+        recommendations.push(format!(
+            "Scattered packet loss of {:.2}% corrupts {:.1}% of frames at {:.0} fps. Check signal strength and lower the stream bitrate.",
+            pattern.loss_percent, corrupted, pattern.frame_rate
+        ));
+    }
+
+    status
+}
+
+/// How much slower than best effort a marked class's median RTT may be
+const QOS_MEDIAN_MARGIN_MS: f64 = 5.0;
+
+/// How much slower than best effort a marked class's P95 RTT may be
+const QOS_P95_MARGIN_MS: f64 = 10.0;
+
+fn analyze_qos(qos: &QosResults, recommendations: &mut Vec<String>) -> Option<Status> {
+    let marked: Vec<_> = qos.classes.iter().filter(|c| c.dscp != 0).collect();
+    if marked.iter().all(|c| c.received_dscp.is_none()) {
+        return None;
+    }
+
+    let mut status = Status::Green;
+
+    let altered: Vec<_> = marked.iter().filter(|c| c.preserved() == Some(false)).collect();
+    if !altered.is_empty() {
+        let changes: Vec<String> = altered.iter()
+            .map(|c| format!("{} {}→{}", c.name, c.dscp, c.received_dscp.unwrap_or_default()))
+            .collect();
+        let what = if altered.iter().all(|c| c.received_dscp == Some(0)) { "stripped" } else { "rewritten" };
+        recommendations.push(format!(
+            "DSCP markings are {} on the way to the host ({}). Router QoS rules that match on DSCP will not see Remote Play traffic; prioritize by device or port instead.",
+            what, changes.join(", ")
+        ));
+        status = Status::Yellow;
+    }
+
+    // Some networks police marked traffic harder than best effort
+    let best_effort_loss = qos.classes.iter()
+        .find(|c| c.dscp == 0)
+        .map(|c| c.loss_percent())
+        .unwrap_or(0.0);
+    for class in marked.iter().filter(|c| c.loss_percent() > best_effort_loss + 5.0) {
+        recommendations.push(format!(
+            "{} marked probes lost {:.1}% against {:.1}% for best effort. The network polices this class; do not mark Remote Play traffic as {}.",
+            class.name, class.loss_percent(), best_effort_loss, class.name
+        ));
+        status = worst(status, Status::Yellow);
+    }
+
+    // Or queue it behind best effort
+    if let Some(best_effort) = qos.classes.iter().find(|c| c.dscp == 0) {
+        for class in &marked {
+            let slower_by = |rtt: fn(&TrafficClassResult) -> Option<f64>| match (rtt(class), rtt(best_effort)) {
+                (Some(ms), Some(be_ms)) => ms - be_ms,
+                _ => 0.0,
+            };
+            let median_gap = slower_by(|c| c.median_rtt_ms);
+            let p95_gap = slower_by(|c| c.p95_rtt_ms);
+            if median_gap > QOS_MEDIAN_MARGIN_MS || p95_gap > QOS_P95_MARGIN_MS {
+                recommendations.push(format!(
+                    "{} marked probes were slower than best effort (median +{:.1}ms, P95 +{:.1}ms). The network queues this class behind unmarked traffic; do not mark Remote Play traffic as {}.",
+                    class.name, median_gap, p95_gap, class.name
+                ));
+                status = worst(status, Status::Yellow);
+            }
+        }
+    }
+
+    Some(status)
+}
+
+fn analyze_ports(ports: &PortCheckResults, recommendations: &mut Vec<String>) -> Option<Status> {
+    let tested: Vec<_> = ports.ports.iter().filter(|r| r.reachable.is_some()).collect();
+    if tested.is_empty() {
+        return None;
+    }
+
+    let mut status = Status::Green;
+    for transport in [Transport::Udp, Transport::Tcp] {
+        let of_transport: Vec<_> = tested.iter().filter(|r| r.port.transport == transport).collect();
+        let blocked: Vec<_> = of_transport.iter().filter(|r| r.reachable == Some(false)).collect();
+        if blocked.is_empty() {
+            continue;
+        }
+
+        let port_list = port_ranges(&blocked.iter().map(|r| r.port.port).collect::<Vec<_>>());
+        // A refusal comes from a host that actively rejects; silence means a firewall drops the packets
+        let refused = blocked.iter().all(|r| r.failure == Some(PortFailure::Refused));
+        let cause = if refused {
+            "are refused by the host"
+        } else {
+            "are silently dropped, usually by the host firewall or a router between the devices"
+        };
+        let fix = match transport {
+            Transport::Udp => "Remote Play streams over UDP; allow Steam through the host firewall for private networks or open these UDP ports.",
+            Transport::Tcp => "Steam uses these TCP ports to discover and connect to the host; allow Steam through the host firewall for private networks.",
+        };
+        recommendations.push(format!("{} ports {} {}. {}", transport, port_list, cause, fix));
+
+        status = worst(status, if blocked.len() == of_transport.len() { Status::Red } else { Status::Yellow });
+    }
+
+    Some(status)
+}
+
+/// Format ports as compact ranges, e.g. "27031-27033, 27036"
+fn port_ranges(ports: &[u16]) -> String {
+    let mut sorted = ports.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for port in sorted {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == port => *end = port,
+            _ => ranges.push((port, port)),
+        }
+    }
+    ranges.iter()
+        .map(|&(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn analyze_network(side: Side, env: &NetworkEnvironment, recommendations: &mut Vec<String>) -> Option<Status> {
+    let iface = env.active()?;
+    let mut status = Status::Green;
+
+    match iface.kind {
+        InterfaceKind::Wifi => {
+            let wifi = iface.wifi.clone().unwrap_or_default();
+            let signal = wifi.signal_dbm;
+
+            if wifi.band() == Some("2.4 GHz") {
+                let with_signal = signal.map(|dbm| format!(" with {:.0} dBm signal", dbm)).unwrap_or_default();
+                recommendations.push(format!(
+                    "{} is on 2.4 GHz{}. This band is crowded and slow for streaming; connect to the 5 GHz network of the access point.",
+                    side.title(), with_signal
+                ));
+                status = worst(status, Status::Yellow);
+            }
+
+            if let Some(dbm) = signal {
+                if dbm < -70.0 {
+                    recommendations.push(format!(
+                        "{} WiFi signal is weak at {:.0} dBm. Below -70 dBm the link drops to low rates and retransmits; move closer to the access point.",
+                        side.title(), dbm
+                    ));
+                    status = worst(status, if dbm < -80.0 { Status::Red } else { Status::Yellow });
+                }
+            }
+
+            if wifi.power_save == Some(true) {
+                recommendations.push(format!(
+                    "{} has WiFi power saving enabled on {}, which delays packets by up to a beacon interval. Disable it with `iw dev {} set power_save off`.",
+                    side.title(), iface.name, iface.name
+                ));
+                status = worst(status, Status::Yellow);
+            }
+
+            // The streaming PC sends the video, so its uplink matters most
+            if side == Side::Host {
+                recommendations.push("Host streams over WiFi. Connect the gaming PC with Ethernet so only one side of the stream crosses the air.".to_string());
+                status = worst(status, Status::Yellow);
+            }
+        }
+        InterfaceKind::Ethernet => {
+            if let Some(speed) = iface.speed_mbps.filter(|&speed| speed < 1000) {
+                recommendations.push(format!(
+                    "{} Ethernet link on {} negotiated only {} Mbps. Check the cable and switch port for gigabit support.",
+                    side.title(), iface.name, speed
+                ));
+                status = worst(status, if speed < 100 { Status::Red } else { Status::Yellow });
+            }
+        }
+        InterfaceKind::Virtual => {
+            recommendations.push(format!(
+                "{} traffic goes through {}, a virtual interface such as a VPN or tunnel. Stream over the LAN directly where possible.",
+                side.title(), iface.name
+            ));
+            status = worst(status, Status::Yellow);
+        }
+        InterfaceKind::Loopback => {}
+    }
+
+    Some(status)
+}
+
+/// When a traffic phase happened, e.g. "during the loss test"
+fn phase_label(phase: &str) -> String {
+    match phase {
+        "before" | "after" => format!("{} the tests", phase),
+        "during" => "during the tests".to_string(),
+        test => format!("during the {} test", test),
+    }
+}
+
+/// One-line summary of an interface, e.g. "wlan0 (WiFi 5 GHz, -58 dBm, 780 Mbps)"
+fn describe_interface(iface: &InterfaceInfo) -> String {
+    let mut details = Vec::new();
+    match iface.kind {
+        InterfaceKind::Wifi => {
+            let wifi = iface.wifi.clone().unwrap_or_default();
+            details.push(match wifi.band() {
+                Some(band) => format!("WiFi {}", band),
+                None => "WiFi".to_string(),
+            });
+            if let Some(dbm) = wifi.signal_dbm {
+                details.push(format!("{:.0} dBm", dbm));
+            }
+            if let Some(rate) = wifi.bitrate_mbps {
+                details.push(format!("{:.0} Mbps", rate));
+            }
+            if wifi.power_save == Some(true) {
+                details.push("power save".to_string());
+            }
+        }
+        InterfaceKind::Ethernet => {
+            details.push("Ethernet".to_string());
+            if let Some(speed) = iface.speed_mbps {
+                details.push(format!("{} Mbps", speed));
+            }
+        }
+        InterfaceKind::Loopback => details.push("loopback".to_string()),
+        InterfaceKind::Virtual => details.push("virtual".to_string()),
+    }
+    if let Some(mtu) = iface.mtu {
+        details.push(format!("MTU {}", mtu));
+    }
+    format!("{} ({})", iface.name, details.join(", "))
+}
+
+/// Summary lines of a machine: OS and device, kernel and memory, CPU, GPU and displays
+fn describe_environment(env: &SystemEnvironment) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let os = env.os.clone().unwrap_or_else(|| "unknown OS".to_string());
+    lines.push(if env.steam_deck { format!("{} on Steam Deck", os) } else { os });
+
+    let mut system = Vec::new();
+    if let Some(kernel) = &env.kernel {
+        system.push(format!("kernel {}", kernel));
+    }
+    if let Some(memory) = env.memory_mb {
+        system.push(format!("{:.1} GB RAM", memory as f64 / 1024.0));
+    }
+    if !system.is_empty() {
+        lines.push(system.join(", "));
+    }
+
+    if let Some(model) = &env.cpu_model {
+        let mut cpu = model.clone();
+        if let Some(threads) = env.cpu_threads {
+            cpu.push_str(&format!(" ×{}", threads));
+        }
+        if let Some(governor) = &env.cpu_governor {
+            cpu.push_str(&format!(", {}", governor));
+        }
+        lines.push(cpu);
+    }
+
+    let mut graphics: Vec<String> = env.gpus.iter()
+        .map(|gpu| match &gpu.driver {
+            Some(driver) => format!("{} GPU ({})", gpu.vendor, driver),
+            None => format!("{} GPU", gpu.vendor),
+        })
+        .collect();
+    graphics.extend(env.displays.iter().map(|display| match display.modes.first() {
+        Some(mode) => format!("{} {}", display.connector, mode),
+        None => display.connector.clone(),
+    }));
+    if !graphics.is_empty() {
+        lines.push(graphics.join(", "));
+    }
+
+    lines
+}
+
+/// The client's power states: samples from the run, then the probe at its end
+///
+/// Machines without any of the sysfs files give empty states, which are left out.
+fn client_power_states(results: &TestResults) -> Vec<&PowerState> {
+    let samples = results.client_power.iter().flat_map(|power| power.samples.iter().map(|sample| &sample.state));
+    let probed = results.client_environment.as_ref().and_then(|env| env.power.as_ref());
+    samples.chain(probed)
+        .filter(|state| **state != PowerState::default())
+        .collect()
+}
+
+/// Battery or AC, with the charge at the first and last reading
+fn describe_supply(states: &[&PowerState]) -> String {
+    let charges: Vec<f64> = states.iter().filter_map(|state| state.battery_percent).collect();
+    let charge = match (charges.first(), charges.last()) {
+        (Some(first), Some(last)) if first != last => format!(" {:.0}% → {:.0}%", first, last),
+        (Some(first), _) => format!(" {:.0}%", first),
+        _ => String::new(),
+    };
+    if states.iter().any(|state| state.on_battery()) {
+        format!("battery{}", charge)
+    } else if states.iter().any(|state| state.ac_online == Some(true)) {
+        format!("AC adapter{}", if charge.is_empty() { String::new() } else { format!(", battery{}", charge) })
+    } else {
+        "no battery reported".to_string()
+    }
+}
+
+fn analyze_power(states: &[&PowerState], wifi_power_save: bool, recommendations: &mut Vec<String>) -> Option<Status> {
+    if states.is_empty() {
+        return None;
+    }
+    let mut status = Status::Green;
+
+    if states.iter().any(|state| state.on_battery()) {
+        let charge = states.iter()
+            .filter_map(|state| state.battery_percent)
+            .reduce(f64::min)
+            .map(|percent| format!(" ({:.0}% charge)", percent))
+            .unwrap_or_default();
+        let wifi = if wifi_power_save { " with WiFi power saving enabled" } else { "" };
+        recommendations.push(format!(
+            "Client was on battery{}{}. Handhelds like the Steam Deck lower CPU, GPU and WiFi power on battery; plug in the charger while streaming.",
+            charge, wifi
+        ));
+        status = worst(status, Status::Yellow);
+    }
+
+    let throttling = states.iter()
+        .filter_map(|state| state.throttling())
+        .max_by(|a, b| a.celsius.total_cmp(&b.celsius));
+    if let Some(temperature) = throttling {
+        recommendations.push(format!(
+            "Client reached {:.0}°C on {}, at its {:.0}°C throttling point. Clocks drop and decoding slows when it is this hot; let it cool down and keep the vents clear.",
+            temperature.celsius, temperature.sensor, temperature.throttle_celsius.unwrap_or(temperature.celsius)
+        ));
+        status = worst(status, Status::Yellow);
+    }
+
+    if states.iter().any(|state| state.platform_profile.as_deref() == Some("low-power")) {
+        recommendations.push("Client uses the low-power platform profile, which caps its clocks. Switch to balanced or performance while streaming.".to_string());
+        status = worst(status, Status::Yellow);
+    }
+
+    Some(status)
+}
+
+fn analyze_system(system: &SystemResults, recommendations: &mut Vec<String>) -> Status {
+    // A thread that waits longer than a 60 fps frame misses it
+    let status = if system.p99_us > 4000.0 || system.max_us > 16_667.0 {
+        Status::Red
+    } else if system.p99_us > 1000.0 || system.max_us > 5000.0 {
+        Status::Yellow
+    } else {
+        return Status::Green;
+    };
+
+    recommendations.push(format!(
+        "Threads on this device wait up to {:.1}ms for a CPU (P99 {:.2}ms); the game or gamescope can starve the Steam client. Cap the game's frame rate or close background apps.",
+        system.max_us / 1000.0, system.p99_us / 1000.0
+    ));
+
+    status
+}
+
+fn analyze_video(video: &VideoResults, recommendations: &mut Vec<String>) -> Status {
+    let has = |codec: &str| video.supported_codecs.iter().any(|c| normalize_codec(c) == codec);
+
+    let mut status = if !has("h264") && !has("hevc") {
+        recommendations.push("No H.264 or HEVC hardware decoder detected. Streaming will fall back to software decoding.".to_string());
+        Status::Red
+    } else if !has("hevc") {
+        recommendations.push("HEVC decoding is unavailable; Steam will use H.264, which needs more bandwidth for the same quality.".to_string());
+        Status::Yellow
+    } else {
+        Status::Green
+    };
+
+    if let Some(fps) = video.decode_fps {
+        if fps < 30.0 {
+            recommendations.push(format!(
+                "Video decoding only reaches {:.1} fps. Lower the stream resolution or enable hardware decoding in Steam.",
+                fps
+            ));
+            status = worst(status, Status::Red);
+        } else if fps < 60.0 {
+            recommendations.push(format!(
+                "Video decoding reaches {:.1} fps, below a 60 fps stream. Consider limiting the stream to 30 fps.",
+                fps
+            ));
+            status = worst(status, Status::Yellow);
+        }
+    }
+
+    status
+}
+
+/// Codecs in order of preference for streaming; the first two need far less bandwidth than H.264
+const STREAM_CODECS: [&str; 3] = ["av1", "hevc", "h264"];
+
+/// Compare the host's hardware encoders with the client's decoders
+///
+/// `None` when the host reported no encoders, as with NVENC, which VA-API does not expose.
+fn analyze_codec_pairing(client: &VideoResults, host: &VideoResults, recommendations: &mut Vec<String>) -> Option<Status> {
+    let encoders: Vec<&str> = STREAM_CODECS.into_iter()
+        .filter(|codec| host.encode_codecs().contains(codec))
+        .collect();
+    if encoders.is_empty() {
+        return None;
+    }
+    let decodes = |codec: &str| client.supported_codecs.iter().any(|c| normalize_codec(c) == codec);
+    let common: Vec<&str> = STREAM_CODECS.into_iter()
+        .filter(|codec| encoders.contains(codec) && decodes(codec))
+        .collect();
+    let list = |codecs: Vec<&str>| codecs.into_iter().map(codec_label).collect::<Vec<_>>().join(", ");
+    let client_decoders = STREAM_CODECS.into_iter().filter(|codec| decodes(codec)).collect::<Vec<_>>();
+
+    if common.is_empty() {
+        recommendations.push(format!(
+            "The host encodes {} in hardware and the client decodes {}; with no codec in common, one side falls back to software. Update the GPU drivers or stream from another machine.",
+            list(encoders), if client_decoders.is_empty() { "nothing".to_string() } else { list(client_decoders) }
+        ));
+        return Some(Status::Red);
+    }
+    // A client without HEVC or AV1 is already reported on its own
+    let efficient = |codec: &&str| *codec != "h264";
+    if !common.iter().any(efficient) && client_decoders.iter().any(efficient) {
+        recommendations.push(format!(
+            "The client decodes {} in hardware but the host encodes only {}, so the stream uses H.264 and needs more bandwidth for the same quality.",
+            list(client_decoders.into_iter().filter(efficient).collect()), list(encoders)
+        ));
+        return Some(Status::Yellow);
+    }
+    Some(Status::Green)
+}
+
+/// Display name of a normalized codec, e.g. "H.264"
+fn codec_label(codec: &str) -> &str {
+    match codec {
+        "h264" => "H.264",
+        "hevc" => "HEVC",
+        "av1" => "AV1",
+        other => other,
+    }
+}
+
+fn analyze_audio(audio: &AudioResults, recommendations: &mut Vec<String>) -> Status {
+    if audio.output_devices.is_empty() {
+        recommendations.push("No audio output devices found. Check that PipeWire or PulseAudio is running.".to_string());
+        return Status::Red;
+    }
+
+    match audio.sample_rate {
+        Some(rate) if rate != 48000 => {
+            recommendations.push(format!(
+                "Audio output runs at {} Hz; Remote Play streams 48000 Hz, so every buffer is resampled.",
+                rate
+            ));
+            Status::Yellow
+        }
+        _ => Status::Green,
+    }
+}
+
+/// Map codec names as reported by different tools to a common spelling
+fn normalize_codec(name: &str) -> &'static str {
+    let lower = name.to_lowercase().replace(['.', '-', ' '], "");
+    match lower.as_str() {
+        "h264" | "avc" => "h264",
+        "h265" | "hevc" => "hevc",
+        "av1" => "av1",
+        _ => "other",
+    }
+}
+
+/// Return the more severe of two statuses
+pub(crate) fn worst(a: Status, b: Status) -> Status {
+    let rank = |s: Status| match s {
+        Status::Red => 3,
+        Status::Yellow => 2,
+        Status::Green => 1,
+    };
+    if rank(b) > rank(a) { b } else { a }
 }
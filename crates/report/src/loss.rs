@@ -0,0 +1,159 @@
+/// Loss burst analysis with a two-state Gilbert-Elliott model
+use chequer_common::LossResults;
+use serde::{Deserialize, Serialize};
+
+/// Number of loss runs of one length
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LossRun {
+    /// Consecutive lost probes
+    pub length: usize,
+    pub count: usize,
+}
+
+/// How losses cluster and what they would do to a video stream
+///
+/// The model is the simple Gilbert form of Gilbert-Elliott: every probe
+/// sent in the good state arrives and every probe sent in the bad state is
+/// lost, so the transition probabilities are fitted directly from the
+/// loss sequence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LossPattern {
+    pub loss_percent: f64,
+    /// Probability of moving from the good to the bad state after a probe
+    pub p_good_to_bad: f64,
+    /// Probability of moving from the bad to the good state after a probe
+    pub p_bad_to_good: f64,
+    /// Mean number of consecutive lost probes
+    pub mean_burst_length: f64,
+    /// Loss runs by length, shortest first
+    pub runs: Vec<LossRun>,
+    pub frame_rate: f64,
+    /// Frame periods covered by the stream
+    pub frames: usize,
+    /// Frame periods in which at least one probe was lost
+    pub corrupted_frames: usize,
+}
+
+impl LossPattern {
+    pub fn corrupted_frame_percent(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        self.corrupted_frames as f64 / self.frames as f64 * 100.0
+    }
+}
+
+/// Fit the loss sequence and map it onto frames at `frame_rate`
+pub fn loss_pattern(loss: &LossResults, frame_rate: f64) -> Option<LossPattern> {
+    let lost = loss.lost_sequence();
+    if lost.is_empty() || frame_rate <= 0.0 {
+        return None;
+    }
+
+    // Transition counts between consecutive probes
+    let (mut good, mut good_to_bad, mut bad, mut bad_to_good) = (0usize, 0usize, 0usize, 0usize);
+    for pair in lost.windows(2) {
+        match (pair[0], pair[1]) {
+            (false, next) => {
+                good += 1;
+                good_to_bad += next as usize;
+            }
+            (true, next) => {
+                bad += 1;
+                bad_to_good += !next as usize;
+            }
+        }
+    }
+    let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+
+    let mut run_lengths = Vec::new();
+    let mut current = 0;
+    for &l in lost.iter().chain(std::iter::once(&false)) {
+        if l {
+            current += 1;
+        } else if current > 0 {
+            run_lengths.push(current);
+            current = 0;
+        }
+    }
+    let mut runs: Vec<LossRun> = Vec::new();
+    run_lengths.sort_unstable();
+    for length in &run_lengths {
+        match runs.last_mut() {
+            Some(run) if run.length == *length => run.count += 1,
+            _ => runs.push(LossRun { length: *length, count: 1 }),
+        }
+    }
+
+    // A frame is corrupted if any probe sent during its period was lost
+    let frame_of = |i: usize| (i as f64 * loss.interval_ms * frame_rate / 1000.0) as usize;
+    let frames = frame_of(lost.len() - 1) + 1;
+    let mut corrupted = vec![false; frames];
+    for (i, _) in lost.iter().enumerate().filter(|(_, &l)| l) {
+        corrupted[frame_of(i)] = true;
+    }
+
+    Some(LossPattern {
+        loss_percent: loss.loss_percent(),
+        p_good_to_bad: ratio(good_to_bad, good),
+        p_bad_to_good: ratio(bad_to_good, bad),
+        mean_burst_length: ratio(loss.lost_seqs.len(), run_lengths.len()),
+        runs,
+        frame_rate,
+        frames,
+        corrupted_frames: corrupted.iter().filter(|&&c| c).count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(sent: usize, lost_seqs: Vec<u32>) -> LossResults {
+        LossResults {
+            sent,
+            interval_ms: 2.0,
+            payload_bytes: 1200,
+            lost_seqs,
+        }
+    }
+
+    #[test]
+    fn test_random_and_burst_loss_differ() {
+        // 1% loss either way: 10 isolated losses or one burst of 10, 2ms apart
+        let random = loss_pattern(&stream(1000, (0..10).map(|i| i * 100 + 50).collect()), 60.0).unwrap();
+        let burst = loss_pattern(&stream(1000, (500..510).collect()), 60.0).unwrap();
+
+        assert_eq!(random.loss_percent, burst.loss_percent);
+        assert_eq!(random.mean_burst_length, 1.0);
+        assert_eq!(burst.mean_burst_length, 10.0);
+        assert_eq!(random.p_bad_to_good, 1.0);
+        assert!((burst.p_bad_to_good - 0.1).abs() < 1e-9);
+        assert_eq!(random.runs, vec![LossRun { length: 1, count: 10 }]);
+        assert_eq!(burst.runs, vec![LossRun { length: 10, count: 1 }]);
+
+        // 2 seconds at 60 fps; isolated losses hit 10 frames, the burst 20ms spans 2
+        assert_eq!(random.frames, 120);
+        assert_eq!(random.corrupted_frames, 10);
+        assert_eq!(burst.corrupted_frames, 2);
+    }
+
+    #[test]
+    fn test_transition_probabilities() {
+        // good good bad bad good bad
+        let pattern = loss_pattern(&stream(6, vec![2, 3, 5]), 60.0).unwrap();
+        assert!((pattern.p_good_to_bad - 2.0 / 3.0).abs() < 1e-9);
+        assert!((pattern.p_bad_to_good - 0.5).abs() < 1e-9);
+        assert_eq!(pattern.runs, vec![LossRun { length: 1, count: 1 }, LossRun { length: 2, count: 1 }]);
+        assert_eq!(pattern.mean_burst_length, 1.5);
+    }
+
+    #[test]
+    fn test_no_loss() {
+        let pattern = loss_pattern(&stream(100, vec![]), 60.0).unwrap();
+        assert_eq!(pattern.mean_burst_length, 0.0);
+        assert!(pattern.runs.is_empty());
+        assert_eq!(pattern.corrupted_frames, 0);
+        assert!(loss_pattern(&stream(0, vec![]), 60.0).is_none());
+    }
+}
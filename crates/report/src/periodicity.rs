@@ -0,0 +1,189 @@
+/// Detection of latency spikes that repeat at a fixed period
+use chequer_common::LatencyResults;
+use serde::{Deserialize, Serialize};
+
+/// Minimum autocorrelation for a lag to count as periodic
+const MIN_CORRELATION: f64 = 0.5;
+
+/// A period must repeat at least this often within the run
+const MIN_REPETITIONS: f64 = 3.0;
+
+/// Fraction of periods in the run that must contain a spike
+const MIN_COVERAGE: f64 = 0.5;
+
+/// Latency spikes recurring at a regular interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodicSpikes {
+    /// Time between spikes
+    pub period_ms: f64,
+    /// Mean spike height above the median RTT
+    pub amplitude_ms: f64,
+    /// Number of spike samples in the run
+    pub spike_count: usize,
+    /// Autocorrelation of the spike series at the detected period (0-1)
+    pub correlation: f64,
+}
+
+/// Look for a periodic spike pattern using the autocorrelation of the spike series
+pub fn detect_periodic_spikes(lat: &LatencyResults) -> Option<PeriodicSpikes> {
+    let (offsets, samples): (Vec<f64>, Vec<f64>) = lat.valid_timeline().into_iter().unzip();
+    if samples.len() < 20 || lat.spike_threshold_ms <= 0.0 {
+        return None;
+    }
+
+    let spikes: Vec<usize> = (0..samples.len())
+        .filter(|&i| samples[i] > lat.spike_threshold_ms)
+        .collect();
+    if spikes.len() < MIN_REPETITIONS as usize {
+        return None;
+    }
+
+    // Resample the spike indicator onto a uniform time grid, one bin per probe interval
+    let duration_ms = offsets[offsets.len() - 1] - offsets[0];
+    let bin_ms = (duration_ms / (samples.len() - 1) as f64).max(1.0);
+    let bins = (duration_ms / bin_ms) as usize + 1;
+    let mut series = vec![0.0; bins];
+    for &i in &spikes {
+        let bin = (((offsets[i] - offsets[0]) / bin_ms) as usize).min(bins - 1);
+        series[bin] = 1.0;
+    }
+
+    // Spread each spike over its neighbours so slightly irregular periods still line up
+    let smoothed: Vec<f64> = (0..bins)
+        .map(|i| {
+            let lo = i.saturating_sub(1);
+            let hi = (i + 1).min(bins - 1);
+            series[lo..=hi].iter().cloned().fold(0.0, f64::max)
+        })
+        .collect();
+
+    let max_lag = (bins as f64 / MIN_REPETITIONS) as usize;
+    let correlations: Vec<(usize, f64)> = (2..=max_lag)
+        .map(|lag| (lag, autocorrelation(&smoothed, lag)))
+        .collect();
+    let best = correlations.iter().map(|&(_, r)| r).fold(0.0, f64::max);
+    if best < MIN_CORRELATION {
+        return None;
+    }
+
+    // Multiples of the true period correlate too; prefer the shortest lag close to the best
+    let (lag, correlation) = correlations
+        .iter()
+        .enumerate()
+        .find(|&(idx, &(_, r))| {
+            let prev = if idx > 0 { correlations[idx - 1].1 } else { 0.0 };
+            let next = correlations.get(idx + 1).map(|&(_, r)| r).unwrap_or(0.0);
+            r >= best * 0.8 && r >= prev && r >= next
+        })
+        .map(|(_, &peak)| peak)?;
+
+    // Most periods must actually contain a spike, otherwise a few clustered spikes look periodic.
+    // Periods are centred on the first spike so jitter around its phase stays in one period.
+    let period_ms = lag as f64 * bin_ms;
+    let first_ms = offsets[spikes[0]];
+    let mut periods: Vec<i64> = spikes.iter()
+        .map(|&i| ((offsets[i] - first_ms) / period_ms).round() as i64)
+        .collect();
+    periods.dedup();
+    let expected = duration_ms / period_ms;
+    if (periods.len() as f64) < expected * MIN_COVERAGE {
+        return None;
+    }
+
+    let amplitude_ms = spikes.iter()
+        .map(|&i| samples[i] - lat.p50_ms)
+        .sum::<f64>() / spikes.len() as f64;
+
+    Some(PeriodicSpikes {
+        period_ms,
+        amplitude_ms,
+        spike_count: spikes.len(),
+        correlation,
+    })
+}
+
+/// Normalized autocorrelation of a series at the given lag
+fn autocorrelation(series: &[f64], lag: usize) -> f64 {
+    let mean = series.iter().sum::<f64>() / series.len() as f64;
+    let variance: f64 = series.iter().map(|&x| (x - mean).powi(2)).sum();
+    if variance == 0.0 || lag >= series.len() {
+        return 0.0;
+    }
+
+    let covariance: f64 = series.iter()
+        .zip(&series[lag..])
+        .map(|(&a, &b)| (a - mean) * (b - mean))
+        .sum();
+    covariance / variance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a run of probes every `interval_ms` with small deterministic noise
+    fn run(count: usize, interval_ms: f64, spike_at: impl Fn(usize) -> bool) -> LatencyResults {
+        let samples: Vec<f64> = (0..count)
+            .map(|i| {
+                let noise = ((i * 7919) % 13) as f64 * 0.05;
+                if spike_at(i) { 45.0 + noise } else { 5.0 + noise }
+            })
+            .collect();
+        let offsets = (0..count).map(|i| i as f64 * interval_ms).collect();
+        LatencyResults::from_samples(samples, 0.0).with_send_offsets(offsets)
+    }
+
+    #[test]
+    fn test_detects_periodic_spikes() {
+        // A spike every 500ms over a 6 second run
+        let lat = run(600, 10.0, |i| i % 50 == 7);
+        let periodic = detect_periodic_spikes(&lat).expect("periodic spikes not detected");
+        assert!((periodic.period_ms - 500.0).abs() <= 10.0, "period {}", periodic.period_ms);
+        assert!(periodic.amplitude_ms > 35.0);
+        assert_eq!(periodic.spike_count, 12);
+    }
+
+    #[test]
+    fn test_tolerates_slightly_irregular_period() {
+        // Spikes drift by one probe around a 1 second period (990ms, 1010ms, ...)
+        let lat = run(600, 10.0, |i| i >= 100 && i % 100 == (i / 100) % 2);
+        let periodic = detect_periodic_spikes(&lat).expect("periodic spikes not detected");
+        assert!((periodic.period_ms - 1000.0).abs() <= 20.0, "period {}", periodic.period_ms);
+    }
+
+    #[test]
+    fn test_ignores_random_spikes() {
+        let positions = [13, 58, 71, 160, 201, 330, 347, 402, 511, 587];
+        let lat = run(600, 10.0, |i| positions.contains(&i));
+        assert!(detect_periodic_spikes(&lat).is_none());
+    }
+
+    #[test]
+    fn test_ignores_isolated_spike_cluster() {
+        // Four spikes close together early in the run are not a repeating pattern
+        let lat = run(600, 10.0, |i| [20, 24, 28, 32].contains(&i));
+        assert!(detect_periodic_spikes(&lat).is_none());
+    }
+
+    #[test]
+    fn test_ignores_bursts_in_few_periods() {
+        // Bursts of three spikes every 500ms, but only in the first four of twelve periods
+        let lat = run(600, 10.0, |i| i < 200 && (7..10).contains(&(i % 50)));
+        assert!(detect_periodic_spikes(&lat).is_none());
+    }
+
+    #[test]
+    fn test_requires_send_offsets() {
+        let mut lat = run(600, 10.0, |i| i % 50 == 7);
+        lat.send_offsets_ms.clear();
+        assert!(detect_periodic_spikes(&lat).is_none());
+    }
+
+    #[test]
+    fn test_skips_warmup_samples() {
+        // Slow warm-up probes would otherwise count as extra, unevenly spaced spikes
+        let lat = run(600, 10.0, |i| i < 5 || i % 50 == 7).with_warmup(5);
+        let periodic = detect_periodic_spikes(&lat).expect("periodic spikes not detected");
+        assert_eq!(periodic.spike_count, 12);
+    }
+}
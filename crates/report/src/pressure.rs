@@ -0,0 +1,151 @@
+/// Correlation of latency spikes with CPU, memory and IO pressure on either peer
+use chequer_common::{LatencyResults, PressureResults, PressureSample, Side};
+use serde::{Deserialize, Serialize};
+
+/// Share of a sample interval a resource must stall for to count as under pressure
+pub const STALL_THRESHOLD_PERCENT: f64 = 10.0;
+
+/// Share of the spikes that must coincide with a pressure source to report it
+const MIN_SHARE: f64 = 0.5;
+
+/// Latency spikes that coincided with stalls on one resource of one peer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PressureCorrelation {
+    pub side: Side,
+    /// "CPU", "memory" or "IO"
+    pub resource: String,
+    /// Spikes in flight while the resource stalled
+    pub coinciding: usize,
+    /// Spikes covered by the peer's pressure samples
+    pub spikes: usize,
+}
+
+/// Find the resources of `side` that stalled during at least half of the latency spikes
+///
+/// Host samples are stamped by the host's clock, so spike times are shifted by
+/// the host clock offset the probes measured; without one the host is skipped.
+pub fn correlate_spikes(lat: &LatencyResults, side: Side, pressure: &PressureResults) -> Vec<PressureCorrelation> {
+    let Some(client_start) = lat.started_at_ms else {
+        return Vec::new();
+    };
+    let start = match side {
+        Side::Client => client_start,
+        Side::Host => match lat.host_clock_offset_ms {
+            Some(offset) => client_start + offset,
+            None => return Vec::new(),
+        },
+    };
+    if lat.spike_threshold_ms <= 0.0 || pressure.samples.is_empty() {
+        return Vec::new();
+    }
+
+    // The sample intervals overlapping each spike, from its send time until its reply
+    let spike_pressure: Vec<Vec<&PressureSample>> = lat.valid_timeline().into_iter()
+        .filter(|&(_, rtt)| rtt > lat.spike_threshold_ms)
+        .map(|(offset, rtt)| samples_during(pressure, start + offset, start + offset + rtt))
+        .filter(|overlapping| !overlapping.is_empty())
+        .collect();
+    if spike_pressure.is_empty() {
+        return Vec::new();
+    }
+
+    let resources = PressureSample::RESOURCES;
+    let mut correlations: Vec<PressureCorrelation> = resources.iter().enumerate()
+        .map(|(resource, name)| {
+            let stalled = |s: &&PressureSample| s.stalls()[resource].is_some_and(|p| p >= STALL_THRESHOLD_PERCENT);
+            let coinciding = spike_pressure.iter()
+                .filter(|overlapping| overlapping.iter().any(stalled))
+                .count();
+            PressureCorrelation {
+                side,
+                resource: name.to_string(),
+                coinciding,
+                spikes: spike_pressure.len(),
+            }
+        })
+        .filter(|c| c.coinciding > 0 && c.coinciding as f64 >= c.spikes as f64 * MIN_SHARE)
+        .collect();
+
+    correlations.sort_by_key(|c| std::cmp::Reverse(c.coinciding));
+    correlations
+}
+
+/// Samples whose interval overlaps `start_ms..=end_ms`
+fn samples_during(pressure: &PressureResults, start_ms: f64, end_ms: f64) -> Vec<&PressureSample> {
+    pressure.samples.iter()
+        .filter(|s| s.timestamp_ms >= start_ms && s.timestamp_ms - pressure.interval_ms <= end_ms)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_MS: f64 = 1_700_000_000_000.0;
+
+    /// Probes every 10ms for 2 seconds with a spike every 200ms
+    fn spiky_run() -> LatencyResults {
+        let samples: Vec<f64> = (0..200).map(|i| if i % 20 == 5 { 40.0 } else { 4.0 }).collect();
+        let offsets = (0..200).map(|i| i as f64 * 10.0).collect();
+        LatencyResults::from_samples(samples, 0.0)
+            .with_send_offsets(offsets)
+            .with_start_time(START_MS)
+    }
+
+    /// 100ms samples over the run, with CPU stalls in the intervals `stalled` picks
+    fn pressure(stalled: impl Fn(usize) -> bool) -> PressureResults {
+        let samples = (1..=21)
+            .map(|i| PressureSample {
+                timestamp_ms: START_MS + i as f64 * 100.0,
+                cpu_busy_percent: Some(50.0),
+                cpu_some_percent: Some(if stalled(i) { 35.0 } else { 1.0 }),
+                memory_some_percent: Some(0.0),
+                io_some_percent: None,
+            })
+            .collect();
+        PressureResults { interval_ms: 100.0, samples }
+    }
+
+    #[test]
+    fn test_spikes_during_cpu_stalls() {
+        // Spikes go out at 50ms, 250ms, ...: in the intervals ending at 100ms, 300ms, ...
+        let correlations = correlate_spikes(&spiky_run(), Side::Client, &pressure(|i| i % 2 == 1));
+        assert_eq!(correlations, vec![PressureCorrelation {
+            side: Side::Client,
+            resource: "CPU".to_string(),
+            coinciding: 10,
+            spikes: 10,
+        }]);
+    }
+
+    #[test]
+    fn test_unrelated_stalls_are_not_reported() {
+        // Stalls only in the intervals between spikes
+        assert!(correlate_spikes(&spiky_run(), Side::Client, &pressure(|i| i % 2 == 0)).is_empty());
+        assert!(correlate_spikes(&spiky_run(), Side::Client, &pressure(|_| false)).is_empty());
+    }
+
+    #[test]
+    fn test_needs_a_common_timeline() {
+        let mut lat = spiky_run();
+        lat.started_at_ms = None;
+        assert!(correlate_spikes(&lat, Side::Client, &pressure(|_| true)).is_empty());
+
+        // Samples from another time cover none of the spikes
+        let mut later = pressure(|_| true);
+        later.samples.iter_mut().for_each(|s| s.timestamp_ms += 60_000.0);
+        assert!(correlate_spikes(&spiky_run(), Side::Client, &later).is_empty());
+    }
+
+    #[test]
+    fn test_host_samples_are_shifted_by_the_clock_offset() {
+        // The host's clock runs 1.5s ahead, so its samples carry later timestamps
+        let mut host = pressure(|i| i % 2 == 1);
+        host.samples.iter_mut().for_each(|s| s.timestamp_ms += 1500.0);
+
+        let lat = spiky_run().with_host_clock_offset(1500.0);
+        assert_eq!(correlate_spikes(&lat, Side::Host, &host).len(), 1);
+        // Without an offset the host's timeline cannot be trusted
+        assert!(correlate_spikes(&spiky_run(), Side::Host, &host).is_empty());
+    }
+}
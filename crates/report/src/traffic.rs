@@ -0,0 +1,141 @@
+/// Background traffic on either peer's interfaces, net of Chequer's own
+use chequer_common::{Side, TrafficResults};
+use serde::{Deserialize, Serialize};
+
+/// Rate of other traffic during a phase above which the network was not quiet
+pub const QUIET_LIMIT_MBPS: f64 = 1.0;
+
+/// Phases shorter than this are counted in the totals but give no meaningful rate
+const MIN_RATE_PHASE_MS: f64 = 200.0;
+
+/// Traffic one interface of one peer carried that was not Chequer's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignTraffic {
+    pub side: Side,
+    pub interface: String,
+    /// Whether this interface carried the tests
+    pub active: bool,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Highest rate over one phase, both directions together
+    pub peak_mbps: f64,
+    /// Phase the peak was in, e.g. "before" or "loss"
+    pub peak_phase: String,
+}
+
+impl ForeignTraffic {
+    /// Whether the traffic was enough to disturb the measurements
+    pub fn is_busy(&self) -> bool {
+        self.peak_mbps > QUIET_LIMIT_MBPS
+    }
+}
+
+/// Other traffic per interface of `side`; interfaces that carried none are left out
+///
+/// Our own traffic is only subtracted on `active_interface`, the one carrying the tests.
+pub fn foreign_traffic(side: Side, traffic: &TrafficResults, active_interface: Option<&str>) -> Vec<ForeignTraffic> {
+    let mut interfaces: Vec<ForeignTraffic> = Vec::new();
+    for phase in &traffic.phases {
+        for iface in &phase.interfaces {
+            let active = active_interface == Some(iface.name.as_str());
+            let (rx_bytes, tx_bytes) = phase.foreign(iface, active);
+            let mbps = if phase.duration_ms >= MIN_RATE_PHASE_MS {
+                (rx_bytes + tx_bytes) as f64 * 8.0 / (phase.duration_ms * 1000.0)
+            } else {
+                0.0
+            };
+
+            let index = match interfaces.iter().position(|f| f.interface == iface.name) {
+                Some(index) => index,
+                None => {
+                    interfaces.push(ForeignTraffic {
+                        side,
+                        interface: iface.name.clone(),
+                        active,
+                        rx_bytes: 0,
+                        tx_bytes: 0,
+                        peak_mbps: 0.0,
+                        peak_phase: phase.name.clone(),
+                    });
+                    interfaces.len() - 1
+                }
+            };
+            let entry = &mut interfaces[index];
+            entry.rx_bytes += rx_bytes;
+            entry.tx_bytes += tx_bytes;
+            if mbps > entry.peak_mbps {
+                entry.peak_mbps = mbps;
+                entry.peak_phase = phase.name.clone();
+            }
+        }
+    }
+
+    interfaces.retain(|f| f.rx_bytes + f.tx_bytes > 0);
+    interfaces
+}
+
+/// Byte count with a decimal unit, e.g. "12.5 MB"
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=999 => format!("{} B", bytes),
+        1_000..=999_999 => format!("{:.1} kB", bytes as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.1} MB", bytes as f64 / 1e6),
+        _ => format!("{:.2} GB", bytes as f64 / 1e9),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chequer_common::{InterfaceTraffic, TrafficPhase};
+
+    fn phase(name: &str, duration_ms: f64, wlan0: (u64, u64), own: (u64, u64)) -> TrafficPhase {
+        TrafficPhase {
+            name: name.to_string(),
+            duration_ms,
+            interfaces: vec![
+                InterfaceTraffic { name: "wlan0".to_string(), rx_bytes: wlan0.0, tx_bytes: wlan0.1 },
+                InterfaceTraffic { name: "docker0".to_string(), rx_bytes: 0, tx_bytes: 0 },
+            ],
+            own_rx_bytes: own.0,
+            own_tx_bytes: own.1,
+        }
+    }
+
+    #[test]
+    fn test_own_traffic_is_subtracted_on_the_active_interface() {
+        let traffic = TrafficResults {
+            phases: vec![
+                phase("before", 500.0, (2_000, 1_000), (0, 0)),
+                phase("loss", 2000.0, (1_500_000, 1_260_000), (1_250_000, 1_250_000)),
+            ],
+        };
+        let foreign = foreign_traffic(Side::Client, &traffic, Some("wlan0"));
+
+        // docker0 moved nothing and is left out
+        assert_eq!(foreign.len(), 1);
+        assert_eq!((foreign[0].rx_bytes, foreign[0].tx_bytes), (252_000, 11_000));
+        assert_eq!(foreign[0].peak_phase, "loss");
+        assert!((foreign[0].peak_mbps - 1.04).abs() < 1e-9);
+        assert!(foreign[0].is_busy());
+
+        // On another interface the same bytes are all someone else's
+        let elsewhere = foreign_traffic(Side::Client, &traffic, Some("eth0"));
+        assert_eq!(elsewhere[0].rx_bytes, 1_502_000);
+    }
+
+    #[test]
+    fn test_short_phases_give_no_rate() {
+        let traffic = TrafficResults { phases: vec![phase("QoS", 20.0, (10_000, 0), (0, 0))] };
+        let foreign = foreign_traffic(Side::Host, &traffic, None);
+        assert_eq!(foreign[0].rx_bytes, 10_000);
+        assert!(!foreign[0].is_busy());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(120_000), "120.0 kB");
+        assert_eq!(format_bytes(12_500_000), "12.5 MB");
+    }
+}
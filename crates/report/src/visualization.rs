@@ -36,7 +36,6 @@ pub fn sparkline(data: &[f64], width: usize) -> String {
 }
 
 /// Generate a horizontal bar chart
-#[allow(dead_code)]
 pub fn histogram(data: &[f64], bins: usize, width: usize) -> Vec<String> {
     if data.is_empty() || bins == 0 {
         return vec![];
@@ -73,28 +72,16 @@ pub fn histogram(data: &[f64], bins: usize, width: usize) -> Vec<String> {
             };
             
             format!(
-                "{:5.1}-{:5.1}ms ┤{}",
+                "{:6.2}-{:6.2}ms ┤{} {}",
                 bin_start,
                 bin_end,
-                "█".repeat(bar_width)
+                "█".repeat(bar_width),
+                count
             )
         })
         .collect()
 }
 
-/// Calculate percentile value from sorted data
-pub fn percentile(data: &[f64], p: f64) -> f64 {
-    if data.is_empty() {
-        return 0.0;
-    }
-    
-    let mut sorted = data.to_vec();
-    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    
-    let idx = ((sorted.len() - 1) as f64 * p / 100.0).round() as usize;
-    sorted[idx]
-}
-
 /// Draw a box with Unicode characters
 pub fn draw_box(title: &str, content: Vec<String>, width: usize) -> String {
     let mut output = String::new();
@@ -182,9 +169,12 @@ mod tests {
     }
 
     #[test]
-    fn test_percentile() {
-        let data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
-        assert_eq!(percentile(&data, 50.0), 6.0);
-        assert_eq!(percentile(&data, 95.0), 10.0);
+    fn test_histogram() {
+        let data = vec![1.0, 1.5, 2.0, 2.0, 9.0];
+        let bars = histogram(&data, 4, 20);
+        assert_eq!(bars.len(), 4);
+        assert!(bars[0].ends_with(" 4"));
+        assert!(bars[3].ends_with(" 1"));
+        assert!(histogram(&[], 4, 20).is_empty());
     }
 }
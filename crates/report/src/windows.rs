@@ -0,0 +1,147 @@
+/// Per-window latency statistics over the duration of a test run
+use chequer_common::{stats, LatencyResults, Status};
+use serde::{Deserialize, Serialize};
+use crate::{latency_status, worst, P99_YELLOW_MS};
+
+/// Windows with fewer samples than this are reported but never flagged
+pub const MIN_WINDOW_SAMPLES: usize = 5;
+
+/// Share of a window's samples above [`P99_YELLOW_MS`] that makes it yellow
+///
+/// A window of a few dozen samples has its maximum for a P99, so the tail
+/// is judged by how often it spikes, and one spike is never enough.
+const WINDOW_SPIKE_SHARE: f64 = 0.1;
+
+/// Latency statistics for one time window of the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyWindow {
+    /// Window start relative to the first probe
+    pub start_ms: f64,
+    pub samples: usize,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub rfc3550_jitter_ms: f64,
+    pub status: Status,
+}
+
+impl LatencyWindow {
+    /// Whether this window is worse than green and has enough samples to trust
+    pub fn is_degraded(&self) -> bool {
+        self.status != Status::Green && self.samples >= MIN_WINDOW_SAMPLES
+    }
+}
+
+/// `window_ms`, but no shorter than the interval between probes
+pub fn clamp_window_ms(lat: &LatencyResults, window_ms: f64) -> f64 {
+    let rate = lat.probe_rate_hz();
+    if rate > 0.0 {
+        window_ms.max(1000.0 / rate)
+    } else {
+        window_ms
+    }
+}
+
+/// Split the run into consecutive windows of `window_ms` by send time
+pub fn window_series(lat: &LatencyResults, window_ms: f64) -> Vec<LatencyWindow> {
+    let timeline = lat.valid_timeline();
+    if window_ms <= 0.0 || timeline.is_empty() {
+        return Vec::new();
+    }
+
+    let first = timeline[0].0;
+    let count = ((timeline[timeline.len() - 1].0 - first) / window_ms) as usize + 1;
+    let mut buckets: Vec<Vec<f64>> = vec![Vec::new(); count];
+    for &(offset, rtt) in &timeline {
+        let idx = (((offset - first) / window_ms) as usize).min(count - 1);
+        buckets[idx].push(rtt);
+    }
+
+    buckets
+        .iter()
+        .enumerate()
+        .map(|(i, bucket)| {
+            let avg_ms = stats::mean(bucket);
+            let rfc3550_jitter_ms = stats::rfc3550_jitter(bucket);
+            let spikes = bucket.iter().filter(|&&rtt| rtt > P99_YELLOW_MS).count();
+            let spiky = spikes > 1 && spikes as f64 >= bucket.len() as f64 * WINDOW_SPIKE_SHARE;
+            let tail = if spiky { Status::Yellow } else { Status::Green };
+            let status = worst(latency_status(avg_ms, rfc3550_jitter_ms), tail);
+
+            LatencyWindow {
+                start_ms: i as f64 * window_ms,
+                samples: bucket.len(),
+                avg_ms,
+                p95_ms: stats::percentile(bucket, 95.0),
+                max_ms: bucket.iter().cloned().fold(0.0, f64::max),
+                rfc3550_jitter_ms,
+                status,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(samples: Vec<f64>, interval_ms: f64) -> LatencyResults {
+        let offsets = (0..samples.len()).map(|i| i as f64 * interval_ms).collect();
+        LatencyResults::from_samples(samples, 0.0).with_send_offsets(offsets)
+    }
+
+    #[test]
+    fn test_window_series_flags_bad_second() {
+        // 9 good seconds followed by one awful second at 10 probes per second
+        let samples = (0..100).map(|i| if i >= 90 { 120.0 } else { 4.0 }).collect();
+        let windows = window_series(&run(samples, 100.0), 1000.0);
+
+        assert_eq!(windows.len(), 10);
+        assert!(windows[..9].iter().all(|w| w.status == Status::Green && !w.is_degraded()));
+        assert_eq!(windows[9].status, Status::Red);
+        assert!(windows[9].is_degraded());
+        assert_eq!(windows[9].start_ms, 9000.0);
+        assert_eq!(windows[9].samples, 10);
+    }
+
+    #[test]
+    fn test_single_spike_does_not_degrade_a_window() {
+        let mut samples = vec![4.0; 20];
+        samples[3] = 51.0;
+        let windows = window_series(&run(samples.clone(), 100.0), 1000.0);
+        assert_eq!(windows[0].samples, 10);
+        assert_eq!(windows[0].status, Status::Green);
+
+        // A second spike in the same window is a pattern
+        samples[7] = 51.0;
+        let windows = window_series(&run(samples, 100.0), 1000.0);
+        assert_eq!(windows[0].status, Status::Yellow);
+        assert_eq!(windows[1].status, Status::Green);
+    }
+
+    #[test]
+    fn test_sparse_windows_are_not_degraded() {
+        let windows = window_series(&run(vec![4.0, 4.0, 4.0, 90.0], 1000.0), 1000.0);
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[3].status, Status::Red);
+        assert!(!windows[3].is_degraded());
+    }
+
+    #[test]
+    fn test_window_series_needs_send_offsets() {
+        let lat = LatencyResults::from_samples(vec![4.0; 10], 0.0);
+        assert!(window_series(&lat, 1000.0).is_empty());
+        assert!(window_series(&run(vec![4.0; 10], 10.0), 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_windows_are_no_shorter_than_the_probe_interval() {
+        let lat = run(vec![4.0; 100], 100.0);
+        assert_eq!(clamp_window_ms(&lat, 0.001), 100.0);
+
+        let windows = window_series(&lat, clamp_window_ms(&lat, 0.001));
+        assert_eq!(windows.len(), 100);
+        assert_eq!(windows[1].start_ms, 100.0);
+        assert!(windows.iter().all(|w| w.samples == 1));
+    }
+}
@@ -0,0 +1,715 @@
+use chequer_common::{ClockEvent, ClockEventKind, InterfaceInfo, KernelRttResults, LatencyResults, LossResults, PowerResults, PowerSample, PortCheckResult, PortFailure, PowerState, PressureResults, PressureSample, Side, Status, SystemResults, Temperature, TcpInfoResults, TcpInfoSnapshot, TestResults, TrafficClassResult, VideoProfile, VideoResults};
+use chequer_report::{DiagnosticReport, ReportConfig};
+
+fn fixture(name: &str) -> TestResults {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let json = std::fs::read_to_string(&path).expect("Failed to read fixture");
+    serde_json::from_str(&json).expect("Failed to parse fixture")
+}
+
+/// Report on fixture `name` after `edit` changes its results
+fn report_with(name: &str, edit: impl FnOnce(&mut TestResults)) -> DiagnosticReport {
+    let mut results = fixture(name);
+    edit(&mut results);
+    DiagnosticReport::from_results(results)
+}
+
+fn bandwidth_status(download_mbps: f64, upload_mbps: f64) -> Option<Status> {
+    report_with("bandwidth.json", |results| {
+        let bw = results.bandwidth.as_mut().unwrap();
+        bw.download_mbps = download_mbps;
+        bw.upload_mbps = upload_mbps;
+    }).bandwidth_status
+}
+
+#[test]
+fn test_bandwidth_fixture_is_green() {
+    let report = DiagnosticReport::from_results(fixture("bandwidth.json"));
+    assert_eq!(report.bandwidth_status, Some(Status::Green));
+    assert_eq!(report.overall_status, Status::Green);
+    assert!(report.recommendations.is_empty());
+}
+
+#[test]
+fn test_bandwidth_download_boundaries() {
+    assert_eq!(bandwidth_status(30.0, 10.0), Some(Status::Green));
+    assert_eq!(bandwidth_status(29.9, 10.0), Some(Status::Yellow));
+    assert_eq!(bandwidth_status(15.0, 10.0), Some(Status::Yellow));
+    assert_eq!(bandwidth_status(14.9, 10.0), Some(Status::Red));
+}
+
+#[test]
+fn test_bandwidth_upload_boundary() {
+    assert_eq!(bandwidth_status(85.0, 1.0), Some(Status::Green));
+    assert_eq!(bandwidth_status(85.0, 0.9), Some(Status::Yellow));
+    // A slow uplink never hides a red downlink
+    assert_eq!(bandwidth_status(10.0, 0.5), Some(Status::Red));
+}
+
+fn video_status(codecs: &[&str], decode_fps: Option<f64>) -> Option<Status> {
+    report_with("video.json", |results| {
+        let video = results.video.as_mut().unwrap();
+        video.supported_codecs = codecs.iter().map(|c| c.to_string()).collect();
+        video.decode_fps = decode_fps;
+    }).video_status
+}
+
+#[test]
+fn test_video_fixture_is_green() {
+    let report = DiagnosticReport::from_results(fixture("video.json"));
+    assert_eq!(report.video_status, Some(Status::Green));
+}
+
+#[test]
+fn test_video_codec_boundaries() {
+    assert_eq!(video_status(&["h264", "h265"], None), Some(Status::Green));
+    assert_eq!(video_status(&["H.264"], None), Some(Status::Yellow));
+    assert_eq!(video_status(&["HEVC"], None), Some(Status::Green));
+    assert_eq!(video_status(&["AV1"], None), Some(Status::Red));
+    assert_eq!(video_status(&[], None), Some(Status::Red));
+}
+
+#[test]
+fn test_video_decode_fps_boundaries() {
+    assert_eq!(video_status(&["H.264", "HEVC"], Some(60.0)), Some(Status::Green));
+    assert_eq!(video_status(&["H.264", "HEVC"], Some(59.9)), Some(Status::Yellow));
+    assert_eq!(video_status(&["H.264", "HEVC"], Some(30.0)), Some(Status::Yellow));
+    assert_eq!(video_status(&["H.264", "HEVC"], Some(29.9)), Some(Status::Red));
+}
+
+fn audio_status(devices: &[&str], sample_rate: Option<u32>) -> Option<Status> {
+    report_with("audio.json", |results| {
+        let audio = results.audio.as_mut().unwrap();
+        audio.output_devices = devices.iter().map(|d| d.to_string()).collect();
+        audio.sample_rate = sample_rate;
+    }).audio_status
+}
+
+#[test]
+fn test_audio_fixture_is_green() {
+    let report = DiagnosticReport::from_results(fixture("audio.json"));
+    assert_eq!(report.audio_status, Some(Status::Green));
+}
+
+#[test]
+fn test_audio_boundaries() {
+    assert_eq!(audio_status(&["Speakers"], Some(48000)), Some(Status::Green));
+    assert_eq!(audio_status(&["Speakers"], None), Some(Status::Green));
+    assert_eq!(audio_status(&["Speakers"], Some(44100)), Some(Status::Yellow));
+    assert_eq!(audio_status(&[], Some(48000)), Some(Status::Red));
+}
+
+#[test]
+fn test_overall_status_is_worst_section() {
+    let mut results = fixture("bandwidth.json");
+    results.audio = fixture("audio.json").audio;
+    results.audio.as_mut().unwrap().sample_rate = Some(44100);
+
+    let report = DiagnosticReport::from_results(results);
+    assert_eq!(report.bandwidth_status, Some(Status::Green));
+    assert_eq!(report.audio_status, Some(Status::Yellow));
+    assert_eq!(report.overall_status, Status::Yellow);
+}
+
+#[test]
+fn test_link_capacity_boundaries() {
+    let link_status = |packet_pair_mbps: Option<f64>, fit_mbps: Option<f64>| report_with("link.json", |results| {
+        let link = results.link_capacity.as_mut().unwrap();
+        link.packet_pair_capacity_mbps = packet_pair_mbps;
+        link.estimated_capacity_mbps = fit_mbps;
+    }).link_status;
+
+    let report = report_with("link.json", |_| {});
+    assert_eq!(report.link_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    assert_eq!(link_status(Some(30.0), None), Some(Status::Green));
+    assert_eq!(link_status(Some(29.9), None), Some(Status::Yellow));
+    assert_eq!(link_status(Some(15.0), None), Some(Status::Yellow));
+    assert_eq!(link_status(Some(14.9), None), Some(Status::Red));
+    // Packet pair is preferred; the size fit is the fallback
+    assert_eq!(link_status(Some(100.0), Some(10.0)), Some(Status::Green));
+    assert_eq!(link_status(None, Some(10.0)), Some(Status::Red));
+    assert_eq!(link_status(None, None), None);
+}
+
+#[test]
+fn test_mtu_below_ethernet() {
+    let mtu_report = |max_payload_bytes: usize, fragmented_delivery: Option<bool>| report_with("mtu.json", |results| {
+        let mtu = results.mtu.as_mut().unwrap();
+        mtu.max_payload_bytes = max_payload_bytes;
+        mtu.fragmented_delivery = fragmented_delivery;
+    });
+
+    let report = report_with("mtu.json", |_| {});
+    assert_eq!(report.mtu_status, Some(Status::Green));
+    assert_eq!(report.raw_results.mtu.as_ref().unwrap().path_mtu(), 1500);
+
+    // PPPoE: 1492 byte path MTU, fragments still arrive
+    let report = mtu_report(1464, Some(true));
+    assert_eq!(report.mtu_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.contains("1492")));
+
+    // Fragments black-holed
+    assert_eq!(mtu_report(1464, Some(false)).mtu_status, Some(Status::Red));
+
+    // Without the Don't Fragment bit the result is not a path MTU
+    let report = report_with("mtu.json", |results| {
+        let mtu = results.mtu.as_mut().unwrap();
+        mtu.max_payload_bytes = 1000;
+        mtu.dont_fragment = false;
+    });
+    assert_eq!(report.mtu_status, None);
+}
+
+fn loss_report(lost_seqs: Vec<u32>) -> DiagnosticReport {
+    report_with("loss.json", |results| results.loss.as_mut().unwrap().lost_seqs = lost_seqs)
+}
+
+#[test]
+fn test_loss_corrupted_frame_boundaries() {
+    // The fixture loses one probe
+    let report = report_with("loss.json", |_| {});
+    let pattern = report.loss_pattern.as_ref().unwrap();
+    assert_eq!((pattern.frames, pattern.corrupted_frames), (120, 1));
+
+    // 120 frames of 8.33 probes at 60 fps: lose the first probe of each of the first few frames
+    let every_frame = |frames: u32| (0..frames).map(|f| (f * 25).div_ceil(3)).collect::<Vec<u32>>();
+    assert_eq!(loss_report(every_frame(1)).loss_status, Some(Status::Green));
+    assert_eq!(loss_report(every_frame(2)).loss_status, Some(Status::Yellow));
+    assert_eq!(loss_report(every_frame(5)).loss_status, Some(Status::Yellow));
+    assert_eq!(loss_report(every_frame(6)).loss_status, Some(Status::Red));
+}
+
+#[test]
+fn test_loss_bursts_are_called_out() {
+    // Same 3% loss rate: scattered corrupts far more frames than a few bursts
+    let scattered = loss_report((0..30).map(|i| i * 33).collect());
+    let bursts = loss_report((0..3).flat_map(|b| (0..10).map(move |i| b * 300 + i)).collect());
+
+    assert_eq!(scattered.loss_status, Some(Status::Red));
+    assert!(scattered.recommendations.iter().any(|r| r.contains("Scattered")));
+    assert_eq!(bursts.loss_pattern.as_ref().unwrap().mean_burst_length, 10.0);
+    assert_eq!(bursts.loss_status, Some(Status::Red));
+    assert!(bursts.recommendations.iter().any(|r| r.contains("bursts of 10.0")));
+
+    let results = TestResults {
+        loss: Some(LossResults { sent: 0, interval_ms: 2.0, payload_bytes: 1200, lost_seqs: vec![] }),
+        ..TestResults::default()
+    };
+    assert_eq!(DiagnosticReport::from_results(results).loss_status, None);
+}
+
+fn qos_report(edit: impl FnOnce(&mut Vec<TrafficClassResult>)) -> DiagnosticReport {
+    report_with("qos.json", |results| edit(&mut results.qos.as_mut().unwrap().classes))
+}
+
+#[test]
+fn test_qos_stripped_and_rewritten_markings() {
+    let report = qos_report(|_| {});
+    assert_eq!(report.qos_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    let stripped = qos_report(|classes| {
+        classes.iter_mut().for_each(|c| c.received_dscp = Some(0));
+    });
+    assert_eq!(stripped.qos_status, Some(Status::Yellow));
+    assert!(stripped.recommendations.iter().any(|r| r.contains("stripped") && r.contains("EF 46→0")));
+
+    let rewritten = qos_report(|classes| classes[3].received_dscp = Some(34));
+    assert_eq!(rewritten.qos_status, Some(Status::Yellow));
+    assert!(rewritten.recommendations.iter().any(|r| r.contains("rewritten")));
+
+    // A host that cannot read the TOS byte gives no verdict
+    let unknown = qos_report(|classes| classes.iter_mut().for_each(|c| c.received_dscp = None));
+    assert_eq!(unknown.qos_status, None);
+}
+
+#[test]
+fn test_qos_policed_class_boundaries() {
+    // 20 probes per class, best effort loses 1
+    let policed = |lost: usize| qos_report(|classes| {
+        classes[0].lost = 1;
+        classes[3].lost = lost;
+    }).qos_status;
+    assert_eq!(policed(2), Some(Status::Green));
+    assert_eq!(policed(3), Some(Status::Yellow));
+}
+
+#[test]
+fn test_qos_delayed_class_boundaries() {
+    // Best effort: median 3.05ms, P95 4.80ms
+    let delayed = |median: f64, p95: f64| qos_report(|classes| {
+        classes[3].median_rtt_ms = Some(median);
+        classes[3].p95_rtt_ms = Some(p95);
+    }).qos_status;
+    assert_eq!(delayed(8.0, 14.0), Some(Status::Green));
+    assert_eq!(delayed(8.2, 14.0), Some(Status::Yellow));
+    assert_eq!(delayed(3.0, 15.0), Some(Status::Yellow));
+}
+
+fn network_report(
+    client: impl FnOnce(&mut InterfaceInfo),
+    host: impl FnOnce(&mut InterfaceInfo),
+) -> DiagnosticReport {
+    report_with("network.json", |results| {
+        client(&mut results.client_network.as_mut().unwrap().interfaces[1]);
+        host(&mut results.host.as_mut().unwrap().network.interfaces[0]);
+    })
+}
+
+#[test]
+fn test_network_wifi_band_and_signal() {
+    let report = network_report(|_| {}, |_| {});
+    assert_eq!(report.network_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    let report = network_report(|client| {
+        let wifi = client.wifi.as_mut().unwrap();
+        wifi.frequency_mhz = Some(2437);
+        wifi.signal_dbm = Some(-72.0);
+    }, |_| {});
+    assert_eq!(report.network_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("Client is on 2.4 GHz with -72 dBm signal")));
+
+    let signal = |dbm: f64| network_report(|client| client.wifi.as_mut().unwrap().signal_dbm = Some(dbm), |_| {}).network_status;
+    assert_eq!(signal(-70.0), Some(Status::Green));
+    assert_eq!(signal(-71.0), Some(Status::Yellow));
+    assert_eq!(signal(-80.0), Some(Status::Yellow));
+    assert_eq!(signal(-81.0), Some(Status::Red));
+
+    let power_save = network_report(|client| client.wifi.as_mut().unwrap().power_save = Some(true), |_| {});
+    assert_eq!(power_save.network_status, Some(Status::Yellow));
+    assert!(power_save.recommendations.iter().any(|r| r.contains("iw dev wlan0 set power_save off")));
+}
+
+#[test]
+fn test_network_host_link() {
+    let speed = |mbps: u32| network_report(|_| {}, |host| host.speed_mbps = Some(mbps)).network_status;
+    assert_eq!(speed(1000), Some(Status::Green));
+    assert_eq!(speed(100), Some(Status::Yellow));
+    assert_eq!(speed(10), Some(Status::Red));
+
+    let report = report_with("network.json", |results| {
+        results.host.as_mut().unwrap().network = results.client_network.clone().unwrap();
+    });
+    assert_eq!(report.network_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("Host streams over WiFi")));
+
+    // Without an identified interface there is nothing to judge
+    let report = report_with("network.json", |results| {
+        results.client_network.as_mut().unwrap().active_interface = None;
+        results.host = None;
+    });
+    assert_eq!(report.network_status, None);
+}
+
+fn ports_report(edit: impl FnOnce(&mut Vec<PortCheckResult>)) -> DiagnosticReport {
+    report_with("ports.json", |results| edit(&mut results.ports.as_mut().unwrap().ports))
+}
+
+fn block(result: &mut PortCheckResult, failure: PortFailure) {
+    result.reachable = Some(false);
+    result.failure = Some(failure);
+}
+
+#[test]
+fn test_ports_firewall_recommendations() {
+    // Steam already holds two of the ports on this host
+    let report = ports_report(|_| {});
+    assert_eq!(report.ports_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    // Every UDP port dropped, TCP fine
+    let report = ports_report(|ports| {
+        ports.iter_mut().filter(|p| p.reachable.is_some() && p.port.port < 27036).for_each(|p| block(p, PortFailure::NoEcho));
+    });
+    assert_eq!(report.ports_status, Some(Status::Red));
+    assert_eq!(report.recommendations.len(), 1);
+    assert!(report.recommendations[0].starts_with("UDP ports 27031-27035 are silently dropped"));
+
+    // One TCP port refused
+    let report = ports_report(|ports| block(&mut ports[7], PortFailure::Refused));
+    assert_eq!(report.ports_status, Some(Status::Yellow));
+    assert!(report.recommendations[0].starts_with("TCP ports 27037 are refused"));
+
+    // Nothing testable
+    let report = ports_report(|ports| ports.iter_mut().for_each(|p| p.reachable = None));
+    assert_eq!(report.ports_status, None);
+}
+
+#[test]
+fn test_latency_tail_boundaries() {
+    // Two spikes in 100 samples put P99 on the spike without moving the average
+    let mut samples = vec![5.0; 98];
+    samples.extend([60.0, 60.0]);
+    let report = DiagnosticReport::from_results(TestResults {
+        latency: Some(LatencyResults::from_samples(samples, 0.0)),
+        ..TestResults::default()
+    });
+    assert_eq!(report.latency_status, Some(Status::Yellow));
+
+    let steady = LatencyResults::from_samples(vec![5.0; 100], 0.0);
+    assert_eq!(steady.p99_ms, 5.0);
+    assert_eq!(steady.spike_count, 0);
+    let report = DiagnosticReport::from_results(TestResults {
+        latency: Some(steady),
+        ..TestResults::default()
+    });
+    assert_eq!(report.latency_status, Some(Status::Green));
+}
+
+#[test]
+fn test_periodic_spikes_add_power_save_recommendation() {
+    // 5ms baseline with a 40ms spike every 2 seconds over 10 seconds of probes
+    let samples: Vec<f64> = (0..1000).map(|i| if i % 200 == 50 { 40.0 } else { 5.0 }).collect();
+    let offsets: Vec<f64> = (0..1000).map(|i| i as f64 * 10.0).collect();
+    let latency = LatencyResults::from_samples(samples, 0.0).with_send_offsets(offsets);
+
+    let report = DiagnosticReport::from_results(TestResults {
+        latency: Some(latency),
+        ..TestResults::default()
+    });
+
+    let periodic = report.periodic_spikes.as_ref().expect("no periodic spikes");
+    assert!((periodic.period_ms - 2000.0).abs() <= 20.0);
+    assert_eq!(report.latency_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.contains("power saving")));
+}
+
+fn latency_report(samples: Vec<f64>) -> DiagnosticReport {
+    DiagnosticReport::from_results(TestResults {
+        latency: Some(LatencyResults::from_samples(samples, 0.0)),
+        ..TestResults::default()
+    })
+}
+
+#[test]
+fn test_rfc3550_jitter_drives_latency_status() {
+    // Alternating RTTs: large interarrival jitter with a moderate average
+    let alternating: Vec<f64> = (0..100).map(|i| if i % 2 == 0 { 5.0 } else { 30.0 }).collect();
+    let report = latency_report(alternating);
+    let lat = report.raw_results.latency.as_ref().unwrap();
+    assert!(lat.rfc3550_jitter_ms > 10.0);
+    assert_eq!(report.latency_status, Some(Status::Yellow));
+
+    // A single step: high RTT standard deviation but almost no interarrival jitter
+    let step: Vec<f64> = (0..100).map(|i| if i < 50 { 5.0 } else { 30.0 }).collect();
+    let report = latency_report(step);
+    let lat = report.raw_results.latency.as_ref().unwrap();
+    assert!(lat.jitter_ms > 10.0);
+    assert!(lat.rfc3550_jitter_ms < 10.0);
+    assert_eq!(lat.pdv_p99_ms, 25.0);
+    assert_eq!(report.latency_status, Some(Status::Green));
+}
+
+#[test]
+fn test_short_bad_burst_is_not_averaged_away() {
+    // Perfect for 9 seconds, awful for the last second, 10 probes per second
+    let samples: Vec<f64> = (0..100).map(|i| if i >= 90 { 150.0 } else { 3.0 }).collect();
+    let offsets: Vec<f64> = (0..100).map(|i| i as f64 * 100.0).collect();
+    let latency = LatencyResults::from_samples(samples, 0.0).with_send_offsets(offsets);
+    let results = TestResults {
+        latency: Some(latency),
+        ..TestResults::default()
+    };
+
+    let report = DiagnosticReport::from_results(results.clone());
+    assert_eq!(report.latency_windows.len(), 10);
+    assert_eq!(report.latency_windows.iter().filter(|w| w.is_degraded()).count(), 1);
+    assert_eq!(report.latency_status, Some(Status::Red));
+
+    // Wider windows dilute the burst, which is why the window length is configurable
+    let config = ReportConfig { window_ms: 10_000.0, ..ReportConfig::default() };
+    let report = DiagnosticReport::from_results_with_config(results, &config);
+    assert_eq!(report.latency_windows.len(), 1);
+    assert_eq!(report.window_ms, 10_000.0);
+    assert_ne!(report.latency_status, Some(Status::Green));
+
+    let json = report.to_json().unwrap();
+    assert!(json.contains("\"latency_windows\""));
+}
+
+#[test]
+fn test_retransmissions_flag_latency() {
+    let before = TcpInfoSnapshot { rtt_ms: 3.0, rttvar_ms: 1.0, rto_ms: 203.0, snd_cwnd: 10, total_retrans: 2, lost: 0, ..TcpInfoSnapshot::default() };
+    let report = |total_retrans: u32| {
+        let after = TcpInfoSnapshot { total_retrans, ..before };
+        let latency = LatencyResults::from_samples(vec![3.0; 50], 0.0).with_tcp_info(before, after);
+        DiagnosticReport::from_results(TestResults { latency: Some(latency), ..TestResults::default() })
+    };
+
+    // Retransmits from before the test do not count
+    assert_eq!(report(2).latency_status, Some(Status::Green));
+
+    let retransmitted = report(3);
+    assert_eq!(retransmitted.latency_status, Some(Status::Yellow));
+    assert!(retransmitted.recommendations.iter().any(|r| r.contains("retransmitted 1 segment during")));
+}
+
+#[test]
+fn test_host_retransmissions_flag_latency() {
+    let before = TcpInfoSnapshot { rtt_ms: 3.0, rttvar_ms: 1.0, rto_ms: 203.0, snd_cwnd: 10, total_retrans: 0, lost: 0, ..TcpInfoSnapshot::default() };
+    let report = |total_retrans: u32| report_with("network.json", |results| {
+        let after = TcpInfoSnapshot { total_retrans, ..before };
+        results.host.as_mut().unwrap().tcp_info = Some(TcpInfoResults { before, after });
+        results.latency = Some(LatencyResults::from_samples(vec![3.0; 50], 0.0));
+    });
+
+    assert_eq!(report(0).latency_status, Some(Status::Green));
+
+    let retransmitted = report(4);
+    assert_eq!(retransmitted.latency_status, Some(Status::Yellow));
+    assert!(retransmitted.recommendations.iter().any(|r| r.starts_with("The host retransmitted 4 segments")));
+}
+
+#[test]
+fn test_stack_overhead_boundary() {
+    let report = |app_ms: f64| {
+        let kernel_rtt = KernelRttResults { sent: 20, app_rtt_ms: vec![app_ms; 20], kernel_rtt_ms: vec![0.2; 20] };
+        DiagnosticReport::from_results(TestResults { kernel_rtt: Some(kernel_rtt), ..TestResults::default() })
+    };
+
+    assert!(report(1.2).recommendations.is_empty());
+    let slow = report(1.3);
+    assert!(slow.recommendations.iter().any(|r| r.starts_with("Local stack overhead reaches 1.10ms")));
+    // Scheduling noise is local, not a network problem
+    assert_eq!(slow.overall_status, Status::Green);
+}
+
+fn system_report(p99_us: f64, max_us: f64) -> DiagnosticReport {
+    // 1000 wake-ups: 980 at 50us, 19 at the P99 value and one at the maximum
+    let mut latencies: Vec<f64> = vec![50.0; 980];
+    latencies.extend(std::iter::repeat_n(p99_us, 19));
+    latencies.push(max_us);
+    let system = SystemResults::from_samples(1000.0, &latencies);
+    DiagnosticReport::from_results(TestResults { system: Some(system), ..TestResults::default() })
+}
+
+#[test]
+fn test_scheduling_latency_boundaries() {
+    assert_eq!(system_report(1000.0, 5000.0).system_status, Some(Status::Green));
+    assert_eq!(system_report(1001.0, 5000.0).system_status, Some(Status::Yellow));
+    assert_eq!(system_report(500.0, 5001.0).system_status, Some(Status::Yellow));
+    assert_eq!(system_report(4001.0, 5000.0).system_status, Some(Status::Red));
+    assert_eq!(system_report(500.0, 16_668.0).system_status, Some(Status::Red));
+
+    let report = system_report(500.0, 20_000.0);
+    assert!(report.recommendations.iter().any(|r| r.contains("not the network")));
+    let histogram = &report.raw_results.system.as_ref().unwrap().histogram;
+    assert_eq!(histogram[2].count, 980);
+    assert_eq!(histogram.last().unwrap().count, 1);
+}
+
+#[test]
+fn test_local_delay_is_explained_once() {
+    let mut results = system_report(5000.0, 10_000.0).raw_results;
+    results.latency = Some(LatencyResults::from_samples(vec![60.0; 50], 0.0));
+    let report = DiagnosticReport::from_results(results);
+    assert_eq!(report.latency_status, Some(Status::Red));
+    assert_eq!(report.recommendations.iter().filter(|r| r.contains("not the network")).count(), 1);
+}
+
+#[test]
+fn test_environments_are_informational_and_exported() {
+    let report = DiagnosticReport::from_results(fixture("environment.json"));
+    assert_eq!(report.overall_status, Status::Green);
+    assert!(report.recommendations.is_empty());
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!(json["raw_results"]["client_environment"]["steam_deck"], true);
+    assert_eq!(json["raw_results"]["host"]["environment"]["gpus"][0]["driver"], "nvidia");
+}
+
+#[test]
+fn test_spikes_attributed_to_host_cpu_pressure() {
+    let start_ms = 1_700_000_000_000.0;
+    // 14 spikes every 200ms; the host's CPU stalls during 12 of them
+    let samples: Vec<f64> = (0..280).map(|i| if i % 20 == 5 { 40.0 } else { 4.0 }).collect();
+    let offsets = (0..280).map(|i| i as f64 * 10.0).collect();
+    // The host's clock runs 2s ahead of the client's
+    let latency = LatencyResults::from_samples(samples, 0.0)
+        .with_send_offsets(offsets)
+        .with_start_time(start_ms)
+        .with_host_clock_offset(2000.0);
+    let pressure = PressureResults {
+        interval_ms: 100.0,
+        samples: (1..=29).map(|i| PressureSample {
+            timestamp_ms: start_ms + i as f64 * 100.0,
+            cpu_busy_percent: Some(90.0),
+            cpu_some_percent: Some(if i % 2 == 1 && i < 24 { 60.0 } else { 0.0 }),
+            memory_some_percent: Some(0.0),
+            io_some_percent: Some(0.0),
+        }).collect(),
+    };
+
+    let mut results = fixture("network.json");
+    results.latency = Some(latency);
+    let mut host_pressure = pressure.clone();
+    host_pressure.samples.iter_mut().for_each(|s| s.timestamp_ms += 2000.0);
+    results.host.as_mut().unwrap().pressure = Some(host_pressure);
+    let report = DiagnosticReport::from_results(results.clone());
+    assert_eq!(report.pressure_correlations.len(), 1);
+    assert!(report.recommendations.iter().any(|r| r.starts_with("12 of 14 latency spikes coincided with host CPU pressure")));
+
+    // The same stalls on the client are blamed on the client
+    results.host.as_mut().unwrap().pressure = None;
+    results.client_pressure = Some(pressure);
+    let report = DiagnosticReport::from_results(results);
+    assert_eq!(report.pressure_correlations[0].side, Side::Client);
+}
+
+#[test]
+fn test_clock_events_warn_and_exclude_samples() {
+    let start = 1_700_000_000_000.0;
+    let mut samples = vec![5.0; 50];
+    samples[20] = 12_000.0;
+    let offsets = (0..50).map(|i| i as f64 * 100.0).collect();
+    let suspend = ClockEvent { kind: ClockEventKind::Suspend, from_ms: start + 2050.0, to_ms: start + 2150.0, magnitude_ms: 12_000.0 };
+    let step = ClockEvent { kind: ClockEventKind::ClockStep, from_ms: start + 4000.0, to_ms: start + 4100.0, magnitude_ms: -1500.0 };
+
+    let mut results = fixture("network.json");
+    results.latency = Some(LatencyResults::from_samples(samples, 0.0)
+        .with_send_offsets(offsets)
+        .with_start_time(start)
+        .with_clock_events(&[suspend.clone(), step.clone()]));
+    results.client_clock_events = vec![suspend];
+    results.host.as_mut().unwrap().clock_events = vec![step];
+    let report = DiagnosticReport::from_results(results);
+
+    // The garbage sample no longer drives the latency status
+    assert_eq!(report.latency_status, Some(Status::Green));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("The client was suspended for 12.0s")));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("The host's wall clock was stepped by -1.5s")));
+}
+
+#[test]
+fn test_background_download_flags_traffic() {
+    let report = DiagnosticReport::from_results(fixture("traffic.json"));
+    assert_eq!(report.traffic_status, Some(Status::Yellow));
+    assert_eq!(report.overall_status, Status::Yellow);
+
+    // The client's own probes are subtracted; the download during and after the loss test is not
+    let client = &report.background_traffic[0];
+    assert_eq!((client.side, client.interface.as_str()), (Side::Client, "wlan0"));
+    assert_eq!((client.rx_bytes, client.tx_bytes), (9_114_000, 52_000));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("The client's wlan0 carried 9.2 MB of other traffic, up to 26.5 Mbit/s during the loss test")));
+
+    // The host saw only a trickle besides the echoes, and its idle docker0 is left out
+    let host: Vec<_> = report.background_traffic.iter().filter(|f| f.side == Side::Host).collect();
+    assert_eq!(host.len(), 1);
+    assert!(!host[0].is_busy());
+}
+
+#[test]
+fn test_quiet_network_is_green() {
+    let mut results = fixture("traffic.json");
+    let phases = &mut results.client_traffic.as_mut().unwrap().phases;
+    phases.retain(|phase| phase.name != "after");
+    phases[2].interfaces[0].rx_bytes = 1_300_000;
+    let report = DiagnosticReport::from_results(results);
+    assert_eq!(report.traffic_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    // Without a traffic check there is no verdict
+    assert_eq!(DiagnosticReport::from_results(fixture("network.json")).traffic_status, None);
+}
+
+fn power_report(states: Vec<PowerState>, wifi_power_save: bool) -> DiagnosticReport {
+    let mut results = fixture("network.json");
+    let client = results.client_network.as_mut().unwrap();
+    client.interfaces[1].wifi.as_mut().unwrap().power_save = Some(wifi_power_save);
+    results.client_power = Some(PowerResults {
+        interval_ms: 1000.0,
+        samples: states.into_iter().enumerate()
+            .map(|(i, state)| PowerSample { timestamp_ms: i as f64 * 1000.0, state })
+            .collect(),
+    });
+    DiagnosticReport::from_results(results)
+}
+
+fn deck_state(battery_percent: f64, ac_online: bool, celsius: f64) -> PowerState {
+    PowerState {
+        ac_online: Some(ac_online),
+        battery_percent: Some(battery_percent),
+        battery_status: Some(if ac_online { "Charging" } else { "Discharging" }.to_string()),
+        cpu_freq_mhz: Some(2800.0),
+        cpu_max_freq_mhz: Some(3500.0),
+        platform_profile: None,
+        temperatures: vec![Temperature { sensor: "amdgpu edge".to_string(), celsius, throttle_celsius: Some(95.0) }],
+    }
+}
+
+#[test]
+fn test_battery_with_wifi_power_save() {
+    let report = power_report(vec![deck_state(64.0, false, 60.0), deck_state(61.0, false, 62.0)], true);
+    assert_eq!(report.power_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("Client was on battery (61% charge) with WiFi power saving enabled.")));
+
+    // Plugged in and cool, nothing to say about power
+    let report = power_report(vec![deck_state(64.0, true, 60.0)], true);
+    assert_eq!(report.power_status, Some(Status::Green));
+    assert!(!report.recommendations.iter().any(|r| r.starts_with("Client was on battery")));
+}
+
+#[test]
+fn test_thermal_throttling_and_low_power_profile() {
+    let mut hot = deck_state(80.0, true, 97.0);
+    hot.platform_profile = Some("low-power".to_string());
+    let report = power_report(vec![deck_state(80.0, true, 70.0), hot], false);
+
+    assert_eq!(report.power_status, Some(Status::Yellow));
+    assert_eq!(report.overall_status, Status::Yellow);
+    assert!(report.recommendations.iter().any(|r| r.starts_with("Client reached 97°C on amdgpu edge, at its 95°C throttling point.")));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("Client uses the low-power platform profile")));
+
+    // Nothing sampled, no verdict
+    assert_eq!(DiagnosticReport::from_results(fixture("network.json")).power_status, None);
+}
+
+fn codecs(decode: &[&str], encode: &[&str]) -> VideoResults {
+    let profile = |codec: &str, encode: bool| VideoProfile {
+        codec: codec.to_string(),
+        profile: format!("VAProfile{}", codec),
+        encode,
+        max_width: Some(4096),
+        max_height: Some(2304),
+    };
+    VideoResults {
+        supported_codecs: decode.iter().map(|c| c.to_string()).collect(),
+        profiles: decode.iter().map(|c| profile(c, false)).chain(encode.iter().map(|c| profile(c, true))).collect(),
+        ..VideoResults::default()
+    }
+}
+
+fn pairing_report(client: VideoResults, host: VideoResults) -> DiagnosticReport {
+    report_with("network.json", |results| {
+        results.video = Some(client);
+        results.host.as_mut().unwrap().video = Some(host);
+    })
+}
+
+#[test]
+fn test_host_encoder_and_client_decoder_share_hevc() {
+    let report = pairing_report(codecs(&["h264", "hevc", "av1"], &["h264", "hevc"]), codecs(&["h264", "hevc"], &["h264", "hevc"]));
+    assert_eq!(report.video_status, Some(Status::Green));
+    assert!(report.recommendations.is_empty());
+
+    // NVENC is invisible to VA-API, so a host without encoders is no evidence
+    let report = pairing_report(codecs(&["h264", "hevc"], &[]), codecs(&["h264", "hevc", "av1"], &[]));
+    assert_eq!(report.video_status, Some(Status::Green));
+}
+
+#[test]
+fn test_host_encoding_only_h264_wastes_client_decoders() {
+    let report = pairing_report(codecs(&["h264", "hevc", "av1"], &[]), codecs(&["h264"], &["h264"]));
+    assert_eq!(report.video_status, Some(Status::Yellow));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("The client decodes AV1, HEVC in hardware but the host encodes only H.264")));
+}
+
+#[test]
+fn test_no_codec_in_common() {
+    let report = pairing_report(codecs(&["h264"], &[]), codecs(&["hevc", "av1"], &["hevc", "av1"]));
+    assert_eq!(report.video_status, Some(Status::Red));
+    assert!(report.recommendations.iter().any(|r| r.starts_with("The host encodes AV1, HEVC in hardware and the client decodes H.264; with no codec in common")));
+}